@@ -12,6 +12,8 @@ pub enum ArgsResult {
     Proceed,
     ProceedWithScriptPath(PathBuf),
     ProceedWithDefaultConfig,
+    JsonFormat,
+    Run(PathBuf),
 }
 
 pub fn handle_args(args: &[String], options: &mut Options) -> ArgsResult {
@@ -24,7 +26,9 @@ pub fn handle_args(args: &[String], options: &mut Options) -> ArgsResult {
         irust {{path_to_rust_file}} will start IRust with the file loaded in the repl
         --help => shows this message
         --reset-config => reset IRust configuration to default
-        --default-config => uses the default configuration for this run (it will not be saved)",
+        --default-config => uses the default configuration for this run (it will not be saved)
+        --format json => evaluate each line of stdin and print the results as JSON records (one per line)
+        --run {{path_to_rust_file}} => evaluate the file statement by statement, print the values and exit (with an error status if a statement fails)",
                 VERSION,
                 Options::config_path()
                     .map(|p| p.to_string_lossy().to_string())
@@ -43,6 +47,20 @@ pub fn handle_args(args: &[String], options: &mut Options) -> ArgsResult {
             ArgsResult::Proceed
         }
         "--default-config" => ArgsResult::ProceedWithDefaultConfig,
+        "--format" => match args.get(1).map(String::as_str) {
+            Some("json") => ArgsResult::JsonFormat,
+            _ => {
+                eprintln!("Unknown format (available: json)");
+                ArgsResult::Exit
+            }
+        },
+        "--run" => match args.get(1) {
+            Some(path) => ArgsResult::Run(PathBuf::from(path)),
+            None => {
+                eprintln!("No file specified");
+                ArgsResult::Exit
+            }
+        },
         maybe_path => {
             let path = Path::new(&maybe_path);
             if path.exists() {
@@ -0,0 +1,139 @@
+// `irust --run <file>`: evaluate the top level statements of a file in order and print the values
+// This is the script mode of IRust, the exit status is an error if a statement fails
+use crate::eval::{prepare, Input};
+use crate::irust::{options::Options, Result};
+use crate::utils::{compile_errors, is_unit_macro_call, split_statements};
+use irust_repl::{CompileMode, EvalConfig, Repl};
+use std::io::Write;
+use std::path::Path;
+
+/// Printed after the value of an expression that was evaluated successfully
+const END_MARK: &str = "IRUST_RUN_END";
+
+/// Returns false if a statement failed to compile or to run
+pub fn run(options: &Options, path: &Path) -> Result<bool> {
+    let code = crate::utils::read_code(path)?;
+    let mut repl = crate::eval::configured_repl(options)?;
+    let success = run_code(
+        &mut repl,
+        &code,
+        options,
+        &mut std::io::stdout(),
+        &mut std::io::stderr(),
+    );
+    repl.cargo.delete_project()?;
+    success
+}
+
+/// Evaluate `code` statement by statement, it stops at the first error (printed to `stderr`)
+fn run_code(
+    repl: &mut Repl,
+    code: &str,
+    options: &Options,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<bool> {
+    for statement in split_statements(code) {
+        // `println!(..);` is evaluated once instead of being inserted in the repl code
+        let unit_macro = is_unit_macro_call(statement.trim_end_matches(';'));
+        let statement = if unit_macro {
+            statement.trim_end_matches(';')
+        } else {
+            statement
+        };
+        let statement = match prepare(statement.to_string(), options, repl)? {
+            None | Some(Input::Comment { doc: true, .. }) => continue,
+            Some(Input::Comment { code, .. }) => {
+                repl.insert(code);
+                continue;
+            }
+            Some(Input::Statement(statement)) => {
+                // `eval_check` output is colored
+                let (toolchain, cargo) = (repl.toolchain(), repl.cargo.clone());
+                let (status, output) = repl.eval_in_tmp_repl(statement.clone(), |_| {
+                    Ok(cargo.cargo_build_output(false, false, toolchain)?)
+                })?;
+                if !status.success() {
+                    writeln!(stderr, "{}", compile_errors(&output))?;
+                    return Ok(false);
+                }
+                repl.insert(statement);
+                continue;
+            }
+            Some(Input::Expression { code, .. }) => code,
+        };
+
+        // The `()` result of `println!(..)` is noise after its output
+        // The end mark is only printed if the evaluation didn't fail at runtime (exp: a panic)
+        let evaluator = if unit_macro {
+            [
+                "{\n".to_string(),
+                format!("\n}};\nprintln!(\"\\n{END_MARK}\");"),
+            ]
+        } else {
+            [
+                "println!(\"{:?}\", {\n".to_string(),
+                format!("\n}});\nprintln!(\"\\n{END_MARK}\");"),
+            ]
+        };
+        let result = repl.eval_with_configuration(EvalConfig {
+            input: statement,
+            interactive_function: None,
+            color: false,
+            evaluator: &evaluator,
+            compile_mode: CompileMode::Debug,
+        })?;
+        // The status is the build status
+        let output = match result.output.rsplit_once(END_MARK) {
+            Some((output, _)) if result.status.success() => output.trim_end_matches('\n'),
+            _ => {
+                writeln!(stderr, "{}", compile_errors(&result.output))?;
+                return Ok(false);
+            }
+        };
+        if !output.is_empty() {
+            writeln!(stdout, "{output}")?;
+        }
+    }
+    Ok(true)
+}
+
+#[test]
+fn run_code_test() {
+    let options = Options::default();
+    let mut repl = Repl::default();
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let code = "\
+fn double(a: u8) -> u8 {
+    a * 2
+}
+let a = double(2);
+println!(\"a is {a}\");
+a + 1";
+    assert!(run_code(&mut repl, code, &options, &mut stdout, &mut stderr).unwrap());
+    assert_eq!(String::from_utf8(stdout).unwrap(), "a is 4\n5\n");
+    assert!(stderr.is_empty());
+
+    // the inputs are prepared like in the interactive session
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let code = "// comment\n\"4\".parse::<u8>()?";
+    assert!(run_code(&mut repl, code, &options, &mut stdout, &mut stderr).unwrap());
+    assert!(run_code(&mut repl, "return a", &options, &mut stdout, &mut stderr).unwrap());
+    assert_eq!(String::from_utf8(stdout).unwrap(), "4\n4\n");
+
+    // it stops at the first error
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let code = "let b: u8 = \"b\";\n1 + 1";
+    assert!(!run_code(&mut repl, code, &options, &mut stdout, &mut stderr).unwrap());
+    assert!(stdout.is_empty());
+    assert!(String::from_utf8(stderr)
+        .unwrap()
+        .starts_with("error[E0308]: mismatched types"));
+
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let code = "let a: u8 = \"200\".parse().unwrap();\ndouble(a)";
+    assert!(!run_code(&mut repl, code, &options, &mut stdout, &mut stderr).unwrap());
+    assert!(String::from_utf8(stderr)
+        .unwrap()
+        .contains("attempt to multiply with overflow"));
+}
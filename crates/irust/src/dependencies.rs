@@ -46,10 +46,7 @@ pub fn warn_about_opt_deps(options: &mut Options) {
                     "{}",
                     "rustup is not installed.\nrustup is required to install rustfmt".red()
                 );
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "rustup is not installed",
-                ));
+                return Err(io::Error::other("rustup is not installed"));
             }
             let cmd = ["rustup", "component", "add", "rustfmt"];
             println!("{}", format!("Running: {cmd:?}").magenta());
@@ -146,7 +143,7 @@ pub fn warn_about_opt_deps(options: &mut Options) {
     println!("{}", "Everything is set!".green());
 }
 
-fn dep_installed(d: &str) -> bool {
+pub fn dep_installed(d: &str) -> bool {
     if let Err(e) = std::process::Command::new(d)
         .arg("-h")
         .stdout(std::process::Stdio::null())
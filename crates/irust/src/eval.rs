@@ -0,0 +1,131 @@
+// The evaluation steps shared by the interactive session, `irust --run` and `irust --format json`:
+// the repl configured with the options and the preparation of an input before it's inserted or evaluated
+use crate::irust::{options::Options, Result};
+use crate::utils::{
+    comment_only, has_question_mark, has_top_level_await, is_statement, top_level_control_flow,
+    wrap_question_mark,
+};
+use irust_repl::{AsyncRuntime, Executor, MainResult, Repl};
+
+/// A repl created and configured with the options, like the one of the interactive session
+pub fn configured_repl(options: &Options) -> Result<Repl> {
+    let mut repl = Repl::new(
+        options.toolchain,
+        options.executor,
+        options.main_result,
+        options.edition,
+        // prelude dir
+        (|| Some(dirs::data_dir()?.join("irust")))(),
+    )?;
+    configure(&mut repl, options);
+    Ok(repl)
+}
+
+/// Apply the options that the repl keeps across resets
+pub fn configure(repl: &mut Repl, options: &Options) {
+    repl.set_type_aliases(options.type_aliases.clone());
+    repl.set_track_alloc(options.track_alloc);
+    repl.set_stack_size(options.stack_size);
+    // Ignore sandbox dir creation error, it will be reported on evaluation
+    let _ = repl.set_sandbox(options.sandbox);
+}
+
+/// How an input is handled
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    /// Only comments, they're kept in the repl code unless one is a doc comment (it would document nothing)
+    Comment { code: String, doc: bool },
+    /// Inserted in the repl code
+    Statement(String),
+    /// Evaluated, `is_async` is set if it uses `.await` outside of an async block
+    Expression { code: String, is_async: bool },
+}
+
+/// Classify the input, `None` if it's empty
+/// `return x` is evaluated as `x`, and the `?` and `.await` of an expression are wrapped when main can't handle them
+pub fn prepare(input: String, options: &Options, repl: &Repl) -> Result<Option<Input>> {
+    let input = match top_level_control_flow(&input) {
+        Some(expression) => expression?,
+        None => input,
+    };
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    if let Some(doc) = comment_only(trimmed) {
+        return Ok(Some(Input::Comment { code: input, doc }));
+    }
+    if is_statement(trimmed, options.auto_insert_semicolon) {
+        return Ok(Some(Input::Statement(input)));
+    }
+
+    // `.await` inside an async block doesn't need a runtime
+    let is_async = has_top_level_await(&input);
+    // With a unit main, the evaluated expression is not inside a function that returns a `Result`
+    let code = if matches!(options.main_result, MainResult::Unit) && has_question_mark(&input) {
+        wrap_question_mark(&input, is_async)
+    } else {
+        input
+    };
+    // `.await` can be used directly with an async executor, otherwise wrap it with the selected runtime
+    let code = if matches!(options.executor, Executor::Sync) && is_async {
+        check_async_runtime(repl, options.async_runtime)?;
+        options.async_runtime.wrap(&code)
+    } else {
+        code
+    };
+    Ok(Some(Input::Expression { code, is_async }))
+}
+
+pub fn check_async_runtime(repl: &Repl, runtime: AsyncRuntime) -> Result<()> {
+    if !repl.cargo.has_dependency(runtime.crate_name())? {
+        return Err(format!(
+            "async runtime `{runtime}` requires the `{0}` crate, add it with `:add {0}`",
+            runtime.crate_name()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[test]
+fn prepare_test() {
+    let options = Options::default();
+    let mut repl = Repl::default();
+    let prepare = |input: &str| prepare(input.to_string(), &options, &repl).unwrap();
+
+    assert_eq!(prepare("  "), None);
+    assert_eq!(
+        prepare("/// a"),
+        Some(Input::Comment {
+            code: "/// a".into(),
+            doc: true
+        })
+    );
+    assert_eq!(
+        prepare("let a = 1;"),
+        Some(Input::Statement("let a = 1;".into()))
+    );
+    assert_eq!(
+        prepare("return 5;"),
+        Some(Input::Expression {
+            code: "5".into(),
+            is_async: false
+        })
+    );
+    assert_eq!(
+        prepare("\"4\".parse::<u8>()?"),
+        Some(Input::Expression {
+            code: wrap_question_mark("\"4\".parse::<u8>()?", false),
+            is_async: false
+        })
+    );
+    // the runtime crate is missing
+    assert!(crate::eval::prepare("f().await".into(), &options, &repl).is_err());
+
+    // the options are applied to the repl
+    let mut options = Options::default();
+    options.type_aliases = vec!["type Num = u8;".into()];
+    configure(&mut repl, &options);
+    assert_eq!(repl.eval("Num::MAX").unwrap().output, "255");
+}
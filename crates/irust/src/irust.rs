@@ -39,20 +39,51 @@ pub struct IRust {
     history: History,
     completer: Option<Completer>,
     script_mg: Option<Box<dyn Script>>,
+    /// Raw output of the last compiler error, shown by `:errors --full`
+    last_error: Option<String>,
+    /// Set by `:multiline`, the next input is only submitted with Alt+Enter
+    multiline: bool,
+    /// Last submitted input, run again by `:retry`
+    last_input: Option<String>,
+    /// Bindings set with `:keep`, `:reset --keep` doesn't remove them
+    kept_bindings: std::collections::BTreeSet<String>,
+    /// Shown by `:prof-startup`
+    startup_timings: StartupTimings,
+    session_start: std::time::Instant,
+    /// Added with `:note`, with the session time when they were added
+    notes: Vec<(std::time::Duration, String)>,
+    /// Inputs submitted in this session, shown by `:history`
+    session_inputs: Vec<String>,
+    /// The exit status of the last `::` shell command, shown by `:last-status`
+    last_status: Option<std::process::ExitStatus>,
+    /// The previous working directories, pushed by `:cd` and popped by `:popd`
+    dir_stack: Vec<std::path::PathBuf>,
+    /// The evaluation results of this session, shown by `:out`
+    eval_outputs: Vec<String>,
+    /// The file watched with `:watch`, with its change notifications
+    watch: Option<WatchedFile>,
+    /// The types found by `:type` for the repl state with this `state_hash`
+    type_cache: (u64, std::collections::HashMap<String, String>),
+    /// The lines entered since `:paste`, evaluated together on `:end`
+    paste_lines: Option<Vec<String>>,
+    /// The `:prompt` template, rendered each time the prompt is updated
+    prompt_template: Option<String>,
+}
+
+/// How long the startup steps took
+#[derive(Default)]
+struct StartupTimings {
+    config_load: std::time::Duration,
+    scaffold: std::time::Duration,
+    init: std::time::Duration,
 }
 
 impl IRust {
     pub fn new(options: Options) -> Self {
+        let now = std::time::Instant::now();
         // Make sure to call Repl::new at the start so it can set `irust-repl` dir, which might be used by others (ScriptManager)
-        let repl = Repl::new(
-            options.toolchain,
-            options.executor,
-            options.main_result,
-            options.edition,
-            // prelude dir
-            (|| Some(dirs::data_dir()?.join("irust")))(),
-        )
-        .expect("Could not create repl");
+        let repl = crate::eval::configured_repl(&options).expect("Could not create repl");
+        let scaffold = now.elapsed();
 
         let mut global_variables = GlobalVariables::new();
 
@@ -70,7 +101,8 @@ impl IRust {
 
         global_variables.prompt_len = prompt.chars().count();
 
-        let printer = Printer::new(std::io::stdout(), prompt);
+        let mut printer = Printer::new(std::io::stdout(), prompt);
+        printer.pager = options.pager.clone();
 
         let completer = if options.enable_rust_analyzer {
             Completer::start_ra(
@@ -100,9 +132,33 @@ impl IRust {
             history,
             completer,
             script_mg,
+            last_error: None,
+            multiline: false,
+            last_input: None,
+            kept_bindings: Default::default(),
+            session_inputs: vec![],
+            last_status: None,
+            dir_stack: vec![],
+            eval_outputs: vec![],
+            watch: None,
+            type_cache: Default::default(),
+            paste_lines: None,
+            prompt_template: None,
+            startup_timings: StartupTimings {
+                config_load: Default::default(),
+                scaffold,
+                init: now.elapsed() - scaffold,
+            },
+            session_start: now,
+            notes: vec![],
         }
     }
 
+    /// The config is loaded before IRust is created
+    pub fn set_config_load_time(&mut self, config_load: std::time::Duration) {
+        self.startup_timings.config_load = config_load;
+    }
+
     fn prepare(&mut self) -> Result<()> {
         // title is optional
         let title = if let Some(title) = self.trigger_set_title_hook() {
@@ -111,6 +167,8 @@ impl IRust {
             format!("IRust: {}", self.global_variables.get_cwd().display())
         };
         self.printer.writer.raw.set_title(&title)?;
+        // Pastes are received as one event instead of key presses (not supported by every terminal)
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::event::EnableBracketedPaste);
         self.welcome()?;
         self.printer.print_prompt_if_set()?;
 
@@ -143,8 +201,8 @@ impl IRust {
             // some events that have an inner input loop like ctrl-r/ ctrl-d require flushing inside their respective handler function
             std::io::Write::flush(&mut self.printer.writer.raw)?;
 
-            let evs = if let Some(ref mut server) = server {
-                read_from_net_and_stdin(server)
+            let evs = if server.is_some() || self.watch.is_some() {
+                self.poll_events(server.as_mut())?
             } else {
                 vec![crossterm::event::read()]
             };
@@ -163,6 +221,31 @@ impl IRust {
         }
     }
 
+    /// Wait for the next input events, from the terminal or the local server,
+    /// the watched file is loaded again meanwhile if it changes while the input is empty
+    fn poll_events(
+        &mut self,
+        server: Option<&mut mpsc::Receiver<String>>,
+    ) -> Result<Vec<std::io::Result<Event>>> {
+        loop {
+            if let Some(input) = server.as_ref().and_then(|server| server.try_recv().ok()) {
+                return Ok(net_input_events(&input));
+            }
+            let changed = self.buffer.is_empty()
+                && self
+                    .watch
+                    .as_ref()
+                    .is_some_and(|watched| watched.changes.try_iter().count() > 0);
+            if changed {
+                self.rerun_watched()?;
+                std::io::Write::flush(&mut self.printer.writer.raw)?;
+            }
+            if let Ok(true) = crossterm::event::poll(Duration::from_millis(100)) {
+                return Ok(vec![crossterm::event::read()]);
+            }
+        }
+    }
+
     fn handle_input_event(&mut self, ev: Event) -> Result<()> {
         // update_script_state before anything else
         self.update_script_state();
@@ -202,6 +285,17 @@ impl IRust {
                     modifiers: KeyModifiers::CONTROL,
                     ..
                 } => self.execute(Command::HandleCtrlE)?,
+                KeyEvent {
+                    code: KeyCode::Enter,
+                    modifiers,
+                    ..
+                } if self.multiline => {
+                    if crate::utils::multiline_submits(modifiers) {
+                        self.execute(Command::HandleEnter(true))?
+                    } else {
+                        self.execute(Command::HandleAltEnter)?
+                    }
+                }
                 KeyEvent {
                     code: KeyCode::Enter,
                     modifiers: KeyModifiers::ALT,
@@ -335,14 +429,20 @@ impl IRust {
                     }
                 }
             },
-            Event::Paste(data) => data.chars().try_for_each(|c| {
-                self.handle_input_event(Event::Key(KeyEvent {
-                    code: KeyCode::Char(c),
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    state: KeyEventState::NONE,
-                }))
-            })?,
+            // A paste is inserted as one input, its new lines don't submit it
+            Event::Paste(data) => data
+                .replace("\r\n", "\n")
+                .replace('\r', "\n")
+                .chars()
+                .try_for_each(|c| match c {
+                    '\n' => self.execute(Command::HandleAltEnter),
+                    c => self.handle_input_event(Event::Key(KeyEvent {
+                        code: KeyCode::Char(c),
+                        modifiers: KeyModifiers::NONE,
+                        kind: KeyEventKind::Press,
+                        state: KeyEventState::NONE,
+                    })),
+                })?,
             Event::FocusGained => (),
             Event::FocusLost => (),
         }
@@ -367,37 +467,63 @@ fn start_server(adress: SocketAddrV4) -> Result<mpsc::Receiver<String>> {
     });
     Ok(rx)
 }
-fn read_from_net_and_stdin(server: &mut mpsc::Receiver<String>) -> Vec<std::io::Result<Event>> {
-    loop {
-        if let Ok(e) = server.try_recv() {
-            return e
-                .chars()
-                .map(|c| {
-                    Ok(Event::Key(KeyEvent {
-                        code: KeyCode::Char(c),
-                        modifiers: KeyModifiers::NONE,
-                        kind: KeyEventKind::Press,
-                        state: KeyEventState::NONE,
-                    }))
-                })
-                .chain(std::iter::once(Ok(Event::Key(KeyEvent {
-                    code: KeyCode::Enter,
-                    modifiers: KeyModifiers::NONE,
-                    kind: KeyEventKind::Press,
-                    state: KeyEventState::NONE,
-                }))))
-                .collect();
-        }
-        if let Ok(true) = crossterm::event::poll(Duration::from_millis(100)) {
-            return vec![crossterm::event::read()];
+/// The key events of an input received by the local server, followed by enter
+fn net_input_events(input: &str) -> Vec<std::io::Result<Event>> {
+    input
+        .chars()
+        .map(|c| {
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            }))
+        })
+        .chain(std::iter::once(Ok(Event::Key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }))))
+        .collect()
+}
+
+/// The file watched with `:watch`, dropping it stops watching
+struct WatchedFile {
+    path: std::path::PathBuf,
+    changes: mpsc::Receiver<()>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+/// Notifies each modification of `path`, used by `:watch`
+/// Its directory is watched since editors often save by replacing the file
+fn watch_file(path: std::path::PathBuf) -> Result<WatchedFile> {
+    use notify::Watcher;
+
+    let (tx, changes) = mpsc::channel();
+    let watched = path.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else { return };
+        if (event.kind.is_create() || event.kind.is_modify()) && event.paths.contains(&watched) {
+            let _ = tx.send(());
         }
-    }
+    })?;
+    watcher.watch(
+        path.parent().ok_or("file must have a parent directory")?,
+        notify::RecursiveMode::NonRecursive,
+    )?;
+    Ok(WatchedFile {
+        path,
+        changes,
+        _watcher: watcher,
+    })
 }
 
 impl Drop for IRust {
     fn drop(&mut self) {
         // ignore errors on drop with let _
         let _ = self.execute(Command::Exit);
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::event::DisableBracketedPaste);
         if std::thread::panicking() {
             let _ = self.printer.writer.raw.write("IRust panicked, to log the error you can redirect stderror to a file, example irust 2>log");
         }
@@ -9,6 +9,7 @@ use crossterm::{
 use irust_api::Command;
 use printer::printer::{PrintQueue, PrinterItem};
 
+use crate::irust::highlight::theme::OutputKind;
 use crate::irust::IRust;
 use crate::irust::{ra::Cycle, Result};
 use crate::{irust::Buffer, utils::StringTools};
@@ -185,10 +186,17 @@ impl IRust {
             }
             Command::HandleEnter(force_eval) => {
                 self.history.unlock();
+                // `:multiline` only applies to one input
+                self.multiline = false;
 
                 let buffer = self.buffer.to_string();
 
-                if !force_eval && !input_is_cmd_or_shell(&buffer) && incomplete_input(&buffer) {
+                // In `:paste` mode every line is submitted as is
+                if !force_eval
+                    && self.paste_lines.is_none()
+                    && !input_is_cmd_or_shell(&buffer)
+                    && incomplete_input(&buffer)
+                {
                     self.execute(Command::HandleAltEnter)?;
                     return Ok(());
                 }
@@ -356,6 +364,11 @@ impl IRust {
                 if !self.buffer.is_empty() {
                     return Ok(());
                 }
+                // EOF ends `:paste` mode
+                if self.paste_lines.is_some() {
+                    self.printer.write_newline(&self.buffer);
+                    return self.execute(Command::Parse(":end".into()));
+                }
 
                 macro_rules! set_exit_flag_and_return {
                     () => {{
@@ -549,10 +562,8 @@ impl IRust {
                                 code: KeyCode::Char('d'),
                                 modifiers: KeyModifiers::CONTROL,
                                 ..
-                            }) => {
-                                if needle.is_empty() {
-                                    break;
-                                }
+                            }) if needle.is_empty() => {
+                                break;
                             }
                             _ => (),
                         }
@@ -693,7 +704,9 @@ impl IRust {
                 SetCursorStyle::SteadyBlock
             )?),
             Command::ResetPrompt => {
-                let prompt = self.options.input_prompt.clone();
+                let prompt = self
+                    .templated_prompt()
+                    .unwrap_or_else(|| self.options.input_prompt.clone());
                 self.global_variables.prompt_len = prompt.chars().count();
                 self.printer.set_prompt(prompt);
 
@@ -806,6 +819,9 @@ impl IRust {
                 // parse and handle errors
                 let output = match self.parse(buf) {
                     Ok(out) => out,
+                    Err(e) if self.options.json_output => {
+                        self.json_record(crate::json_format::message_record(&e.to_string()))
+                    }
                     Err(e) => {
                         let mut printer = PrintQueue::default();
                         printer.push(PrinterItem::String(e.to_string(), self.options.err_color));
@@ -823,6 +839,35 @@ impl IRust {
         }
     }
 
+    /// Load the file watched with `:watch` again after it changed
+    pub fn rerun_watched(&mut self) -> Result<()> {
+        let Some(watched) = &self.watch else {
+            return Ok(());
+        };
+        let path = watched.path.clone();
+        self.printer.cursor.hide();
+        self.printer.write_newline(&self.buffer);
+
+        let mut output = PrintQueue::default();
+        output.push(PrinterItem::String(
+            format!("{} changed, reloading", path.display()),
+            self.theme.output_color(OutputKind::Info),
+        ));
+        output.add_new_line(1);
+        // the errors are shown and the file is still watched
+        match self.load_inner(path) {
+            Ok(mut load_output) => output.append(&mut load_output),
+            Err(e) => {
+                output.push(PrinterItem::String(
+                    e.to_string(),
+                    self.theme.output_color(OutputKind::Error),
+                ));
+                output.add_new_line(1);
+            }
+        }
+        self.print_output(output)
+    }
+
     fn print_output(&mut self, output: PrintQueue) -> Result<()> {
         // ensure buffer is cleaned
         self.buffer.clear();
@@ -875,10 +920,7 @@ enum Dir {
 // helper functions
 
 fn incomplete_input(buffer: &str) -> bool {
-    StringTools::unmatched_brackets(buffer)
-        || buffer
-            .trim_end()
-            .ends_with(|c| c == ':' || c == '.' || c == '=')
+    StringTools::unmatched_brackets(buffer) || buffer.trim_end().ends_with([':', '.', '='])
 }
 
 fn input_is_cmd_or_shell(buffer: &str) -> bool {
@@ -1,5 +1,7 @@
 use crossterm::style::Color;
 use printer::printer::{PrintQueue, PrinterItem};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 use std::sync::OnceLock;
 
 static NO_COLOR: OnceLock<bool> = OnceLock::new();
@@ -113,18 +115,504 @@ pub fn format_eval_output(
     Some(eval_output)
 }
 
-fn check_is_err(s: &str) -> bool {
-    !s.contains("dev [unoptimized + debuginfo]")
+/// How much of the compiler errors is shown
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorVerbosity {
+    /// Only the first line of each error
+    Summary,
+    #[default]
+    Full,
 }
 
-pub fn format_check_output(
-    output: String,
-    show_warnings: bool,
-    repl_name: &str,
-) -> Option<PrintQueue> {
-    if check_is_err(&output) {
-        Some(format_err_printqueue(&output, show_warnings, repl_name))
-    } else {
+impl FromStr for ErrorVerbosity {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "summary" => Ok(ErrorVerbosity::Summary),
+            "full" => Ok(ErrorVerbosity::Full),
+            _ => Err("Unknown error verbosity (available: summary, full)".into()),
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorVerbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorVerbosity::Summary => write!(f, "summary"),
+            ErrorVerbosity::Full => write!(f, "full"),
+        }
+    }
+}
+
+/// How the values of a type are rendered, set per type with `:display <type> as <strategy>`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayStrategy {
+    #[default]
+    Debug,
+    Display,
+    /// With `serde_json::to_string`, the type needs to implement `Serialize`
+    Json,
+}
+
+impl FromStr for DisplayStrategy {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(DisplayStrategy::Debug),
+            "display" => Ok(DisplayStrategy::Display),
+            "json" => Ok(DisplayStrategy::Json),
+            _ => Err("Unknown display strategy (available: debug, display, json)".into()),
+        }
+    }
+}
+
+impl std::fmt::Display for DisplayStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayStrategy::Debug => write!(f, "debug"),
+            DisplayStrategy::Display => write!(f, "display"),
+            DisplayStrategy::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Evaluates the input and renders its value with the strategy registered for its type,
+/// with `irust_repl::ReplRender` if `repl_render` is set and the type implements it,
+/// with `Display` if `display` is set and the type implements it, `Debug` otherwise
+/// The rendering is picked at compile time with autoref specialization: the method call on `&&&IRustRender<&T>`
+/// prefers the impls for `&&IRustRender<&T>` (the registered types), then `&&&IRustRender<&T>` (`ReplRender`),
+/// then `&IRustRender<&T>` (`Display`) then `IRustRender<&T>` (`Debug`) which need derefs
+pub fn render_evaluator(
+    strategies: &std::collections::BTreeMap<String, DisplayStrategy>,
+    repl_render: bool,
+    display: bool,
+) -> [String; 2] {
+    let mut start = "{\nstruct IRustRender<T>(T);\n\
+        trait IRustRenderDebug { fn irust_render(&self) -> String; }\n\
+        impl<T: std::fmt::Debug> IRustRenderDebug for IRustRender<&T> { fn irust_render(&self) -> String { format!(\"{:?}\", self.0) } }\n"
+        .to_string();
+    if display {
+        start += "trait IRustRenderDisplay { fn irust_render(&self) -> String; }\n\
+            impl<T: std::fmt::Display> IRustRenderDisplay for &IRustRender<&T> { fn irust_render(&self) -> String { format!(\"{}\", self.0) } }\n";
+    }
+    if repl_render {
+        start += "trait IRustRenderRepl { fn irust_render(&self) -> String; }\n\
+            impl<T: irust_repl::ReplRender> IRustRenderRepl for &&&IRustRender<&T> { fn irust_render(&self) -> String { irust_repl::ReplRender::repl_render(self.0) } }\n";
+    }
+    for (idx, (ty, strategy)) in strategies.iter().enumerate() {
+        let render = match strategy {
+            DisplayStrategy::Debug => "format!(\"{:?}\", self.0)",
+            DisplayStrategy::Display => "format!(\"{}\", self.0)",
+            DisplayStrategy::Json => {
+                "serde_json::to_string(self.0).unwrap_or_else(|e| e.to_string())"
+            }
+        };
+        start += &format!(
+            "trait IRustRender{idx} {{ fn irust_render(&self) -> String; }}\n\
+            impl IRustRender{idx} for &&IRustRender<&{ty}> {{ fn irust_render(&self) -> String {{ {render} }} }}\n"
+        );
+    }
+    start += "let irust_value = {\n";
+    [
+        start,
+        "\n};\nprintln!(\"{}\", (&&&IRustRender(&irust_value)).irust_render());\n}".to_string(),
+    ]
+}
+
+#[test]
+fn render_evaluator_test() {
+    use irust_repl::CompileMode;
+
+    let mut repl = irust_repl::Repl::default();
+    repl.insert(
+        "struct Meters(u32); impl std::fmt::Debug for Meters { fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, \"Meters {{ 0: {} }}\", self.0) } }",
+    );
+    repl.insert(
+        "impl std::fmt::Display for Meters { fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, \"{}m\", self.0) } }",
+    );
+    let mut eval = |input: &str, strategies: &std::collections::BTreeMap<_, _>| {
+        crate::probe::eval_with(
+            &mut repl,
+            input,
+            &render_evaluator(strategies, false, false),
+            CompileMode::Debug,
+        )
+        .output
+    };
+
+    let mut strategies = std::collections::BTreeMap::new();
+    assert_eq!(eval("Meters(5)", &strategies), "Meters { 0: 5 }");
+    strategies.insert("Meters".to_string(), DisplayStrategy::Display);
+    assert_eq!(eval("Meters(5)", &strategies), "5m");
+    // the other types still use Debug
+    assert_eq!(eval("\"a\"", &strategies), "\"a\"");
+    assert_eq!(eval("1 + 2", &strategies), "3");
+    strategies.insert("String".to_string(), DisplayStrategy::Display);
+    assert_eq!(eval("String::from(\"a\")", &strategies), "a");
+
+    // types implementing `ReplRender`
+    repl.cargo
+        .cargo_add_sync(&[concat!(env!("CARGO_MANIFEST_DIR"), "/../irust_repl").to_string()])
+        .unwrap();
+    repl.insert(
+        "struct Matrix([[u8; 2]; 2]); impl irust_repl::ReplRender for Matrix { fn repl_render(&self) -> String { self.0.map(|row| format!(\"{row:?}\")).join(\"\\n\") } }",
+    );
+    let mut eval = |input: &str, strategies: &std::collections::BTreeMap<_, _>, display: bool| {
+        crate::probe::eval_with(
+            &mut repl,
+            input,
+            &render_evaluator(strategies, true, display),
+            CompileMode::Debug,
+        )
+        .output
+    };
+    assert_eq!(
+        eval("Matrix([[1, 2], [3, 4]])", &strategies, false),
+        "[1, 2]\n[3, 4]"
+    );
+    assert_eq!(eval("Meters(5)", &strategies, false), "5m");
+    assert_eq!(eval("Some(1)", &strategies, false), "Some(1)");
+
+    // `:display on` uses `Display` when it's implemented, the registered types and `ReplRender` still win
+    let strategies =
+        std::collections::BTreeMap::from([("Meters".to_string(), DisplayStrategy::Debug)]);
+    assert_eq!(eval("\"a\"", &strategies, true), "a");
+    assert_eq!(eval("1.5", &strategies, true), "1.5");
+    assert_eq!(eval("Some(1)", &strategies, true), "Some(1)");
+    assert_eq!(eval("Meters(5)", &strategies, true), "Meters { 0: 5 }");
+    assert_eq!(
+        eval("Matrix([[1, 2], [3, 4]])", &strategies, true),
+        "[1, 2]\n[3, 4]"
+    );
+}
+
+pub fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // skip the escape sequence until its final letter, exp: `\u{1b}[38;5;9m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// The input prompt of a `:prompt` template: `{cwd}` is the working directory (`~` for the home directory)
+/// and `{n}` the operation number, exp: `[{n}] {cwd}> ` => `[3] ~/src> `
+/// The color codes are removed, they would break the cursor position
+pub fn render_prompt(
+    template: &str,
+    cwd: &std::path::Path,
+    home: Option<&std::path::Path>,
+    operation_number: usize,
+) -> String {
+    let cwd = match home.and_then(|home| cwd.strip_prefix(home).ok()) {
+        Some(relative) if relative.as_os_str().is_empty() => "~".to_string(),
+        Some(relative) => format!("~{}{}", std::path::MAIN_SEPARATOR, relative.display()),
+        None => cwd.display().to_string(),
+    };
+    strip_ansi(
+        &template
+            .replace("{cwd}", &cwd)
+            .replace("{n}", &operation_number.to_string()),
+    )
+}
+
+#[test]
+fn render_prompt_test() {
+    let home = std::path::Path::new("/home/user");
+    assert_eq!(
+        render_prompt("[{n}] {cwd}> ", &home.join("src"), Some(home), 3),
+        format!("[3] ~{}src> ", std::path::MAIN_SEPARATOR)
+    );
+    assert_eq!(render_prompt("{cwd} $ ", home, Some(home), 1), "~ $ ");
+    assert_eq!(
+        render_prompt("{cwd}: ", std::path::Path::new("/tmp"), Some(home), 1),
+        "/tmp: "
+    );
+    assert_eq!(
+        render_prompt("\u{1b}[31mIn\u{1b}[0m: ", home, None, 1),
+        "In: "
+    );
+}
+
+/// The compiler warnings of a successful build output (exp: unused variables), None if there are no warnings
+/// The cargo summary (exp: "`x` (bin "x") generated 1 warning") is not kept
+pub fn format_warnings(build_output: &str) -> Option<String> {
+    let mut warnings = vec![];
+    let mut in_warning = false;
+    for line in build_output.lines() {
+        let stripped = strip_ansi(line);
+        if stripped.starts_with("warning") {
+            in_warning = !stripped.starts_with("warning: `");
+        } else if stripped.trim().is_empty() {
+            // the warnings are separated by an empty line
+            if in_warning {
+                warnings.push("");
+            }
+            in_warning = false;
+            continue;
+        }
+        if in_warning {
+            warnings.push(line);
+        }
+    }
+    let warnings = warnings.join("\n").trim_end().to_string();
+    (!warnings.is_empty()).then_some(warnings)
+}
+
+#[test]
+fn format_warnings_test() {
+    let output = "\
+   Compiling irust_host_repl v0.1.0 (/tmp/irust_repls/irust_host_repl)
+warning: unused variable: `a`
+ --> src/main.rs:2:5
+  |
+2 | let a = 1;
+  |     ^ help: if this is intentional, prefix it with an underscore: `_a`
+  |
+  = note: `#[warn(unused_variables)]` on by default
+
+\u{1b}[0m\u{1b}[1m\u{1b}[33mwarning\u{1b}[0m: function `f` is never used
+ --> src/main.rs:3:4
+
+warning: `irust_host_repl` (bin \"irust_host_repl\") generated 2 warnings
+    Finished `dev` profile [unoptimized + debuginfo] target(s) in 0.20s";
+    assert_eq!(
+        format_warnings(output).unwrap(),
+        "\
+warning: unused variable: `a`
+ --> src/main.rs:2:5
+  |
+2 | let a = 1;
+  |     ^ help: if this is intentional, prefix it with an underscore: `_a`
+  |
+  = note: `#[warn(unused_variables)]` on by default
+
+\u{1b}[0m\u{1b}[1m\u{1b}[33mwarning\u{1b}[0m: function `f` is never used
+ --> src/main.rs:3:4"
+    );
+    assert_eq!(
+        format_warnings("   Compiling a v0.1.0\n    Finished `dev` profile"),
         None
+    );
+}
+
+/// The main file line of the first compiler error of a build output, exp: `4` for ` --> src/main.rs:4:15`
+pub fn first_error_line(output: &str) -> Option<usize> {
+    let mut in_error = false;
+    output.lines().map(strip_ansi).find_map(|line| {
+        if line.starts_with("error") {
+            in_error = true;
+        } else if line.starts_with("warning") {
+            in_error = false;
+        }
+        let location = line
+            .trim_start()
+            .strip_prefix("--> src/main.rs:")
+            .filter(|_| in_error)?;
+        location.split(':').next()?.parse().ok()
+    })
+}
+
+#[test]
+fn first_error_line_test() {
+    let output = "\
+warning: unused variable: `a`
+ --> src/main.rs:2:5
+\u{1b}[0m\u{1b}[1m\u{1b}[38;5;9merror[E0308]\u{1b}[0m\u{1b}[0m\u{1b}[1m: mismatched types\u{1b}[0m
+\u{1b}[0m \u{1b}[0m\u{1b}[0m\u{1b}[1m\u{1b}[38;5;12m--> \u{1b}[0m\u{1b}[0msrc/main.rs:4:15\u{1b}[0m
+error[E0425]: cannot find value `b` in this scope
+ --> src/main.rs:7:1";
+    assert_eq!(first_error_line(output), Some(4));
+    assert_eq!(first_error_line("error: could not compile"), None);
+}
+
+/// Keep only the headline of each error, exp: `error[E0308]: mismatched types`
+pub fn summarize_err(formatted_error: &str) -> String {
+    let headlines: Vec<String> = formatted_error
+        .lines()
+        .map(strip_ansi)
+        .filter(|line| {
+            line.starts_with("error")
+                && !line.starts_with("error: aborting due to")
+                && !line.starts_with("error: could not compile")
+        })
+        .collect();
+    if headlines.is_empty() {
+        // not a compiler error (exp: a cargo failure), nothing to summarize
+        return formatted_error.to_string();
+    }
+    headlines.join("\n") + "\n(use `:errors --full` to see the full output)"
+}
+
+#[test]
+fn summarize_err_test() {
+    let error = "\
+\u{1b}[0m\u{1b}[1m\u{1b}[38;5;9merror[E0308]\u{1b}[0m\u{1b}[0m\u{1b}[1m: mismatched types\u{1b}[0m
+ --> src/main.rs:4:15
+  |
+4 | { let x: u8 = \"a\"; f(\"b\", 4) }
+  |          --   ^^^ expected `u8`, found `&str`
+  |          |
+  |          expected due to this
+
+error[E0308]: arguments to this function are incorrect
+ --> src/main.rs:4:20
+  |
+note: function defined here
+ --> src/main.rs:2:4
+help: did you mean
+  |
+
+error: aborting due to 2 previous errors";
+    assert_eq!(
+        summarize_err(error),
+        "\
+error[E0308]: mismatched types
+error[E0308]: arguments to this function are incorrect
+(use `:errors --full` to see the full output)"
+    );
+}
+
+/// Which frames of a panic backtrace are shown
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BacktraceFilter {
+    /// Only the top user frame
+    Minimal,
+    /// Frames of the user code (hides std/core frames and the generated main wrapper)
+    #[default]
+    User,
+    /// The raw backtrace
+    Full,
+}
+
+impl FromStr for BacktraceFilter {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(BacktraceFilter::Minimal),
+            "user" => Ok(BacktraceFilter::User),
+            "full" => Ok(BacktraceFilter::Full),
+            _ => Err("Unknown backtrace filter (available: minimal, user, full)".into()),
+        }
+    }
+}
+
+impl std::fmt::Display for BacktraceFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BacktraceFilter::Minimal => write!(f, "minimal"),
+            BacktraceFilter::User => write!(f, "user"),
+            BacktraceFilter::Full => write!(f, "full"),
+        }
+    }
+}
+
+/// Remove the backtrace frames (a frame is `N: symbol` followed by its `at file:line` lines)
+/// that don't belong to the user code, according to `filter`
+pub fn filter_backtrace(output: &str, filter: BacktraceFilter, repl_name: &str) -> String {
+    const BACKTRACE_TAG: &str = "stack backtrace:";
+    if filter == BacktraceFilter::Full || !output.contains(BACKTRACE_TAG) {
+        return output.to_string();
     }
+
+    let frame_symbol = |line: &str| -> Option<String> {
+        let (index, symbol) = line.trim_start().split_once(": ")?;
+        index
+            .chars()
+            .all(|c| c.is_ascii_digit())
+            .then(|| symbol.trim().to_string())
+    };
+    // IRust wraps the evaluated input in main, this frame is not interesting
+    let wrapper = format!("{repl_name}::main");
+    let is_user_frame =
+        |symbol: &str| symbol.starts_with(&format!("{repl_name}::")) && symbol != wrapper;
+
+    let mut filtered = vec![];
+    let mut in_backtrace = false;
+    let mut keep_frame = false;
+    let mut kept_frames = 0;
+    for line in output.lines() {
+        if line.trim() == BACKTRACE_TAG {
+            in_backtrace = true;
+        } else if in_backtrace {
+            if let Some(symbol) = frame_symbol(line) {
+                keep_frame =
+                    is_user_frame(&symbol) && (filter == BacktraceFilter::User || kept_frames == 0);
+                if keep_frame {
+                    kept_frames += 1;
+                }
+            } else if line.starts_with(char::is_whitespace) {
+                // `at file:line` of the current frame
+            } else {
+                in_backtrace = false;
+                keep_frame = true;
+            }
+            if !keep_frame {
+                continue;
+            }
+        }
+        filtered.push(line);
+    }
+    filtered.join("\n")
+}
+
+#[test]
+fn filter_backtrace_test() {
+    let output = "\
+thread 'main' panicked at src/main.rs:2:10:
+boom
+stack backtrace:
+   0: __rustc::rust_begin_unwind
+             at /rustc/library/std/src/panicking.rs:689:5
+   1: core::panicking::panic_fmt
+             at /rustc/library/core/src/panicking.rs:80:14
+   2: repl::main::g
+             at /tmp/repl/src/main.rs:3:10
+   3: repl::main::f
+             at /tmp/repl/src/main.rs:2:10
+   4: repl::main
+             at /tmp/repl/src/main.rs:4:1
+   5: core::ops::function::FnOnce::call_once
+             at /rustc/library/core/src/ops/function.rs:250:5
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.";
+
+    assert_eq!(
+        filter_backtrace(output, BacktraceFilter::Full, "repl"),
+        output
+    );
+    assert_eq!(
+        filter_backtrace(output, BacktraceFilter::User, "repl"),
+        "\
+thread 'main' panicked at src/main.rs:2:10:
+boom
+stack backtrace:
+   2: repl::main::g
+             at /tmp/repl/src/main.rs:3:10
+   3: repl::main::f
+             at /tmp/repl/src/main.rs:2:10
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace."
+    );
+    assert_eq!(
+        filter_backtrace(output, BacktraceFilter::Minimal, "repl"),
+        "\
+thread 'main' panicked at src/main.rs:2:10:
+boom
+stack backtrace:
+   2: repl::main::g
+             at /tmp/repl/src/main.rs:3:10
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace."
+    );
+}
+
+pub fn check_is_err(s: &str) -> bool {
+    !s.contains("dev [unoptimized + debuginfo]")
 }
@@ -13,13 +13,130 @@ impl IRust {
         #[cfg(windows)]
         let readme = include_str!("..\\..\\README.md");
 
-        let compact = !buffer.contains("full");
+        // exp: :help add
+        let topic = buffer
+            .strip_prefix(":help")
+            .expect("already checked")
+            .trim();
+        if topic == "full" {
+            return Ok(parse_markdown(&readme.into(), &self.theme));
+        }
+
+        let commands = commands_help(readme);
+        if topic.is_empty() {
+            let mut queue = PrintQueue::default();
+            for (name, help) in &commands {
+                queue.push(PrinterItem::String(format!("{name:<20}"), Color::Magenta));
+                queue.push(PrinterItem::String(help.summary.clone(), Color::White));
+                queue.add_new_line(1);
+            }
+            queue.push(PrinterItem::Str(
+                "`:help <command>` shows the details of a command, `:help full` shows the full help",
+                Color::DarkGreen,
+            ));
+            queue.add_new_line(1);
+            return Ok(queue);
+        }
+
+        let name = if topic.starts_with(':') {
+            topic.to_string()
+        } else {
+            format!(":{topic}")
+        };
+        match commands.iter().find(|(command, _)| *command == name) {
+            Some((_, help)) => Ok(parse_markdown(
+                &help.details.join("\n\n").into(),
+                &self.theme,
+            )),
+            None => {
+                let names = commands.iter().map(|(command, _)| *command);
+                Err(match crate::utils::closest(&name, names) {
+                    Some(closest) => format!("Unknown command `{name}`, did you mean `{closest}`?"),
+                    None => format!("Unknown command `{name}`"),
+                }
+                .into())
+            }
+        }
+    }
+}
+
+/// The help of a command, from its README lines
+struct CommandHelp {
+    /// The start of its first description, exp: `add dependencies`
+    summary: String,
+    /// Its README lines (a command can have several, exp: `:display`)
+    details: Vec<String>,
+}
+
+/// The help of the commands in the README order, exp: `**:add** *<dep_list>* => add dependencies ..`
+/// gives `:add` with the summary `add dependencies`
+fn commands_help(readme: &str) -> Vec<(&str, CommandHelp)> {
+    let mut commands: Vec<(&str, CommandHelp)> = vec![];
+    for line in readme.lines() {
+        let Some((name, rest)) = line
+            .strip_prefix("**:")
+            .and_then(|line| line.split_once("**"))
+        else {
+            continue;
+        };
+        let name = &line[2..name.len() + 3];
+        let description = rest.split_once("=> ").map_or(rest.trim(), |(_, d)| d);
+        let summary_end = [", ", ". ", " ("]
+            .iter()
+            .filter_map(|separator| description.find(separator))
+            .min()
+            .unwrap_or(description.len());
+        match commands.iter_mut().find(|(command, _)| *command == name) {
+            Some((_, help)) => help.details.push(line.to_string()),
+            None => commands.push((
+                name,
+                CommandHelp {
+                    summary: description[..summary_end].to_string(),
+                    details: vec![line.to_string()],
+                },
+            )),
+        }
+    }
+    commands
+}
 
-        Ok(parse_markdown(&readme.into(), &self.theme, compact))
+#[test]
+fn commands_help_test() {
+    let readme = "# IRust\n\
+        **:help** => print help, use `:help full` for the full version\n\n\
+        **:add** *<dep_list>* => add dependencies (requires cargo-edit)\n\n\
+        **:display** *on|off* => render with `Display`. The types ..\n\n\
+        **:display** *<type>* as *<strategy>* => render a type\n\n\
+        **:sync** sync the changes\n\n\
+        **ctrl-l** clear screen\n";
+    let commands = commands_help(readme);
+    let summaries: Vec<_> = commands
+        .iter()
+        .map(|(name, help)| (*name, help.summary.as_str(), help.details.len()))
+        .collect();
+    assert_eq!(
+        summaries,
+        [
+            (":help", "print help", 1),
+            (":add", "add dependencies", 1),
+            (":display", "render with `Display`", 2),
+            (":sync", "sync the changes", 1),
+        ]
+    );
+
+    // every README command has a help
+    #[cfg(unix)]
+    let commands = commands_help(include_str!("../../README.md"));
+    #[cfg(unix)]
+    for name in [":add", "::", ":help", ":edit", ":reset"] {
+        assert!(
+            commands.iter().any(|(command, _)| *command == name),
+            "{name}"
+        );
     }
 }
 
-fn parse_markdown(buffer: &Buffer, theme: &Theme, compact: bool) -> PrintQueue {
+fn parse_markdown(buffer: &Buffer, theme: &Theme) -> PrintQueue {
     let mut queue = PrintQueue::default();
 
     let buffer = buffer.to_string();
@@ -28,10 +145,6 @@ fn parse_markdown(buffer: &Buffer, theme: &Theme, compact: bool) -> PrintQueue {
     (|| -> Option<()> {
         loop {
             let line = buffer.next()?;
-            if compact && line.starts_with("<img") {
-                break Some(());
-            }
-
             if line.trim_start().starts_with("##") {
                 queue.push(PrinterItem::String(line.to_string(), Color::Yellow));
             } else if line.trim_start().starts_with('#') {
@@ -11,8 +11,45 @@ fn themes_path() -> Result<std::path::PathBuf> {
         .join("themes"))
 }
 
+/// The themes that don't need a theme file, an installed theme with the same name has precedence
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light"];
+
+pub fn builtin_theme(name: &str) -> Option<Theme> {
+    match name {
+        "dark" => Some(Theme::default()),
+        // no white, yellow or grey that are hard to read on a light background
+        "light" => Some(Theme {
+            keyword: "dark_magenta".into(),
+            keyword2: "dark_red".into(),
+            function: "dark_blue".into(),
+            r#type: "dark_cyan".into(),
+            symbol: "dark_red".into(),
+            r#macro: "dark_yellow".into(),
+            literal: "dark_green".into(),
+            lifetime: "magenta".into(),
+            comment: "dark_grey".into(),
+            r#const: "dark_green".into(),
+            ident: "black".into(),
+            paren_rainbow: true,
+            output: OutputTheme {
+                success: "dark_blue".into(),
+                warning: "dark_yellow".into(),
+                info: "dark_magenta".into(),
+                error: "dark_red".into(),
+                value: "dark_blue".into(),
+            },
+        }),
+        _ => None,
+    }
+}
+
 pub fn theme(name: String) -> Result<Theme> {
-    let selected_theme_path = themes_path()?.join(name + ".toml");
+    let selected_theme_path = themes_path()?.join(name.clone() + ".toml");
+    if !selected_theme_path.exists() {
+        if let Some(theme) = builtin_theme(&name) {
+            return Ok(theme);
+        }
+    }
 
     let data = std::fs::read_to_string(selected_theme_path)?;
 
@@ -60,12 +97,62 @@ pub struct Theme {
     pub r#const: String,
     pub ident: String,
     pub paren_rainbow: bool,
+    // Themes created before this field existed don't have it
+    #[serde(default)]
+    pub output: OutputTheme,
+}
+
+/// Semantic kind of a command output, the theme maps each kind to a color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    /// A command succeeded without output, exp: `Ok!`
+    Success,
+    /// Something didn't go as expected but it's not an error, exp: `Aborted`
+    Warning,
+    /// Progress messages, exp: `Building...`
+    Info,
+    Error,
+    /// The result of a query, exp: the current toolchain
+    Value,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct OutputTheme {
+    pub success: String,
+    pub warning: String,
+    pub info: String,
+    pub error: String,
+    pub value: String,
+}
+
+impl Default for OutputTheme {
+    fn default() -> Self {
+        Self {
+            success: "blue".into(),
+            warning: "yellow".into(),
+            info: "magenta".into(),
+            error: "red".into(),
+            value: "blue".into(),
+        }
+    }
 }
 
 impl Theme {
     pub fn reset(&mut self) {
         *self = Self::default();
     }
+
+    pub fn output_color(&self, kind: OutputKind) -> Color {
+        let (color, default) = match kind {
+            OutputKind::Success => (&self.output.success, Color::Blue),
+            OutputKind::Warning => (&self.output.warning, Color::Yellow),
+            OutputKind::Info => (&self.output.info, Color::Magenta),
+            OutputKind::Error => (&self.output.error, Color::Red),
+            OutputKind::Value => (&self.output.value, Color::Blue),
+        };
+        theme_color_to_term_color(color).unwrap_or(default)
+    }
 }
 
 impl Default for Theme {
@@ -83,6 +170,7 @@ impl Default for Theme {
             r#const: "dark_green".into(),
             ident: "white".into(),
             paren_rainbow: true,
+            output: OutputTheme::default(),
         }
     }
 }
@@ -125,3 +213,60 @@ pub fn theme_color_to_term_color(color: &str) -> Option<Color> {
         }
     }
 }
+
+#[test]
+fn builtin_theme_test() {
+    for name in BUILTIN_THEMES {
+        let theme = builtin_theme(name).unwrap();
+        for color in [
+            &theme.keyword,
+            &theme.keyword2,
+            &theme.function,
+            &theme.r#type,
+            &theme.symbol,
+            &theme.r#macro,
+            &theme.literal,
+            &theme.lifetime,
+            &theme.comment,
+            &theme.r#const,
+            &theme.ident,
+            &theme.output.success,
+            &theme.output.warning,
+            &theme.output.info,
+            &theme.output.error,
+            &theme.output.value,
+        ] {
+            assert!(
+                theme_color_to_term_color(color).is_some(),
+                "{name}: {color}"
+            );
+        }
+    }
+    assert!(builtin_theme("nope").is_none());
+}
+
+#[test]
+fn output_color_test() {
+    let theme = Theme::default();
+    assert_ne!(
+        theme.output_color(OutputKind::Warning),
+        theme.output_color(OutputKind::Error)
+    );
+    assert_eq!(theme.output_color(OutputKind::Warning), Color::Yellow);
+
+    // themes without the output colors use the default ones
+    let mut old_theme = toml::to_string(&Theme::default()).unwrap();
+    old_theme.truncate(old_theme.find("[output]").unwrap());
+    let mut theme: Theme = toml::from_str(&old_theme).unwrap();
+    assert_eq!(theme.output_color(OutputKind::Error), Color::Red);
+
+    theme.output.warning = "#ff8000".into();
+    assert_eq!(
+        theme.output_color(OutputKind::Warning),
+        Color::Rgb {
+            r: 255,
+            g: 128,
+            b: 0
+        }
+    );
+}
@@ -149,6 +149,26 @@ impl History {
             .map(|e| e.to_owned().to_owned())
     }
 
+    /// Fuzzy search the history, most relevant first (recent entries win ties)
+    pub fn fuzzy_search(&self, query: &str) -> Vec<(String, Vec<usize>)> {
+        let mut history = self
+            .history
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        history.dedup();
+        crate::utils::fuzzy_rank(query, history.into_iter())
+            .into_iter()
+            .map(|(entry, indices)| (entry.to_owned(), indices))
+            .collect()
+    }
+
+    /// The history entries, oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(String::as_str)
+    }
+
     pub fn lock(&mut self) {
         self.lock = true;
     }
@@ -1,10 +1,17 @@
-use crate::irust::{IRust, Result};
+use crate::irust::{
+    format::{BacktraceFilter, ErrorVerbosity},
+    IRust, Result,
+};
 use crossterm::style::Color;
-use irust_repl::{CompileMode, Edition, Executor, MainResult, ToolChain, DEFAULT_EVALUATOR};
+use irust_repl::{
+    AsyncRuntime, CompileMode, Edition, Executor, MainResult, ToolChain, DEFAULT_EVALUATOR,
+};
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
+// Missing fields (exp: options added in a newer IRust version) fallback to their default value
+#[serde(default)]
 pub struct Options {
     add_irust_cmd_to_history: bool,
     add_shell_cmd_to_history: bool,
@@ -46,6 +53,45 @@ pub struct Options {
     pub theme: String,
     pub compile_mode: CompileMode,
     pub new_lines_after_output: usize,
+    pub echo_insert: bool,
+    pub async_runtime: AsyncRuntime,
+    pub bt_filter: BacktraceFilter,
+    pub persist_target: bool,
+    pub confirm_destructive: bool,
+    pub trait_impls: Vec<String>,
+    pub type_aliases: Vec<String>,
+    pub error_verbosity: ErrorVerbosity,
+    pub track_alloc: bool,
+    pub fmt_on_edit: bool,
+    pub quiet: bool,
+    /// Set by `:json on`, the evaluation results and the errors are printed as JSON records
+    pub json_output: bool,
+    /// Set by `:show-types on`, the evaluation results are followed by their type
+    pub show_types: bool,
+    /// Set by `:display on`, the evaluation results implementing `Display` are rendered with it instead of `Debug`
+    pub display_values: bool,
+    /// Set by `:panic-catch on`, the panics of the evaluations are caught and reported with their message
+    pub panic_catch: bool,
+    /// Set by `:capture on`, the stdout of the evaluations is shown apart from their value
+    pub capture_stdout: bool,
+    /// Set by `:warnings on`, the compiler warnings of the successful evaluations are shown
+    pub show_eval_warnings: bool,
+    pub sandbox: bool,
+    /// Command aliases set with `:bind`, exp: `ll = "::ls -la"` expands `:ll` to `::ls -la`
+    pub aliases: std::collections::BTreeMap<String, String>,
+    /// Command used to page the outputs that don't fit in the terminal, exp: `less -R`
+    pub pager: Option<String>,
+    /// Names shown instead of long types by `:type`, set with `:alias-type`
+    pub type_display_aliases: std::collections::BTreeMap<String, String>,
+    /// Show the `()` result of macros like `println!` when they're evaluated
+    pub echo_unit_macros: bool,
+    /// How the values of a type are rendered instead of `Debug`, set with `:display`
+    pub display_strategies:
+        std::collections::BTreeMap<String, crate::irust::format::DisplayStrategy>,
+    /// The stack size in bytes of the thread that runs the evaluations, None uses the main thread
+    pub stack_size: Option<usize>,
+    /// The toolchains used by `:compare-toolchains`, all the installed ones if it's empty
+    pub compare_toolchains: Vec<ToolChain>,
 }
 
 impl Default for Options {
@@ -106,6 +152,52 @@ impl Default for Options {
             theme: "default".into(),
             compile_mode: CompileMode::Debug,
             new_lines_after_output: 1,
+            echo_insert: false,
+            async_runtime: AsyncRuntime::BlockOn,
+            bt_filter: BacktraceFilter::User,
+            persist_target: false,
+            confirm_destructive: true,
+            trait_impls: [
+                "std::fmt::Debug",
+                "std::fmt::Display",
+                "Clone",
+                "Copy",
+                "Default",
+                "PartialEq",
+                "Eq",
+                "PartialOrd",
+                "Ord",
+                "std::hash::Hash",
+                "Send",
+                "Sync",
+                "Sized",
+                "Unpin",
+                "Iterator",
+                "IntoIterator",
+                "std::error::Error",
+            ]
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect(),
+            type_aliases: vec![],
+            error_verbosity: ErrorVerbosity::Full,
+            track_alloc: false,
+            fmt_on_edit: false,
+            quiet: false,
+            json_output: false,
+            show_types: false,
+            display_values: false,
+            panic_catch: false,
+            capture_stdout: false,
+            show_eval_warnings: false,
+            sandbox: false,
+            aliases: Default::default(),
+            pager: None,
+            type_display_aliases: Default::default(),
+            echo_unit_macros: false,
+            display_strategies: Default::default(),
+            stack_size: None,
+            compare_toolchains: vec![],
         }
     }
 }
@@ -120,16 +212,43 @@ impl Options {
 
     pub fn new() -> Result<Self> {
         if let Some(config_path) = Options::config_path() {
-            let mut config_file = std::fs::File::open(config_path)?;
-            let mut config_data = String::new();
-            config_file.read_to_string(&mut config_data)?;
-
-            toml::from_str(&config_data).map_err(|e| e.into())
+            Self::from_path(&config_path)
         } else {
             Ok(Options::default())
         }
     }
 
+    pub fn from_path(config_path: &std::path::Path) -> Result<Self> {
+        let mut config_file = std::fs::File::open(config_path)?;
+        let mut config_data = String::new();
+        config_file.read_to_string(&mut config_data)?;
+
+        toml::from_str(&config_data).map_err(|e| e.into())
+    }
+
+    /// The settings that differ in `new` and that are only applied at startup
+    pub fn restart_required(&self, new: &Options) -> Vec<&'static str> {
+        let mut settings = vec![];
+        if self.edition.to_string() != new.edition.to_string() {
+            settings.push("edition");
+        }
+        if self.type_aliases != new.type_aliases {
+            settings.push("type_aliases");
+        }
+        if self.enable_rust_analyzer != new.enable_rust_analyzer {
+            settings.push("enable_rust_analyzer");
+        }
+        if self.activate_scripting != new.activate_scripting {
+            settings.push("activate_scripting");
+        }
+        if self.local_server != new.local_server
+            || self.local_server_adress != new.local_server_adress
+        {
+            settings.push("local_server");
+        }
+        settings
+    }
+
     pub fn reset(&mut self) {
         *self = Self::default();
     }
@@ -187,6 +306,25 @@ impl IRust {
     }
 }
 
+#[test]
+fn refresh_options_test() {
+    let config_path = std::env::temp_dir().join("irust_refresh_options_test.toml");
+    let old = Options::default();
+    let new = Options {
+        theme: "solarized".into(),
+        edition: Edition::E2018,
+        ..Default::default()
+    };
+    Options::write_config_file(config_path.clone(), &new).unwrap();
+
+    let refreshed = Options::from_path(&config_path).unwrap();
+    assert_eq!(refreshed.theme, "solarized");
+    // the theme is applied live, the edition needs a restart
+    assert_eq!(old.restart_required(&refreshed), vec!["edition"]);
+    assert!(refreshed.restart_required(&refreshed).is_empty());
+    std::fs::remove_file(config_path).unwrap();
+}
+
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub enum Debugger {
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -8,22 +9,35 @@ use crossterm::style::Color;
 
 use super::format::format_err_printqueue;
 use super::highlight::highlight;
+use super::highlight::theme::{OutputKind, Theme};
+use super::options::Options;
+use crate::eval::Input;
 use crate::irust::{IRust, Result};
 use crate::utils::{copy_dir, stdout_and_stderr};
 use crate::utils::{find_workpace_root, patch_name_to};
 use crate::{
-    irust::format::{format_check_output, format_eval_output},
+    irust::format::{
+        check_is_err, filter_backtrace, first_error_line, format_err, format_eval_output,
+        format_warnings, render_evaluator, summarize_err, BacktraceFilter, DisplayStrategy,
+        ErrorVerbosity,
+    },
     utils::ctrlc_cancel,
 };
-use irust_repl::{CompileMode, EvalConfig, EvalResult, Executor, MainResult, ToolChain};
+use irust_repl::{
+    AsyncRuntime, CompileMode, Edition, EvalConfig, EvalResult, Executor, MainResult, ToolChain,
+    DEFAULT_EVALUATOR,
+};
 use printer::printer::{PrintQueue, PrinterItem};
 
 const SUCCESS: &str = "Ok!";
 
 macro_rules! success {
-    () => {{
+    ($irust: expr) => {{
         let mut print_queue = PrintQueue::default();
-        print_queue.push(PrinterItem::Str(SUCCESS, Color::Blue));
+        print_queue.push(PrinterItem::Str(
+            SUCCESS,
+            $irust.theme.output_color(OutputKind::Success),
+        ));
         print_queue.add_new_line(1);
 
         Ok(print_queue)
@@ -39,28 +53,319 @@ macro_rules! print_queue {
     }};
 }
 
+/// Is this the output of `success!`
+fn is_success(print_queue: &PrintQueue) -> bool {
+    matches!(
+        print_queue.clone().collect::<Vec<_>>().as_slice(),
+        [PrinterItem::Str(SUCCESS, _), PrinterItem::NewLine]
+    )
+}
+
+#[test]
+fn is_success_test() {
+    let mut success = PrintQueue::default();
+    success.push(PrinterItem::Str(SUCCESS, Color::Blue));
+    success.add_new_line(1);
+    assert!(is_success(&success));
+    let output: Result<PrintQueue> = print_queue!(SUCCESS.to_string() + "4", Color::Blue);
+    assert!(!is_success(&output.unwrap()));
+    assert!(!is_success(&PrintQueue::default()));
+}
+
+/// The inserted code with a note about what it added, shown with `echo-insert`
+fn echo_insert(code: &str, theme: &Theme) -> PrintQueue {
+    let mut print_queue = highlight(&code.into(), theme);
+    print_queue.add_new_line(1);
+    let note = match crate::utils::describe_item(code) {
+        Some(item) => format!("added {item}"),
+        None => "added statement".to_string(),
+    };
+    print_queue.push(PrinterItem::String(
+        note,
+        theme.output_color(OutputKind::Success),
+    ));
+    print_queue.add_new_line(1);
+    print_queue
+}
+
+#[test]
+fn echo_insert_test() {
+    let theme = Theme::default();
+    let text = |print_queue: PrintQueue| {
+        print_queue
+            .map(|item| match item {
+                PrinterItem::Char(c, _) => c.to_string(),
+                PrinterItem::String(s, _) => s,
+                PrinterItem::Str(s, _) => s.to_string(),
+                PrinterItem::RcString(s, range, _) => s[range].to_string(),
+                PrinterItem::NewLine => "\n".to_string(),
+            })
+            .collect::<String>()
+    };
+    assert_eq!(
+        text(echo_insert("#[derive(Debug)]\nstruct Foo;", &theme)),
+        "#[derive(Debug)]\nstruct Foo;\nadded struct Foo\n"
+    );
+    assert_eq!(
+        text(echo_insert("let a = 4;", &theme)),
+        "let a = 4;\nadded let a\n"
+    );
+    let mut print_queue = echo_insert("a += 1;", &theme);
+    assert!(print_queue.any(|item| matches!(
+        item,
+        PrinterItem::String(note, color)
+            if note == "added statement" && color == theme.output_color(OutputKind::Success)
+    )));
+}
+
+/// Commands matched exactly by `parse`
+const EXACT_COMMANDS: &[&str] = &[
+    ":reset",
+    ":restart",
+    ":show",
+    ":check-unsafe",
+    ":prof-build",
+    ":prof-startup",
+    ":notes",
+    ":coverage",
+    ":version-pin",
+    ":undo-dep",
+    ":state-hash",
+    ":pwd",
+    ":vars",
+    ":deps-outdated",
+    ":deps",
+    ":multiline",
+    ":refresh",
+    ":reload-deps",
+    ":clear",
+    ":paste-clipboard",
+    ":paste",
+    ":pop",
+    ":undo",
+    ":irust",
+    ":sync",
+    ":fmt",
+    ":run",
+    ":last-status",
+    ":popd",
+    ":dirs",
+    ":unwatch",
+    ":exit",
+    ":quit",
+];
+/// Commands matched by prefix by `parse`
+const PREFIX_COMMANDS: &[&str] = &[
+    ":help",
+    "::",
+    ":edition",
+    ":edit-last",
+    ":edit",
+    ":add",
+    ":hard_load",
+    ":load",
+    ":reload",
+    ":rename",
+    ":save",
+    ":toml",
+    ":type",
+    ":del",
+    ":dbg",
+    ":color",
+    ":cd",
+    ":toolchain",
+    ":theme",
+    ":main_result",
+    ":check_statements",
+    ":time",
+    ":benchmark-compare",
+    ":bench",
+    ":asm",
+    ":await",
+    ":executor",
+    ":evaluator",
+    ":scripts",
+    ":compile_time",
+    ":compile_mode",
+    ":profile",
+    ":prompt",
+    ":expand",
+    ":env-file",
+    ":config",
+    ":copy",
+    ":doc",
+    ":eval-with",
+    ":trait-impls",
+    ":explain-type",
+    ":inspect",
+    ":json",
+    ":compare-toolchains",
+    ":input",
+    ":errors",
+    ":hist-search",
+    ":history",
+    ":grep",
+    ":bind",
+    ":retry",
+    ":alias-type",
+    ":alias",
+    ":display",
+    ":keep",
+    ":note",
+    ":shell-bind",
+    ":search",
+    ":out",
+    ":watch",
+    ":export",
+    ":assert",
+    ":show-types",
+    ":snippet",
+    ":panic-catch",
+    ":capture",
+    ":quiet",
+    ":step",
+    ":unkeep",
+    ":warnings",
+    ":mem",
+    ":complete",
+];
+
+/// Would `parse` dispatch this command to a builtin command
+fn is_builtin_command(cmd: &str) -> bool {
+    EXACT_COMMANDS.contains(&cmd) || PREFIX_COMMANDS.iter().any(|c| cmd.starts_with(c))
+}
+
+#[test]
+fn is_builtin_command_test() {
+    assert!(is_builtin_command(":show"));
+    assert!(is_builtin_command(":types"));
+    assert!(!is_builtin_command(":shows"));
+    assert!(is_builtin_command(":alias"));
+    assert!(!is_builtin_command(":ll"));
+
+    // every command dispatched by `parse_first_order` is in the tables
+    let source = include_str!("parser.rs");
+    // the last occurrence, the first ones are in this test
+    let start = source.rfind("fn parse_first_order(").expect("exists");
+    let end = start + source[start..].find("fn reset(").expect("exists");
+    let commands = source[start..end]
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .filter(|literal| literal.starts_with(':'))
+        // exp: `cmd.starts_with(":reset ")`
+        .map(str::trim_end)
+        .collect::<Vec<_>>();
+    assert!(commands.len() > 100);
+    for command in commands {
+        assert!(
+            is_builtin_command(command),
+            "`{command}` is not in the tables"
+        );
+    }
+}
+
 impl IRust {
     pub fn parse(&mut self, buffer: String) -> Result<PrintQueue> {
+        // `:paste` mode: the lines are evaluated together on `:end`
+        if let Some(lines) = self.paste_lines.as_mut() {
+            if buffer.trim() != ":end" {
+                lines.push(buffer);
+                return Ok(PrintQueue::default());
+            }
+            let block = self.paste_lines.take().expect("already checked").join("\n");
+            if !block.trim().is_empty() {
+                self.session_inputs.push(block.clone());
+            }
+            return self.parse_second_order(block);
+        }
+        if !buffer.trim().is_empty() && !buffer.starts_with(":history") {
+            self.session_inputs.push(buffer.clone());
+        }
+        let buffer = crate::utils::retry_input(buffer, &mut self.last_input)?;
+        let buffer = crate::utils::expand_alias(&buffer, &self.options.aliases).unwrap_or(buffer);
+        let output = self.parse_first_order(buffer)?;
+        // In quiet mode, commands that succeed without output don't print anything
+        if self.options.quiet && is_success(&output) {
+            return Ok(PrintQueue::default());
+        }
+        Ok(output)
+    }
+
+    fn parse_first_order(&mut self, buffer: String) -> Result<PrintQueue> {
         // check if a script want to act upon the input
         // if so scripts have precedence over normal flow
 
         // Order matters in this match
         match buffer.as_str() {
-            ":reset" => self.reset(),
-            ":show" => Ok(self.show()),
+            cmd if cmd == ":reset" || cmd.starts_with(":reset ") => self.reset(buffer),
+            ":restart" => self.restart(),
+            cmd if cmd == ":show" || cmd.starts_with(":show ") => self.show(buffer),
+            ":check-unsafe" => self.check_unsafe(),
+            ":prof-build" => self.prof_build(),
+            ":prof-startup" => self.prof_startup(),
+            ":notes" => self.notes(),
+            ":coverage" => self.coverage(),
+            ":version-pin" => self.version_pin(),
+            ":undo-dep" => self.undo_dep(),
+            ":state-hash" => self.state_hash(),
+            ":pwd" => self.pwd(),
+            ":vars" => self.vars(),
+            cmd if cmd == ":complete" || cmd.starts_with(":complete ") => self.complete(buffer),
+            ":deps-outdated" => self.deps_outdated(),
+            ":deps" => self.deps(),
+            ":multiline" => self.multiline(),
+            ":refresh" => self.refresh(),
+            ":reload-deps" => self.reload_deps(),
+            ":clear" => self.clear(),
+            ":paste-clipboard" => self.paste_clipboard(),
+            ":paste" => self.paste(),
             ":pop" => self.pop(),
+            ":undo" => self.undo(),
             ":irust" => self.irust(),
             ":sync" => self.sync(),
+            ":fmt" => self.fmt(),
+            ":run" => self.run_main(),
+            ":last-status" => self.last_status(),
+            ":popd" => self.popd(),
+            ":dirs" => self.dirs(),
+            cmd if cmd == ":mem" || cmd.starts_with(":mem ") => self.mem(buffer),
+            ":unwatch" => self.unwatch(),
             ":exit" | ":quit" => self.exit(),
             cmd if cmd.starts_with(":help") => self.help(buffer),
             cmd if cmd.starts_with("::") => self.run_cmd(buffer),
+            cmd if cmd.starts_with(":edition") => self.edition(buffer),
+            cmd if cmd.starts_with(":edit-last") => self.edit_last(buffer),
             cmd if cmd.starts_with(":edit") => self.extern_edit(buffer),
+            cmd if cmd.starts_with(":json") => {
+                self.toggle(&buffer, ":json", |options| &mut options.json_output)
+            }
+            cmd if cmd.starts_with(":copy") => self.copy(buffer),
+            cmd if cmd.starts_with(":snippet") => self.snippet(buffer),
+            cmd if cmd.starts_with(":show-types") => {
+                self.toggle(&buffer, ":show-types", |options| &mut options.show_types)
+            }
+            cmd if cmd.starts_with(":panic-catch") => {
+                self.toggle(&buffer, ":panic-catch", |options| &mut options.panic_catch)
+            }
+            cmd if cmd.starts_with(":capture") => {
+                self.toggle(&buffer, ":capture", |options| &mut options.capture_stdout)
+            }
+            cmd if cmd.starts_with(":quiet") => {
+                self.toggle(&buffer, ":quiet", |options| &mut options.quiet)
+            }
+            cmd if cmd.starts_with(":warnings") => self.toggle(&buffer, ":warnings", |options| {
+                &mut options.show_eval_warnings
+            }),
+            cmd if cmd.starts_with(":toml") => self.toml(buffer),
             cmd if cmd.starts_with(":add") => self.add_dep(buffer),
             cmd if cmd.starts_with(":hard_load_crate") => self.hard_load_crate(buffer),
             cmd if cmd.starts_with(":hard_load") => self.hard_load(buffer),
             cmd if cmd.starts_with(":load") => self.load(buffer),
             cmd if cmd.starts_with(":reload") => self.reload(),
-            cmd if cmd.starts_with(":type") => self.show_type(),
+            cmd if cmd.starts_with(":rename") => self.rename(buffer),
+            cmd if cmd.starts_with(":save") => self.save(buffer),
+            cmd if cmd.starts_with(":type") => self.show_type(buffer),
             cmd if cmd.starts_with(":del") => self.del(buffer),
             cmd if cmd.starts_with(":dbg") => self.dbg(buffer),
             cmd if cmd.starts_with(":color") => self.color(buffer),
@@ -70,15 +375,49 @@ impl IRust {
             cmd if cmd.starts_with(":main_result") => self.main_result(buffer),
             cmd if cmd.starts_with(":check_statements") => self.check_statements(buffer),
             cmd if cmd.starts_with(":time_release") => self.time_release(buffer),
+            cmd if cmd.starts_with(":time-build") => self.time_build(buffer),
             cmd if cmd.starts_with(":time") => self.time(buffer),
-            cmd if cmd.starts_with(":bench") => self.bench(),
+            cmd if cmd.starts_with(":benchmark-compare") => self.benchmark_compare(buffer),
+            cmd if cmd.starts_with(":bench") => self.bench(buffer),
             cmd if cmd.starts_with(":asm") => self.asm(buffer),
+            cmd if cmd.starts_with(":await") => self.await_expression(buffer),
             cmd if cmd.starts_with(":executor") => self.executor(buffer),
             cmd if cmd.starts_with(":evaluator") => self.evaluator(buffer),
             cmd if cmd.starts_with(":scripts") => self.scripts(buffer),
             cmd if cmd.starts_with(":compile_time") => self.compile_time(buffer),
             cmd if cmd.starts_with(":compile_mode") => self.compile_mode(buffer),
+            cmd if cmd == ":prompt" || cmd.starts_with(":prompt ") => self.prompt(buffer),
+            cmd if cmd == ":profile" || cmd.starts_with(":profile ") => self.profile(buffer),
             cmd if cmd.starts_with(":expand") => self.expand(buffer),
+            cmd if cmd.starts_with(":env-file") => self.env_file(buffer),
+            cmd if cmd.starts_with(":config") => self.config(buffer),
+            cmd if cmd.starts_with(":doc") => self.doc(buffer),
+            cmd if cmd.starts_with(":eval-with") => self.eval_with(buffer),
+            cmd if cmd.starts_with(":trait-impls") => self.trait_impls(buffer),
+            cmd if cmd.starts_with(":explain-type") => self.explain_type(buffer),
+            cmd if cmd.starts_with(":inspect") => self.inspect(buffer),
+            cmd if cmd.starts_with(":compare-toolchains") => self.compare_toolchains(buffer),
+            cmd if cmd.starts_with(":input") => self.input(buffer),
+            cmd if cmd.starts_with(":errors") => self.errors(buffer),
+            cmd if cmd.starts_with(":hist-search") => self.hist_search(buffer),
+            cmd if cmd.starts_with(":history") => self.session_history(buffer),
+            cmd if cmd.starts_with(":grep") => self.grep(buffer),
+            cmd if cmd.starts_with(":bind") => self.bind(buffer),
+            cmd if cmd.starts_with(":alias-type") => self.alias_type(buffer),
+            // `:alias` is another name of `:bind`
+            cmd if cmd == ":alias" || cmd.starts_with(":alias ") => self.bind(buffer),
+            cmd if cmd.starts_with(":display") => self.display(buffer),
+            cmd if cmd.starts_with(":keep") => self.keep(buffer),
+            cmd if cmd.starts_with(":note") => self.note(buffer),
+            cmd if cmd.starts_with(":shell-bind") => self.shell_bind(buffer),
+            cmd if cmd.starts_with(":search") => self.search(buffer),
+            cmd if cmd == ":out" || cmd.starts_with(":out ") => self.out(buffer),
+            cmd if cmd == ":watch" || cmd.starts_with(":watch ") => self.watch(buffer),
+            cmd if cmd.starts_with(":export") => self.export(buffer),
+            cmd if cmd.starts_with(":assert") => self.assert(buffer),
+            cmd if cmd.starts_with(":step") => self.step(buffer),
+            cmd if cmd.starts_with(":unkeep") => self.unkeep(buffer),
+            cmd if crate::utils::shell_assignment(cmd).is_some() => self.shell_assign(buffer),
             cmd if self.options.shell_interpolate && cmd.contains("$$") => {
                 let buffer = self.shell_interpolate(buffer)?;
                 self.parse_second_order(buffer)
@@ -87,33 +426,653 @@ impl IRust {
         }
     }
 
-    fn reset(&mut self) -> Result<PrintQueue> {
+    fn reset(&mut self, buffer: String) -> Result<PrintQueue> {
+        const USAGE: &str = "Usage: `:reset [--yes] [--keep] [--keep-deps] [template]`";
+        let (flags, names): (Vec<&str>, Vec<&str>) = buffer
+            .split_whitespace()
+            .skip(1)
+            .partition(|arg| arg.starts_with('-'));
+        if names.len() > 1
+            || flags
+                .iter()
+                .any(|flag| !["--yes", "--keep", "--keep-deps"].contains(flag))
+        {
+            return Err(USAGE.into());
+        }
+        // read the template before the reset so a missing one keeps the session
+        let template = names
+            .first()
+            .map(|name| self.read_template(name))
+            .transpose()?;
+        if !self.confirm_destructive(&buffer, "This will remove all the repl code")? {
+            return print_queue!(
+                "Aborted".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        let kept = if flags.contains(&"--keep") {
+            crate::utils::binding_statements(&self.code_before_cursor(), &self.kept_bindings)
+        } else {
+            vec![]
+        };
+        // the build artifacts are in the shared target directory that the reset doesn't touch
+        let deps = if flags.contains(&"--keep-deps") {
+            self.repl.cargo.dependency_lines()?
+        } else {
+            vec![]
+        };
+        self.repl.reset()?;
+        if !deps.is_empty() {
+            self.repl.cargo.add_dependency_lines(&deps)?;
+            self.wait_add(self.repl.build()?, "Build")?;
+            if let Some(ra) = self.completer.as_mut() {
+                ra.rust_analyzer.reload_workspace()?;
+            }
+        }
+        if let Some(template) = template {
+            let (path, template) = template;
+            let (commands, code) = crate::utils::split_template(&template);
+            for command in commands {
+                self.add_dep(command)?;
+            }
+            let output = self.load_code(code, &path)?;
+            if !is_success(&output) {
+                return Ok(output);
+            }
+            self.repl.write_to_extern()?;
+        }
+        if !kept.is_empty() {
+            for statement in kept {
+                self.repl.insert(statement);
+            }
+            self.repl.write_to_extern()?;
+        }
+        success!(self)
+    }
+
+    /// The path and the code of the `:reset` template `name`
+    fn read_template(&self, name: &str) -> Result<(PathBuf, String)> {
+        let dir = crate::utils::templates_dir().ok_or("Error accessing config_dir")?;
+        let path = dir.join(format!("{name}.rs"));
+        if !path.is_file() {
+            let names = crate::utils::template_names(&dir);
+            let available = if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            };
+            return Err(format!(
+                "Template `{name}` not found in {}, available templates: {available}",
+                dir.display()
+            )
+            .into());
+        }
+        let template = std::fs::read_to_string(&path)?;
+        Ok((path, template))
+    }
+
+    fn snippet(&mut self, buffer: String) -> Result<PrintQueue> {
+        const USAGE: &str = "Usage: `:snippet save <name>`, `:snippet list` or `:snippet <name>`";
+        let dir = crate::utils::snippets_dir().ok_or("Error accessing config_dir")?;
+        let args: Vec<&str> = buffer
+            .strip_prefix(":snippet")
+            .expect("already checked")
+            .split_whitespace()
+            .collect();
+        let snippet_path = |name: &str| -> Result<PathBuf> {
+            if !crate::utils::is_ident(name) || matches!(name, "save" | "list") {
+                return Err(
+                    format!("Invalid snippet name `{name}`, it should be an identifier other than `save` and `list`").into(),
+                );
+            }
+            Ok(dir.join(format!("{name}.rs")))
+        };
+        match args[..] {
+            ["list"] => {
+                let names = crate::utils::template_names(&dir);
+                if names.is_empty() {
+                    return print_queue!(
+                        "No snippets".into(),
+                        self.theme.output_color(OutputKind::Warning)
+                    );
+                }
+                print_queue!(names.join("\n"), self.theme.output_color(OutputKind::Value))
+            }
+            ["save", name] => {
+                let path = snippet_path(name)?;
+                let block_len = crate::utils::last_block_len(self.repl.inserted_lines());
+                if block_len == 0 {
+                    return Err("Nothing to save, the snippet is the last inserted block".into());
+                }
+                let inserted_lines = self.repl.inserted_lines();
+                let block = inserted_lines[inserted_lines.len() - block_len..].join("\n");
+                std::fs::create_dir_all(&dir)?;
+                std::fs::write(&path, block + "\n")?;
+                print_queue!(
+                    format!("Saved to {}", path.display()),
+                    self.theme.output_color(OutputKind::Success)
+                )
+            }
+            [name] if name != "save" => {
+                let path = snippet_path(name)?;
+                if !path.is_file() {
+                    return Err(format!(
+                        "Snippet `{name}` not found in {}, available snippets: {}",
+                        dir.display(),
+                        crate::utils::template_names(&dir).join(", ")
+                    )
+                    .into());
+                }
+                // like a typed input, the errors are shown and nothing is inserted
+                let code = std::fs::read_to_string(&path)?;
+                self.parse_second_order(code.trim_end().to_string())
+            }
+            _ => Err(USAGE.into()),
+        }
+    }
+
+    /// Recreate the repl cargo project from scratch then add the dependencies and the code again
+    fn restart(&mut self) -> Result<PrintQueue> {
+        let deps = self.repl.cargo.dependency_lines()?;
+        let lines = self.repl.inserted_lines().to_vec();
+
+        self.repl.cargo.delete_project()?;
         self.repl.reset()?;
-        success!()
+        if !deps.is_empty() {
+            self.repl.cargo.add_dependency_lines(&deps)?;
+        }
+        self.wait_add(self.repl.build()?, "Build")?;
+
+        // the type aliases are already inserted by the reset
+        let fresh = self.repl.inserted_lines().len();
+        let lines = if lines.starts_with(self.repl.inserted_lines()) {
+            &lines[fresh..]
+        } else {
+            &lines[..]
+        };
+        let code = lines.join("\n");
+
+        let mut print_queue = PrintQueue::default();
+        self.before_compiling_hook();
+        let result = self.repl.eval_build(code.clone());
+        self.after_compiling_hook();
+        if result?.status.success() {
+            self.repl.insert(code);
+        } else {
+            // replay block by block, the blocks that don't compile anymore are skipped
+            for block in crate::utils::code_blocks(lines) {
+                let block = lines[block].join("\n");
+                self.before_compiling_hook();
+                let result = self.repl.eval_build(block.clone());
+                self.after_compiling_hook();
+                let EvalResult { output, status, .. } = result?;
+                if status.success() {
+                    self.repl.insert(block);
+                    continue;
+                }
+                print_queue.push(PrinterItem::Str(
+                    "Skipped:",
+                    self.theme.output_color(OutputKind::Warning),
+                ));
+                print_queue.add_new_line(1);
+                let block: Vec<char> = block.chars().collect();
+                print_queue.append(&mut highlight(&block.into(), &self.theme));
+                print_queue.add_new_line(1);
+                print_queue.append(&mut self.format_compile_error(output));
+            }
+        }
+        self.repl.write_to_extern()?;
+
+        if let Some(ra) = self.completer.as_mut() {
+            ra.rust_analyzer.reload_workspace()?;
+        }
+
+        if print_queue.is_empty() {
+            success!(self)
+        } else {
+            Ok(print_queue)
+        }
+    }
+
+    /// The repl code up to where the next input is inserted
+    fn code_before_cursor(&self) -> String {
+        self.repl
+            .lines()
+            .take(self.repl.cursor())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn note(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :note --insert the parser is too slow
+        const USAGE: &str = "Usage: `:note [--insert] <text>`";
+        let note = buffer.strip_prefix(":note").expect("already checked");
+        // exp: `:notes x`
+        if !note.starts_with(char::is_whitespace) {
+            return Err(USAGE.into());
+        }
+        let note = note.trim();
+        let (note, insert) = match note.strip_prefix("--insert") {
+            Some(note) => (note.trim(), true),
+            None => (note, false),
+        };
+        if note.is_empty() {
+            return Err(USAGE.into());
+        }
+        // Add it to the code as a comment
+        if insert {
+            for line in note.lines() {
+                self.repl.insert(format!("// {line}"));
+            }
+            self.repl.write_to_extern()?;
+        }
+        self.notes
+            .push((self.session_start.elapsed(), note.to_string()));
+        success!(self)
+    }
+
+    fn notes(&mut self) -> Result<PrintQueue> {
+        if self.notes.is_empty() {
+            return print_queue!(
+                "No notes".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        print_queue!(
+            crate::utils::format_notes(&self.notes),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn keep(&mut self, buffer: String) -> Result<PrintQueue> {
+        let name = buffer
+            .strip_prefix(":keep")
+            .expect("already checked")
+            .trim();
+        if name.is_empty() || name == "--list" {
+            if self.kept_bindings.is_empty() {
+                return print_queue!(
+                    "No kept bindings".into(),
+                    self.theme.output_color(OutputKind::Warning)
+                );
+            }
+            let kept = self
+                .kept_bindings
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n");
+            return print_queue!(kept, self.theme.output_color(OutputKind::Value));
+        }
+        if crate::utils::binding_statements(&self.code_before_cursor(), &[name.to_string()].into())
+            .is_empty()
+        {
+            return Err(format!("No binding named `{name}`").into());
+        }
+        self.kept_bindings.insert(name.to_string());
+        success!(self)
+    }
+
+    fn unkeep(&mut self, buffer: String) -> Result<PrintQueue> {
+        let name = buffer
+            .strip_prefix(":unkeep")
+            .expect("already checked")
+            .trim();
+        if name.is_empty() {
+            return Err("Usage: `:unkeep <name>`".into());
+        }
+        if !self.kept_bindings.remove(name) {
+            return Err(format!("`{name}` is not kept").into());
+        }
+        success!(self)
+    }
+
+    /// Ask the user to confirm a destructive action (if `confirm-destructive` is on)
+    /// `--yes` skips the prompt, without it non-interactive sessions always decline
+    fn confirm_destructive(&mut self, buffer: &str, warning: &str) -> Result<bool> {
+        use crossterm::event::{Event, KeyEvent, KeyEventKind};
+        use std::io::IsTerminal;
+
+        if let Some(answer) = crate::utils::destructive_confirmation(
+            self.options.confirm_destructive,
+            buffer,
+            std::io::stdin().is_terminal(),
+        ) {
+            return Ok(answer);
+        }
+
+        self.printer.writer.raw.write_with_color(
+            &format!("{warning}, continue? [y/N] "),
+            self.theme.output_color(OutputKind::Warning),
+        )?;
+        self.printer.writer.raw.flush()?;
+        let answer = loop {
+            if let Event::Key(KeyEvent { code, kind, .. }) = crossterm::event::read()? {
+                if kind == KeyEventKind::Release {
+                    continue;
+                }
+                if let Some(answer) = crate::utils::confirmation_answer(code) {
+                    break answer;
+                }
+            }
+        };
+        self.printer.write_newline(&self.buffer);
+        Ok(answer)
+    }
+
+    fn step(&mut self, buffer: String) -> Result<PrintQueue> {
+        let code = buffer
+            .strip_prefix(":step")
+            .expect("already checked")
+            .trim();
+        let statements = crate::utils::split_statements(code);
+        if statements.is_empty() {
+            return Err("Usage: `:step <code>`".into());
+        }
+
+        for (idx, statement) in statements.iter().enumerate() {
+            let mut print_queue = PrintQueue::default();
+            print_queue.push(PrinterItem::String(
+                format!("[{}/{}] {statement}", idx + 1, statements.len()),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            print_queue.add_new_line(1);
+
+            // Stop on the first error
+            let value = if crate::utils::is_statement(statement, self.options.auto_insert_semicolon)
+            {
+                let result = self.repl.eval_check(statement.to_string())?;
+                if !result.status.success() {
+                    print_queue.append(&mut self.format_compile_error(result.output));
+                    return Ok(print_queue);
+                }
+                self.repl.insert(statement);
+                self.repl.write_to_extern()?;
+                // Show the new binding
+                crate::utils::let_binding_name(statement)
+                    .map(|name| -> Result<_> {
+                        let result = self.repl.eval(name)?;
+                        Ok(result
+                            .status
+                            .success()
+                            .then(|| format!("{name} = {}", result.output)))
+                    })
+                    .transpose()?
+                    .flatten()
+            } else {
+                let result = self.repl.eval(statement)?;
+                if !result.status.success() {
+                    print_queue.append(&mut self.format_compile_error(result.output));
+                    return Ok(print_queue);
+                }
+                Some(result.output)
+            };
+            if let Some(value) = value {
+                print_queue.push(PrinterItem::String(
+                    value,
+                    self.theme.output_color(OutputKind::Value),
+                ));
+                print_queue.add_new_line(1);
+            }
+
+            if idx + 1 == statements.len() {
+                return Ok(print_queue);
+            }
+            self.printer.print_output(print_queue)?;
+            if !self.wait_for_next_step()? {
+                return print_queue!(
+                    "Aborted".into(),
+                    self.theme.output_color(OutputKind::Warning)
+                );
+            }
+        }
+        unreachable!("the last statement returns")
+    }
+
+    /// Wait for a key press between `:step` steps, returns false if the user stopped (Esc, q or CtrlC)
+    fn wait_for_next_step(&mut self) -> Result<bool> {
+        use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+        use std::io::IsTerminal;
+
+        if !std::io::stdin().is_terminal() {
+            return Ok(true);
+        }
+        self.printer.writer.raw.write_with_color(
+            "Press a key for the next step (q to stop)",
+            self.theme.output_color(OutputKind::Warning),
+        )?;
+        self.printer.writer.raw.flush()?;
+        let next = loop {
+            if let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            }) = crossterm::event::read()?
+            {
+                if kind == KeyEventKind::Release {
+                    continue;
+                }
+                break !(matches!(code, KeyCode::Esc | KeyCode::Char('q'))
+                    || code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL));
+            }
+        };
+        self.printer.write_newline(&self.buffer);
+        Ok(next)
+    }
+
+    /// Read a line of text from the user, returns `None` if it was cancelled with Esc or CtrlC
+    fn read_line(&mut self, prompt: &str) -> Result<Option<String>> {
+        use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+        self.printer
+            .writer
+            .raw
+            .write_with_color(prompt, self.theme.output_color(OutputKind::Warning))?;
+        self.printer.writer.raw.flush()?;
+        let mut line = String::new();
+        let line = loop {
+            let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            }) = crossterm::event::read()?
+            else {
+                continue;
+            };
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+            match code {
+                KeyCode::Enter => break Some(line),
+                KeyCode::Esc => break None,
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break None,
+                KeyCode::Backspace if !line.is_empty() => {
+                    line.pop();
+                    self.printer.writer.raw.write("\u{8} \u{8}")?;
+                }
+                KeyCode::Char(c) => {
+                    line.push(c);
+                    self.printer.writer.raw.write(c)?;
+                }
+                _ => (),
+            }
+            self.printer.writer.raw.flush()?;
+        };
+        self.printer.write_newline(&self.buffer);
+        Ok(line)
+    }
+
+    fn input(&mut self, buffer: String) -> Result<PrintQueue> {
+        let arg = buffer
+            .strip_prefix(":input")
+            .expect("already checked")
+            .trim();
+        if arg.is_empty() {
+            return Err("Usage: `:input <name>` or `:input <name>:<type>`".into());
+        }
+        let (name, ty) = match arg.split_once(':') {
+            Some((name, ty)) => (name.trim(), Some(ty.trim())),
+            None => (arg, None),
+        };
+
+        let Some(value) = self.read_line(&format!("{name}: "))? else {
+            return print_queue!(
+                "Aborted".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        };
+        let binding = crate::utils::input_binding(name, ty, &value)?;
+
+        // Make sure the value can be parsed before inserting it, so it doesn't panic in every later evaluation
+        if let Some(ty) = ty.filter(|ty| *ty != "String") {
+            let EvalResult { output, status, .. } = self
+                .repl
+                .eval(format!("{value:?}.parse::<{ty}>().is_ok()"))?;
+            if !status.success() {
+                return Ok(format_err_printqueue(
+                    &output,
+                    self.options.show_warnings,
+                    &self.repl.cargo.name,
+                ));
+            }
+            if output != "true" {
+                return Err(format!("Failed to parse `{value}` as `{ty}`").into());
+            }
+        }
+
+        self.repl.insert(binding);
+        self.repl.write_to_extern()?;
+        success!(self)
     }
 
     fn pop(&mut self) -> Result<PrintQueue> {
         self.repl.pop();
-        success!()
+        success!(self)
+    }
+
+    fn undo(&mut self) -> Result<PrintQueue> {
+        let block_len = crate::utils::last_block_len(self.repl.inserted_lines());
+        if block_len == 0 {
+            return Err("Nothing to undo".into());
+        }
+        let removed = self.repl.remove_last_lines(block_len).join("\n");
+        self.repl.write_to_extern()?;
+
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::Str(
+            "Undone:",
+            self.theme.output_color(OutputKind::Info),
+        ));
+        print_queue.add_new_line(1);
+        let removed: Vec<char> = removed.chars().collect();
+        print_queue.append(&mut highlight(&removed.into(), &self.theme));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
     }
 
     fn check_statements(&mut self, buffer: String) -> Result<PrintQueue> {
         const ERROR: &str = "Invalid argument, accepted values are `false` `true`";
         let buffer = buffer.split_whitespace().nth(1).ok_or(ERROR)?;
         self.options.check_statements = buffer.parse().map_err(|_| ERROR)?;
-        success!()
+        success!(self)
     }
 
     fn del(&mut self, buffer: String) -> Result<PrintQueue> {
-        if let Some(line_num) = buffer.split_whitespace().last() {
-            self.repl.del(line_num)?;
+        let args = buffer.strip_prefix(":del").expect("already checked").trim();
+        if args.is_empty() {
+            return Err(
+                "Usage: `:del <line_num>`, `:del 3-5`, `:del 2 4 6` or `:del /pattern/`".into(),
+            );
+        }
+        // exp: :del /println!/
+        if let Some(pattern) = args
+            .strip_prefix('/')
+            .and_then(|args| args.strip_suffix('/'))
+            .filter(|pattern| !pattern.is_empty())
+        {
+            return self.del_matching(pattern);
+        }
+        let line_nums = crate::utils::parse_line_numbers(args)?;
+        self.repl.del_lines(&line_nums)?;
+        success!(self)
+    }
+
+    /// Remove the inserted blocks that contain `pattern`
+    fn del_matching(&mut self, pattern: &str) -> Result<PrintQueue> {
+        let inserted_lines = self.repl.inserted_lines();
+        // the body line of the first inserted line
+        let start = self.repl.cursor() - inserted_lines.len();
+        let matching: Vec<_> = crate::utils::code_blocks(inserted_lines)
+            .into_iter()
+            .filter(|block| inserted_lines[block.clone()].join("\n").contains(pattern))
+            .collect();
+        if matching.is_empty() {
+            return print_queue!(
+                format!("No blocks contain `{pattern}`"),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        let line_nums: Vec<usize> = matching
+            .iter()
+            .flat_map(|block| block.start + start..block.end + start)
+            .collect();
+
+        let (body, cursor) = (self.repl.body(), self.repl.cursor());
+        self.repl.del_lines(&line_nums)?;
+
+        self.before_compiling_hook();
+        let result = self.repl.eval_build(String::new());
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.repl.hard_load(body, cursor);
+                return Err(e);
+            }
+        };
+        // The remaining code depends on the removed blocks, go back to the previous code
+        if !status.success() {
+            self.repl.hard_load(body, cursor);
+            let mut print_queue = self.format_compile_error(output);
+            print_queue.push(PrinterItem::String(
+                "The deletion was reverted".into(),
+                self.theme.output_color(OutputKind::Warning),
+            ));
+            print_queue.add_new_line(1);
+            return Ok(print_queue);
         }
-        success!()
+        self.repl.write_to_extern()?;
+        print_queue!(
+            format!("Removed {} blocks", matching.len()),
+            self.theme.output_color(OutputKind::Success)
+        )
     }
 
-    fn show(&mut self) -> PrintQueue {
-        let code: Vec<char> = self.repl.show().chars().collect();
-        highlight(&code.into(), &self.theme)
+    fn show(&mut self, buffer: String) -> Result<PrintQueue> {
+        let filter = buffer
+            .strip_prefix(":show")
+            .expect("already checked")
+            .trim();
+        let code = self.repl.show();
+        let code = if filter.is_empty() {
+            code
+        } else {
+            // without the `Current Repl Code:` header
+            let code = code.split_once('\n').map_or("", |(_, code)| code);
+            let filtered = crate::utils::filter_shown_code(code, filter);
+            if filtered.is_empty() {
+                return print_queue!(
+                    "No matches".into(),
+                    self.theme.output_color(OutputKind::Warning)
+                );
+            }
+            filtered
+        };
+        let code: Vec<char> = code.chars().collect();
+        Ok(highlight(&code.into(), &self.theme))
     }
 
     fn toolchain(&mut self, buffer: String) -> Result<PrintQueue> {
@@ -123,9 +1082,12 @@ impl IRust {
             let toolchain = ToolChain::from_str(toolchain)?;
             self.repl.set_toolchain(toolchain);
             self.options.toolchain = toolchain;
-            success!()
+            success!(self)
         } else {
-            print_queue!(self.options.toolchain.to_string(), Color::Blue)
+            print_queue!(
+                self.options.toolchain.to_string(),
+                self.theme.output_color(OutputKind::Value)
+            )
         }
     }
 
@@ -136,9 +1098,13 @@ impl IRust {
             if let Ok(theme) = super::highlight::theme::theme(name.to_string()) {
                 self.theme = theme;
                 self.options.theme = name.to_string();
-                success!()
+                success!(self)
             } else {
-                Err("Failed to set theme".into())
+                Err(format!(
+                    "Failed to set theme, the built-in themes are: {}",
+                    super::highlight::theme::BUILTIN_THEMES.join(", ")
+                )
+                .into())
             }
         } else {
             let installed_themes: Vec<_> = super::highlight::theme::installed_themes()
@@ -155,9 +1121,18 @@ impl IRust {
                         .map(|t| format!("- {}", t.replace(".toml", "")))
                         .collect::<Vec<_>>()
                         .join("\n")
-                ))
+                ));
+                msg.push('\n');
             }
-            print_queue!(msg, Color::Blue)
+            msg.push_str(&format!(
+                "Built-in themes:\n{}",
+                super::highlight::theme::BUILTIN_THEMES
+                    .iter()
+                    .map(|t| format!("- {t}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+            print_queue!(msg, self.theme.output_color(OutputKind::Value))
         }
     }
 
@@ -168,45 +1143,83 @@ impl IRust {
             let main_result = MainResult::from_str(main_result)?;
             self.repl.set_main_result(main_result);
             self.options.main_result = main_result;
-            success!()
+            success!(self)
         } else {
-            print_queue!(self.options.main_result.to_string(), Color::Blue)
+            print_queue!(
+                self.options.main_result.to_string(),
+                self.theme.output_color(OutputKind::Value)
+            )
+        }
+    }
+
+    fn edition(&mut self, buffer: String) -> Result<PrintQueue> {
+        let Some(edition) = buffer.split_whitespace().nth(1) else {
+            return print_queue!(
+                self.repl.edition().to_string(),
+                self.theme.output_color(OutputKind::Value)
+            );
+        };
+        let edition = Edition::from_str(edition)?;
+        let previous_edition = self.repl.edition();
+        self.repl.set_edition(edition)?;
+
+        // the session code is rebuilt with the new edition
+        self.before_compiling_hook();
+        let result = self.repl.eval_build(String::new());
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result?;
+        if !status.success() {
+            self.repl.set_edition(previous_edition)?;
+            let mut print_queue = self.format_compile_error(output);
+            print_queue.push(PrinterItem::String(
+                format!("The edition was reverted to {previous_edition}"),
+                self.theme.output_color(OutputKind::Warning),
+            ));
+            print_queue.add_new_line(1);
+            return Ok(print_queue);
         }
+        self.options.edition = edition;
+        success!(self)
     }
 
     fn add_dep(&mut self, buffer: String) -> Result<PrintQueue> {
         let mut dep: Vec<String> = crate::utils::split_args(buffer);
         dep.remove(0); //drop :add
 
-        // Try to canonicalize all arguments that corresponds to an existing path
-        // This is necessary because `:add relative_path` doesn't work without it
-        // Note this might be a bit too aggressive (an argument might be canonicalized, that the user didn't not intend for it to be considered as a path)
-        // But the usefulness of this trick, outways this possible edge case
+        // Canonicalize the paths, this is necessary because `:add relative_path` doesn't work without it
         // canonicalize is problamatic on windows -> need to handle extended path
         #[cfg(unix)]
-        for p in dep.iter_mut() {
+        let canonicalize = |p: &str| {
             let path = std::path::Path::new(p);
-            if path.exists() {
-                if let Ok(full_path) = path.canonicalize() {
-                    if let Some(full_path) = full_path.to_str() {
-                        *p = full_path.to_string();
-                    }
-                }
+            if !path.exists() {
+                return None;
             }
-        }
+            Some(path.canonicalize().ok()?.to_str()?.to_string())
+        };
         // But still the most common case is `:add .` so we can special case that
         #[cfg(windows)]
-        for p in dep.iter_mut() {
-            if p == "." {
-                *p = self
-                    .global_variables
-                    .get_cwd()
-                    .to_str()
-                    .ok_or("Error parsing path to dependecy")?
-                    .to_string();
+        let canonicalize = |p: &str| {
+            if p != "." {
+                return None;
+            }
+            Some(self.global_variables.get_cwd().to_str()?.to_string())
+        };
+        let dep = crate::utils::add_dep_args(dep, canonicalize);
+
+        // The local crates are checked before cargo sees them, so the error is clear
+        let mut local_crates = vec![];
+        for path in crate::utils::add_dep_paths(&dep) {
+            let cargo_toml = std::path::Path::new(&path).join("Cargo.toml");
+            if !cargo_toml.is_file() {
+                return Err(format!("`{path}` is not a crate, it has no Cargo.toml").into());
             }
+            let name = crate::utils::package_name(&std::fs::read_to_string(&cargo_toml)?)
+                .map_err(|e| format!("Invalid crate `{path}`: {e}"))?;
+            local_crates.push(name);
         }
 
+        self.repl
+            .snapshot_manifest(format!(":add {}", dep.join(" ")))?;
         self.wait_add(self.repl.add_dep(&dep)?, "Add")?;
         self.wait_add(self.repl.build()?, "Build")?;
 
@@ -221,7 +1234,20 @@ impl IRust {
             ra.rust_analyzer.reload_workspace()?;
         }
 
-        success!()
+        if local_crates.is_empty() {
+            return success!(self);
+        }
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::Str(
+            SUCCESS,
+            self.theme.output_color(OutputKind::Success),
+        ));
+        print_queue.push(PrinterItem::String(
+            format!(" added {}", local_crates.join(", ")),
+            self.theme.output_color(OutputKind::Success),
+        ));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
     }
 
     fn color(&mut self, buffer: String) -> Result<PrintQueue> {
@@ -230,7 +1256,7 @@ impl IRust {
         // reset theme
         if buffer.peek() == Some(&"reset") {
             self.theme.reset();
-            return success!();
+            return success!(self);
         }
 
         let mut parse = || -> Result<()> {
@@ -252,16 +1278,62 @@ impl IRust {
 
         parse()?;
 
-        success!()
+        success!(self)
     }
 
     fn load(&mut self, buffer: String) -> Result<PrintQueue> {
-        let path = if let Some(path) = buffer.split_whitespace().nth(1) {
+        let (flags, args): (Vec<&str>, Vec<&str>) = buffer
+            .split_whitespace()
+            .skip(1)
+            .partition(|arg| arg.starts_with("--"));
+        let append = match flags[..] {
+            [] => false,
+            ["--append"] => true,
+            _ => return Err("Usage: `:load [--append] <path>`".into()),
+        };
+        let path = if let Some(path) = args.first() {
             std::path::Path::new(&path).to_path_buf()
         } else {
             return Err("No path specified").map_err(|e| e.into());
         };
-        self.load_inner(path)
+        if append {
+            self.append_inner(path)
+        } else {
+            self.load_inner(path)
+        }
+    }
+
+    fn watch(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :watch src/main.rs
+        let path = buffer
+            .strip_prefix(":watch")
+            .expect("already checked")
+            .trim();
+        if path.is_empty() {
+            return self.unwatch();
+        }
+        // canonicalized so `:cd` doesn't change the watched file
+        let path = std::path::Path::new(path)
+            .canonicalize()
+            .map_err(|e| format!("Can't watch `{path}`: {e}"))?;
+        self.watch = Some(super::watch_file(path.clone())?);
+
+        let mut print_queue = self.load_inner(path.clone())?;
+        print_queue.push(PrinterItem::String(
+            format!("Watching {}, `:unwatch` to stop", path.display()),
+            self.theme.output_color(OutputKind::Info),
+        ));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn unwatch(&mut self) -> Result<PrintQueue> {
+        // dropping the watcher stops watching
+        let watched = self.watch.take().ok_or("No file is watched")?;
+        print_queue!(
+            format!("Stopped watching {}", watched.path.display()),
+            self.theme.output_color(OutputKind::Success)
+        )
     }
 
     fn reload(&mut self) -> Result<PrintQueue> {
@@ -273,6 +1345,136 @@ impl IRust {
         self.load_inner(path)
     }
 
+    fn rename(&mut self, buffer: String) -> Result<PrintQueue> {
+        let args: Vec<&str> = buffer.split_whitespace().skip(1).collect();
+        let [old, new] = args[..] else {
+            return Err("Usage: `:rename <old> <new>`".into());
+        };
+        if let Some(name) = [old, new]
+            .into_iter()
+            .find(|name| !crate::utils::is_ident(name))
+        {
+            return Err(format!("`{name}` is not a valid identifier").into());
+        }
+
+        let (body, cursor) = (self.repl.body(), self.repl.cursor());
+        let (renamed, count) = crate::utils::rename_identifier(&body, old, new);
+        if count == 0 {
+            return Err(format!("`{old}` is not used in the repl code").into());
+        }
+        self.repl.hard_load(renamed, cursor);
+
+        self.before_compiling_hook();
+        let result = self.repl.eval_build(String::new());
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.repl.hard_load(body, cursor);
+                return Err(e);
+            }
+        };
+        // The rename broke the code, go back to the previous code
+        if !status.success() {
+            self.repl.hard_load(body, cursor);
+            let mut print_queue = self.format_compile_error(output);
+            print_queue.push(PrinterItem::String(
+                "The rename was reverted".into(),
+                self.theme.output_color(OutputKind::Warning),
+            ));
+            print_queue.add_new_line(1);
+            return Ok(print_queue);
+        }
+        self.repl.write_to_extern()?;
+        print_queue!(
+            format!("Renamed {count} occurrences of `{old}` to `{new}`"),
+            self.theme.output_color(OutputKind::Success)
+        )
+    }
+
+    fn save(&mut self, buffer: String) -> Result<PrintQueue> {
+        const DEFAULT_PATH: &str = "irust_session.rs";
+        // A plain `:save` reuses the last loaded or saved path
+        let path = match buffer.split_whitespace().nth(1) {
+            Some(path) => PathBuf::from(path),
+            None => self
+                .global_variables
+                .get_last_loaded_coded_path()
+                .filter(|path| !path.to_str().is_some_and(crate::utils::is_url))
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_PATH)),
+        };
+
+        let mut session = String::new();
+        // The notes are kept as comments
+        for note in crate::utils::format_notes(&self.notes).lines() {
+            session += &format!("// {note}\n");
+        }
+        session += &self.repl.user_code();
+        session.push('\n');
+        std::fs::write(&path, session)
+            .map_err(|e| format!("Failed to save the session to {}: {e}", path.display()))?;
+
+        self.global_variables.set_last_loaded_coded_path(path);
+        success!(self)
+    }
+
+    fn export(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :export ../experiment --force
+        const USAGE: &str = "Usage: `:export [--force] [--yes] <dir>`";
+        let (flags, args): (Vec<&str>, Vec<&str>) = buffer
+            .split_whitespace()
+            .skip(1)
+            .partition(|arg| arg.starts_with("--"));
+        if flags
+            .iter()
+            .any(|flag| !["--force", "--yes"].contains(flag))
+        {
+            return Err(USAGE.into());
+        }
+        let force = flags.contains(&"--force");
+        let [dir] = args[..] else {
+            return Err(USAGE.into());
+        };
+        let dir = std::path::Path::new(dir);
+        if dir
+            .read_dir()
+            .is_ok_and(|mut entries| entries.next().is_some())
+        {
+            if !force {
+                return Err(format!(
+                    "`{}` is not empty, use `--force` to overwrite it",
+                    dir.display()
+                )
+                .into());
+            }
+            if !self.confirm_destructive(
+                &buffer,
+                &format!("This will overwrite the files of {}", dir.display()),
+            )? {
+                return print_queue!(
+                    "Aborted".into(),
+                    self.theme.output_color(OutputKind::Warning)
+                );
+            }
+        }
+
+        std::fs::create_dir_all(dir.join("src"))?;
+        let cargo_toml = dir.join("Cargo.toml");
+        std::fs::copy(&self.repl.cargo.paths.cargo_toml_file, &cargo_toml)?;
+        // the package is named after the directory instead of the repl
+        let name = crate::utils::package_name_of(&std::fs::canonicalize(dir)?);
+        patch_name_to(&cargo_toml, &name)?;
+        std::fs::write(dir.join("src").join("main.rs"), self.repl.standalone_code())?;
+
+        print_queue!(
+            format!(
+                "Exported the session to {}, run it with `cargo run` in it",
+                dir.display()
+            ),
+            self.theme.output_color(OutputKind::Success)
+        )
+    }
+
     pub fn hard_load_crate(&mut self, buffer: String) -> Result<PrintQueue> {
         // 0- Load to repl
         let _ = self.hard_load(buffer.clone())?;
@@ -315,7 +1517,7 @@ impl IRust {
             &self.repl.cargo.paths.irust_src_dir,
         )?;
 
-        success!()
+        success!(self)
     }
 
     pub fn hard_load(&mut self, buffer: String) -> Result<PrintQueue> {
@@ -326,7 +1528,14 @@ impl IRust {
         let code = std::fs::read_to_string(buffer[1])?;
         let cursor: usize = buffer[2].parse()?;
         self.repl.hard_load(code, cursor);
-        success!()
+        success!(self)
+    }
+
+    /// Like `load_inner` but the code is inserted after the current repl code
+    /// The build runs in a temporary repl, so a failed append leaves the session as it was
+    fn append_inner(&mut self, path: PathBuf) -> Result<PrintQueue> {
+        let code = crate::utils::loaded_code(&path)?;
+        self.load_code(code, &path)
     }
 
     pub fn load_inner(&mut self, path: PathBuf) -> Result<PrintQueue> {
@@ -334,101 +1543,305 @@ impl IRust {
         self.global_variables
             .set_last_loaded_coded_path(path.clone());
 
+        // read code, before the reset so a failed read keeps the session
+        let code = crate::utils::loaded_code(&path)?;
+
         // reset repl
         self.repl.reset()?;
 
-        // read code
-        let code = std::fs::read_to_string(path)?;
+        self.load_code(code, &path)
+    }
 
-        // build the code
-        let EvalResult { output, status } = self.repl.eval_build(code.clone())?;
+    /// Build the code of `path` then insert it if it compiles
+    fn load_code(&mut self, code: String, path: &std::path::Path) -> Result<PrintQueue> {
+        let EvalResult { output, status, .. } = self.repl.eval_build(code.clone())?;
 
         if !status.success() {
-            Ok(format_err_printqueue(
-                &output,
-                self.options.show_warnings,
-                &self.repl.cargo.name,
-            ))
+            Ok(self.load_error(&output, &code, path))
         } else {
             self.repl.insert(code);
-            success!()
+            success!(self)
+        }
+    }
+
+    /// The build error of the loaded `code`, with the line of `path` where the first error is
+    fn load_error(&self, output: &str, code: &str, path: &std::path::Path) -> PrintQueue {
+        let mut print_queue =
+            format_err_printqueue(output, self.options.show_warnings, &self.repl.cargo.name);
+        // crate attributes are inserted before the main function
+        if code.trim_start().starts_with("#!") {
+            return print_queue;
+        }
+        // the code is inserted at the cursor
+        let code_start = self.repl.main_file_line(self.repl.cursor());
+        let line = first_error_line(output)
+            .and_then(|line| line.checked_sub(code_start))
+            .map(|offset| offset + 1)
+            .filter(|&line| line <= code.lines().count());
+        if let Some(line) = line {
+            print_queue.add_new_line(1);
+            print_queue.push(PrinterItem::String(
+                format!("in {} near line {line}", path.display()),
+                self.theme.output_color(OutputKind::Error),
+            ));
+            print_queue.add_new_line(1);
         }
+        print_queue
     }
 
-    fn show_type(&mut self) -> Result<PrintQueue> {
+    fn show_type(&mut self, buffer: String) -> Result<PrintQueue> {
         // TODO
         // We should probably use the `Any` trait instead of the current method
         // Current method might break with compiler updates
         // On the other hand `Any` is more limited
 
-        const TYPE_FOUND_MSG: &str = "expected `()`, found ";
-        const EMPTY_TYPE_MSG: &str = "dev [unoptimized + debuginfo]";
-
-        let variable = self
-            .buffer
-            .to_string()
-            .trim_start_matches(":type")
+        let variable = buffer
+            .strip_prefix(":type")
+            .expect("already checked")
             .to_string();
-        let mut raw_out = String::new();
+        if let Some(expression) = variable.trim_start().strip_prefix("--full") {
+            return self.show_full_type(expression);
+        }
+        // The compiler doesn't show the lifetimes, prefer the annotated type of a binding
+        if let Some(var_type) =
+            crate::utils::annotated_type(&self.code_before_cursor(), variable.trim())
+        {
+            return print_queue!(
+                crate::utils::apply_type_display_aliases(
+                    &var_type,
+                    &self.options.type_display_aliases
+                ),
+                self.theme.output_color(OutputKind::Value)
+            );
+        }
 
-        let toolchain = self.options.toolchain;
-        let get_type = format!("let _:() = {variable};");
+        // `:type let x = foo();` shows the type of `x`, the binding is only inserted in the tmp repl
+        let probe = if variable.trim_start().starts_with("let ") {
+            crate::probe::let_type_probe(&variable)?
+        } else {
+            crate::probe::type_probe(&variable)
+        };
 
-        let cargo = self.repl.cargo.clone();
-        self.repl.eval_in_tmp_repl(get_type, |_| -> Result<()> {
-            let (_status, out) = cargo.cargo_build_output(false, false, toolchain)?;
-            raw_out = out;
-            Ok(())
+        // The types are cached until the repl state changes (the code, the dependencies or the settings)
+        let state_hash = self.repl.state_hash()?;
+        if self.type_cache.0 != state_hash {
+            self.type_cache = (state_hash, HashMap::new());
+        }
+        let var_type = match self.type_cache.1.get(variable.trim()) {
+            Some(var_type) => var_type.clone(),
+            None => {
+                let toolchain = self.options.toolchain;
+                let cargo = self.repl.cargo.clone();
+                let (status, raw_out) = self.repl.eval_in_tmp_repl(probe, |_| -> Result<_> {
+                    Ok(cargo.cargo_build_output(false, false, toolchain)?)
+                })?;
+
+                let var_type = if let Some(found) = crate::probe::parse_found_type(&raw_out) {
+                    found
+                } else if status.success() {
+                    "()".into()
+                } else {
+                    "Unknown".into()
+                };
+                self.type_cache
+                    .1
+                    .insert(variable.trim().to_string(), var_type.clone());
+                var_type
+            }
+        };
+
+        let var_type =
+            crate::utils::apply_type_display_aliases(&var_type, &self.options.type_display_aliases);
+
+        // Show the value of integer literals in multiple bases
+        if let Some(bases) = crate::utils::int_literal_bases(&variable) {
+            return print_queue!(
+                format!("{var_type}\n{bases}"),
+                self.theme.output_color(OutputKind::Value)
+            );
+        }
+
+        print_queue!(var_type, self.theme.output_color(OutputKind::Value))
+    }
+
+    fn show_full_type(&mut self, expression: &str) -> Result<PrintQueue> {
+        if expression.trim().is_empty() {
+            return Err("Usage: `:type --full <expression>`".into());
+        }
+        let result = self.repl.eval_with_configuration(EvalConfig {
+            input: crate::probe::full_type_probe(expression),
+            interactive_function: Some(ctrlc_cancel),
+            color: true,
+            evaluator: &crate::utils::unit_macro_evaluator(),
+            compile_mode: CompileMode::Debug,
         })?;
+        if !result.status.success() {
+            return Ok(self.format_compile_error(result.output));
+        }
+        let full_type = crate::probe::parse_full_type(&result.output)
+            .ok_or_else(|| format!("Failed to find the type:\n{}", result.output))?;
+        print_queue!(full_type, self.theme.output_color(OutputKind::Value))
+    }
 
-        let var_type = if raw_out.contains(TYPE_FOUND_MSG) {
-            raw_out
-                .lines()
-                // there is a case where there could be 2 found msg
-                // the second one is more detailed
-                .rev()
-                .find(|l| l.contains("found"))
-                // safe
-                .unwrap()
-                .rsplit("found ")
-                .next()
-                // safe
-                .unwrap()
-                .to_string()
-        } else if raw_out.contains(EMPTY_TYPE_MSG) {
-            "()".into()
-        } else {
-            "Unknown".into()
+    fn complete(&mut self, buffer: String) -> Result<PrintQueue> {
+        let prefix = buffer
+            .strip_prefix(":complete")
+            .expect("already checked")
+            .trim();
+        let completions = crate::utils::completions(&self.code_before_cursor(), prefix);
+        if completions.is_empty() {
+            return print_queue!(
+                format!("No completions for `{prefix}`"),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        print_queue!(
+            completions.join("\n"),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn vars(&mut self) -> Result<PrintQueue> {
+        let code = self.code_before_cursor();
+        let names = crate::utils::bound_variables(&code);
+        if names.is_empty() {
+            return print_queue!(
+                "No variables".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+
+        let toolchain = self.options.toolchain;
+        let cargo = self.repl.cargo.clone();
+        let mut found_type = |probe: String| -> Option<String> {
+            let cargo = cargo.clone();
+            let (_, raw_out) = self
+                .repl
+                .eval_in_tmp_repl(probe, |_| -> Result<_> {
+                    Ok(cargo.cargo_build_output(false, false, toolchain)?)
+                })
+                .ok()?;
+            crate::probe::parse_found_type(&raw_out)
         };
+        // One build for all the variables, if it fails probe them one by one
+        let mut types = found_type(crate::probe::vars_probe(&names))
+            .map(|ty| crate::probe::split_tuple_type(&ty))
+            .unwrap_or_default();
+        if types.len() != names.len() {
+            types = names
+                .iter()
+                .map(|name| found_type(crate::probe::type_probe(name)))
+                .map(Option::unwrap_or_default)
+                .collect();
+        }
 
-        print_queue!(var_type, self.options.ok_color)
+        let mut print_queue = PrintQueue::default();
+        for (name, found) in names.iter().zip(types) {
+            // The compiler doesn't show the lifetimes, prefer the annotated type
+            let var_type = crate::utils::annotated_type(&code, name)
+                .or(Some(found).filter(|ty| !ty.is_empty()))
+                .map(|ty| {
+                    crate::utils::apply_type_display_aliases(
+                        &ty,
+                        &self.options.type_display_aliases,
+                    )
+                })
+                .unwrap_or_else(|| "?".into());
+            print_queue.push(PrinterItem::String(
+                name.clone(),
+                self.theme.output_color(OutputKind::Value),
+            ));
+            print_queue.push(PrinterItem::Str(": ", Color::White));
+            print_queue.push(PrinterItem::String(
+                var_type,
+                self.theme.output_color(OutputKind::Value),
+            ));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
     }
 
     fn run_cmd(&mut self, buffer: String) -> Result<PrintQueue> {
         // remove ::
         let buffer = &buffer[2..];
 
-        let mut cmd = buffer.split_whitespace();
-        let output = stdout_and_stderr(if cfg!(windows) {
-            std::process::Command::new("cmd")
-                .arg("/C")
-                .arg(cmd.next().unwrap_or_default())
-                .args(&cmd.collect::<Vec<&str>>())
-                .output()?
+        let output = crate::utils::shell_output(buffer, &self.global_variables.get_cwd())?;
+        let status = output.status;
+        self.last_status = Some(status);
+        let output = stdout_and_stderr(output).trim().to_owned();
+
+        if status.success() {
+            return print_queue!(output, self.options.shell_color);
+        }
+        // failures stand out with the error color and their exit status
+        let mut print_queue = PrintQueue::default();
+        if !output.is_empty() {
+            print_queue.push(PrinterItem::String(
+                output,
+                self.theme.output_color(OutputKind::Error),
+            ));
+            print_queue.add_new_line(1);
+        }
+        print_queue.push(PrinterItem::String(
+            status.to_string(),
+            self.theme.output_color(OutputKind::Warning),
+        ));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn last_status(&mut self) -> Result<PrintQueue> {
+        let status = self.last_status.ok_or("No shell command was run yet")?;
+        // killed by a signal on unix
+        let code = status
+            .code()
+            .map_or(status.to_string(), |code| code.to_string());
+        let color = if status.success() {
+            self.options.shell_color
         } else {
-            std::process::Command::new(cmd.next().unwrap_or_default())
-                .args(&cmd.collect::<Vec<&str>>())
-                .output()?
-        })
-        .trim()
-        .to_owned();
+            self.theme.output_color(OutputKind::Error)
+        };
+        print_queue!(code, color)
+    }
+
+    fn shell_bind(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :shell-bind out = ls -la
+        const USAGE: &str = "Usage: `:shell-bind <variable> = <shell command>`";
+        let (name, cmd) = buffer
+            .strip_prefix(":shell-bind")
+            .expect("already checked")
+            .split_once('=')
+            .ok_or(USAGE)?;
+        let (name, cmd) = (name.trim(), cmd.trim());
+        if name.is_empty() || cmd.is_empty() {
+            return Err(USAGE.into());
+        }
+
+        let output = crate::utils::shell_output(cmd, &self.global_variables.get_cwd())?;
+        if !output.status.success() {
+            return Err(format!(
+                "`{cmd}` failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        let statement =
+            crate::utils::shell_bind_statement(name, &String::from_utf8_lossy(&output.stdout))?;
+        self.repl.insert(statement);
+        self.repl.write_to_extern()?;
+        success!(self)
+    }
 
-        print_queue!(output, self.options.shell_color)
+    fn shell_assign(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: let files = ::ls;
+        let (name, cmd) = crate::utils::shell_assignment(&buffer).expect("already checked");
+        self.shell_bind(format!(":shell-bind {name} = {cmd}"))
     }
 
     fn parse_second_order(&mut self, buffer: String) -> Result<PrintQueue> {
         // Time irust compiling (includes rustc compiling + irust code)
-        let timer = if self.options.compile_time {
+        let timer = if self.options.compile_time && !self.options.json_output {
             Some(Instant::now())
         } else {
             None
@@ -444,96 +1857,281 @@ impl IRust {
             }
             buffer
         };
+        let mut print_queue = match crate::eval::prepare(buffer, &self.options, &self.repl)? {
+            None => PrintQueue::default(),
+            Some(Input::Comment { code, doc }) => {
+                // a doc comment that documents nothing doesn't compile
+                if !doc {
+                    self.repl.insert(code);
+                    self.repl.write_to_extern()?;
+                }
+                PrintQueue::default()
+            }
+            Some(Input::Statement(buffer)) => {
+                let mut print_queue = PrintQueue::default();
 
-        // This trimmed buffer should not be inserted nor evaluated
-        let buffer_trimmed = buffer.trim();
-
-        let mut print_queue = if buffer_trimmed.is_empty() {
-            PrintQueue::default()
-        } else if buffer_trimmed.ends_with(';')
-            || self.options.auto_insert_semicolon
-                // These patterns are used to detect statements that don't require to be terminated with ';'
-                // Note: `loop` can return a value so we don't add it here, exp: `loop {break 4}`
-                && match buffer_trimmed
-                    .split_whitespace()
-                    .collect::<Vec<_>>()
-                    .as_slice()
-                {
-                    // async fn|const fn|unsafe fn
-                    [_, "fn", ..]
-                    | ["fn", ..]
-                    | ["enum", ..]
-                    | ["struct", ..]
-                    | ["trait", ..]
-                    | ["impl", ..]
-                    | ["pub", ..]
-                    | ["extern", ..]
-                    | ["macro", ..] => true,
-                    | ["macro_rules!", ..] => true,
-                    // attribute exp:
-                    // #[derive(Debug)]
-                    // struct B{}
-                    [tag, ..] if tag.starts_with('#') => true,
-                    _ => false,
+                let mut insert_flag = true;
+
+                if self.options.check_statements {
+                    self.before_compiling_hook();
+                    let check_result = self.repl.eval_check(buffer.clone());
+                    self.after_compiling_hook();
+                    let output = check_result?.output;
+                    if check_is_err(&output) {
+                        if self.options.json_output {
+                            self.last_error = Some(output.clone());
+                            print_queue.append(
+                                &mut self.json_record(crate::json_format::error_record(&output)),
+                            );
+                        } else {
+                            print_queue.append(&mut self.format_compile_error(output));
+                        }
+                        insert_flag = false;
+                    }
                 }
-        {
-            let mut print_queue = PrintQueue::default();
 
-            let mut insert_flag = true;
+                // if cargo_check is disabled or if cargo_check is enabled but returned no error
+                if insert_flag {
+                    if self.options.json_output {
+                        print_queue
+                            .append(&mut self.json_record(crate::json_format::insert_record()));
+                    } else if self.options.echo_insert {
+                        print_queue.append(&mut echo_insert(&buffer, &self.theme));
+                    }
+                    self.repl.insert(buffer);
+                    self.repl.write_to_extern()?;
+                }
+
+                print_queue
+            }
+            Some(Input::Expression {
+                code: buffer,
+                is_async,
+            }) => {
+                let mut outputs = PrintQueue::default();
+
+                // The `()` result of `println!(..)` is noise after its output
+                let unit_evaluator = crate::utils::unit_macro_evaluator();
+                let hide_unit =
+                    !self.options.echo_unit_macros && crate::utils::is_unit_macro_call(&buffer);
+                // The types registered with `:display` are rendered with their strategy and the types implementing
+                // `irust_repl::ReplRender` with it if the repl depends on irust_repl, then with `Display` after `:display on`
+                // (only with the default evaluator)
+                let repl_render = self.repl.cargo.has_dependency("irust_repl")?;
+                let render_evaluator = ((!self.options.display_strategies.is_empty()
+                    || repl_render
+                    || self.options.display_values)
+                    && self.options.evaluator[..] == DEFAULT_EVALUATOR[..])
+                    .then(|| {
+                        render_evaluator(
+                            &self.options.display_strategies,
+                            repl_render,
+                            self.options.display_values,
+                        )
+                    });
+                let json_evaluator = crate::json_format::value_evaluator();
+                let show_types = self.options.show_types && !self.options.json_output && !hide_unit;
+                let typed = |buffer: String| {
+                    if show_types {
+                        crate::probe::typed_input(&buffer)
+                    } else {
+                        buffer
+                    }
+                };
+                // `.await` can't be used in the `catch_unwind` closure
+                let panic_catch = self.options.panic_catch
+                    && !self.options.json_output
+                    && (!is_async || matches!(self.options.executor, Executor::Sync));
+
+                // json records already have their stdout apart
+                let capture = self.options.capture_stdout && !self.options.json_output;
+                let capturing = |buffer: String| {
+                    if capture {
+                        crate::probe::capturing_input(&buffer)
+                    } else {
+                        buffer
+                    }
+                };
+
+                let user_evaluator = self.options.evaluator.clone();
+                let evaluator: &[String] = if self.options.json_output {
+                    &json_evaluator
+                } else if hide_unit {
+                    &unit_evaluator
+                } else if let Some(render_evaluator) = &render_evaluator {
+                    render_evaluator
+                } else {
+                    &user_evaluator
+                };
+                let (color, compile_mode) = (!self.options.json_output, self.options.compile_mode);
+                let eval = |repl: &mut irust_repl::Repl, input: String| {
+                    repl.eval_with_configuration(EvalConfig {
+                        input,
+                        interactive_function: Some(ctrlc_cancel),
+                        color,
+                        evaluator,
+                        compile_mode,
+                    })
+                };
 
-            if self.options.check_statements {
                 self.before_compiling_hook();
-                let check_result = self.repl.eval_check(buffer.clone());
+                let mut result = if panic_catch {
+                    eval(
+                        &mut self.repl,
+                        typed(capturing(crate::probe::panic_catching_input(&buffer))),
+                    )
+                } else {
+                    eval(&mut self.repl, typed(capturing(buffer.clone())))
+                };
+                // Some expressions can't be moved into a closure or a binding (exp: a reference to a temporary),
+                // they're evaluated without catching their panic or their stdout
+                if (panic_catch || capture)
+                    && result.as_ref().is_ok_and(|result| {
+                        !result.status.success() && result.build_output.is_none()
+                    })
+                {
+                    result = eval(&mut self.repl, typed(buffer));
+                }
                 self.after_compiling_hook();
-                if let Some(mut e) = format_check_output(
-                    check_result?.output,
+                let EvalResult {
+                    output,
+                    status,
+                    alloc_stats,
+                    build_output,
+                } = result?;
+                let caught_panic = if panic_catch && !status.success() {
+                    crate::probe::split_caught_panic(&output)
+                } else {
+                    None
+                };
+                let output =
+                    filter_backtrace(&output, self.options.bt_filter, &self.repl.cargo.name);
+                let (captured_stdout, output) = match crate::probe::split_captured_stdout(&output)
+                    .filter(|_| capture && status.success())
+                {
+                    Some((stdout, value)) => (Some(stdout), value),
+                    None => (None, output),
+                };
+                let (output, eval_type) = if show_types && status.success() {
+                    crate::probe::split_eval_type(&output)
+                } else {
+                    (output, None)
+                };
+
+                if self.options.json_output {
+                    let mut record = if status.success() {
+                        crate::json_format::value_record(&output)
+                    } else {
+                        crate::json_format::error_record(&output)
+                    };
+                    if record["kind"] == "value" {
+                        self.global_variables
+                            .set_last_output(record["value"].as_str().unwrap_or_default().into());
+                    } else {
+                        self.last_error = Some(output);
+                    }
+                    if let Some(alloc_stats) = alloc_stats {
+                        record["allocations"] = serde_json::json!({"count": alloc_stats.count, "bytes": alloc_stats.bytes});
+                    }
+                    return Ok(self.json_record(record));
+                }
+
+                // Save output if it was a success
+                if status.success() {
+                    self.global_variables.set_last_output(output.clone());
+                }
+
+                if let Some(warnings) = build_output
+                    .filter(|_| self.options.show_eval_warnings)
+                    .as_deref()
+                    .and_then(format_warnings)
+                {
+                    outputs.push(PrinterItem::String(
+                        warnings,
+                        self.theme.output_color(OutputKind::Warning),
+                    ));
+                    outputs.add_new_line(1);
+                }
+
+                if let Some(stdout) = captured_stdout.filter(|stdout| !stdout.trim().is_empty()) {
+                    let (stdout, hidden) = crate::probe::head_lines(
+                        stdout.trim_end(),
+                        crate::probe::CAPTURE_MAX_LINES,
+                    );
+                    outputs.push(PrinterItem::Str(
+                        "stdout:",
+                        self.theme.output_color(OutputKind::Info),
+                    ));
+                    outputs.add_new_line(1);
+                    outputs.push(PrinterItem::String(stdout, self.options.eval_color));
+                    outputs.add_new_line(1);
+                    if hidden > 0 {
+                        outputs.push(PrinterItem::String(
+                            format!("... ({hidden} more lines)"),
+                            self.theme.output_color(OutputKind::Info),
+                        ));
+                        outputs.add_new_line(1);
+                    }
+                }
+
+                let stack_overflow = output.contains("has overflowed its stack");
+                let output_prompt = self.get_output_prompt();
+                if let Some((stdout, message)) = caught_panic {
+                    if !stdout.trim().is_empty() {
+                        outputs.push(PrinterItem::String(
+                            stdout.trim_end().to_string(),
+                            self.options.eval_color,
+                        ));
+                        outputs.add_new_line(1);
+                    }
+                    outputs.push(PrinterItem::String(
+                        format!("panicked at: {message}"),
+                        self.theme.output_color(OutputKind::Error),
+                    ));
+                    outputs.add_new_line(1);
+                } else if !status.success() {
+                    outputs.append(&mut self.format_compile_error(output));
+                } else if let Some(mut eval_output) = format_eval_output(
+                    status,
+                    match eval_type {
+                        Some(ty) => crate::probe::with_eval_type(&output, &ty),
+                        None => output.clone(),
+                    },
+                    output_prompt,
                     self.options.show_warnings,
                     &self.repl.cargo.name,
+                    self.options.new_lines_after_output,
                 ) {
-                    print_queue.append(&mut e);
-                    insert_flag = false;
+                    outputs.append(&mut eval_output);
+                    self.eval_outputs.push(output);
                 }
-            }
-
-            // if cargo_check is disabled or if cargo_check is enabled but returned no error
-            if insert_flag {
-                self.repl.insert(buffer);
-                self.repl.write_to_extern()?;
-            }
 
-            print_queue
-        } else {
-            let mut outputs = PrintQueue::default();
-
-            self.before_compiling_hook();
-            let result = self.repl.eval_with_configuration(EvalConfig {
-                input: buffer,
-                interactive_function: Some(ctrlc_cancel),
-                color: true,
-                evaluator: &self.options.evaluator,
-                compile_mode: self.options.compile_mode,
-            });
-            self.after_compiling_hook();
-            let EvalResult { output, status } = result?;
+                if stack_overflow {
+                    outputs.push(PrinterItem::String(
+                    format!(
+                        "[-] the stack overflowed (stack size: {}), it can be increased with `:config stack-size <bytes>`",
+                        self.options
+                            .stack_size
+                            .map_or_else(|| "default".into(), |size| format!("{size} bytes"))
+                    ),
+                    self.theme.output_color(OutputKind::Warning),
+                ));
+                    outputs.add_new_line(1);
+                }
 
-            // Save output if it was a success
-            if status.success() {
-                self.global_variables.set_last_output(output.clone());
-            }
+                if let Some(alloc_stats) = alloc_stats {
+                    outputs.push(PrinterItem::String(
+                        format!(
+                            "[-] allocations: {} ({} bytes)",
+                            alloc_stats.count, alloc_stats.bytes
+                        ),
+                        self.theme.output_color(OutputKind::Info),
+                    ));
+                    outputs.add_new_line(1);
+                }
 
-            let output_prompt = self.get_output_prompt();
-            if let Some(mut eval_output) = format_eval_output(
-                status,
-                output,
-                output_prompt,
-                self.options.show_warnings,
-                &self.repl.cargo.name,
-                self.options.new_lines_after_output,
-            ) {
-                outputs.append(&mut eval_output);
+                outputs
             }
-
-            outputs
         };
 
         // Print compile time
@@ -543,7 +2141,7 @@ impl IRust {
                     "[-] compiling took: {} millisseconds",
                     timer.elapsed().as_millis()
                 ),
-                Color::Magenta,
+                self.theme.output_color(OutputKind::Info),
             );
             print_queue.add_new_line(1);
             print_queue.push(time);
@@ -552,273 +2150,1184 @@ impl IRust {
         Ok(print_queue)
     }
 
-    pub fn sync(&mut self) -> Result<PrintQueue> {
-        match self.repl.update_from_extern_main_file() {
-            Ok(_) => success!(),
-            Err(e) => {
-                self.repl.reset()?;
-                Err(e)
-            }
-        }
+    /// One JSON record per line, printed without colors so it can be parsed
+    pub(super) fn json_record(&self, record: serde_json::Value) -> PrintQueue {
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::String(record.to_string(), Color::White));
+        print_queue.add_new_line(1);
+        print_queue
     }
 
-    fn extern_edit(&mut self, buffer: String) -> Result<PrintQueue> {
-        // exp: :edit vi
-        let editor: String = match buffer.split_whitespace().nth(1) {
-            Some(ed) => ed.to_string(),
+    /// `:<command> [on|off]` sets the toggle, without an argument it shows its state
+    fn toggle(
+        &mut self,
+        buffer: &str,
+        command: &str,
+        toggle: impl FnOnce(&mut Options) -> &mut bool,
+    ) -> Result<PrintQueue> {
+        let arg = buffer.strip_prefix(command).expect("already checked");
+        let state = crate::utils::parse_toggle(arg)
+            .map_err(|e| format!("{e}, usage: `{command} [on|off]`"))?;
+        let toggle = toggle(&mut self.options);
+        match state {
+            Some(on) => *toggle = on,
             None => {
-                if let Ok(ed) = env::var("EDITOR") {
-                    ed
-                } else {
-                    return Err("No editor specified".into());
-                }
+                let on = *toggle;
+                return print_queue!(
+                    if on { "on" } else { "off" }.into(),
+                    self.theme.output_color(OutputKind::Value)
+                );
             }
-        };
-
-        self.printer.writer.raw.write_with_color(
-            format!("waiting for {editor}..."),
-            crossterm::style::Color::Magenta,
-        )?;
+        }
+        success!(self)
+    }
 
-        // Write repl to disk
-        self.repl.write_to_extern()?;
+    fn prompt(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :prompt "[{n}] {cwd}> ", the quotes keep the trailing spaces
+        let template = buffer
+            .strip_prefix(":prompt")
+            .expect("already checked")
+            .trim();
+        let template = template
+            .strip_prefix('"')
+            .and_then(|template| template.strip_suffix('"'))
+            .unwrap_or(template);
+        self.prompt_template = (!template.is_empty()).then(|| template.to_string());
+        let prompt = self
+            .templated_prompt()
+            .unwrap_or_else(|| self.options.input_prompt.clone());
+        self.global_variables.prompt_len = prompt.chars().count();
+        self.printer.set_prompt(prompt);
+        success!(self)
+    }
 
-        // beautify code
-        self.repl
-            .cargo
-            .cargo_fmt_file(&self.repl.cargo.paths.main_file_extern);
+    /// The input prompt rendered from the `:prompt` template if it's set
+    pub fn templated_prompt(&self) -> Option<String> {
+        let template = self.prompt_template.as_ref()?;
+        Some(crate::irust::format::render_prompt(
+            template,
+            &self.global_variables.get_cwd(),
+            dirs::home_dir().as_deref(),
+            self.global_variables.operation_number,
+        ))
+    }
 
-        // some commands are not detected from path but still works  with cmd /C
-        #[cfg(windows)]
-        std::process::Command::new("cmd")
-            .arg("/C")
-            .arg(editor)
-            .arg(&self.repl.cargo.paths.main_file_extern)
-            .spawn()?
-            .wait()?;
+    /// Format a compiler error according to `error-verbosity`, the raw output is kept for `:errors --full`
+    fn format_compile_error(&mut self, output: String) -> PrintQueue {
+        let formatted_error =
+            format_err(&output, self.options.show_warnings, &self.repl.cargo.name);
+        self.last_error = Some(output);
+        let formatted_error = match self.options.error_verbosity {
+            ErrorVerbosity::Summary => summarize_err(&formatted_error),
+            ErrorVerbosity::Full => formatted_error,
+        };
+        PrinterItem::String(formatted_error, self.theme.output_color(OutputKind::Error)).into()
+    }
 
-        #[cfg(not(windows))]
-        std::process::Command::new(editor)
-            .arg(&self.repl.cargo.paths.main_file_extern)
-            .spawn()?
-            .wait()?;
+    fn session_history(&mut self, buffer: String) -> Result<PrintQueue> {
+        let limit = buffer
+            .strip_prefix(":history")
+            .expect("already checked")
+            .trim();
+        let limit = match limit {
+            "" => self.session_inputs.len(),
+            limit => limit
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid count: `{limit}`, usage: `:history [count]`"))?,
+        };
+        if self.session_inputs.is_empty() {
+            return print_queue!(
+                "No inputs yet".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
 
-        self.sync()
+        let skipped = self.session_inputs.len().saturating_sub(limit);
+        let mut print_queue = PrintQueue::default();
+        for (idx, input) in self.session_inputs.iter().enumerate().skip(skipped) {
+            print_queue.push(PrinterItem::String(
+                format!("{:>3}: ", idx + 1),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            print_queue.append(&mut highlight(&input.as_str().into(), &self.theme));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
     }
 
-    fn irust(&mut self) -> Result<PrintQueue> {
-        print_queue!(self.ferris(), Color::Red)
-    }
+    fn hist_search(&mut self, buffer: String) -> Result<PrintQueue> {
+        const MAX_RESULTS: usize = 10;
+        let query = buffer
+            .strip_prefix(":hist-search")
+            .expect("already checked")
+            .trim();
+        if query.is_empty() {
+            return Err("Usage: `:hist-search <query>`".into());
+        }
+        let matches: Vec<_> = self
+            .history
+            .fuzzy_search(query)
+            .into_iter()
+            // don't suggest previous searches
+            .filter(|(entry, _)| !entry.starts_with(":hist-search"))
+            .take(MAX_RESULTS)
+            .collect();
+        if matches.is_empty() {
+            return print_queue!(
+                "No matches".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
 
-    fn cd(&mut self, buffer: String) -> Result<PrintQueue> {
-        use std::env::*;
-        let buffer = buffer
-            .split(":cd")
-            .skip(1)
-            .collect::<String>()
-            .trim()
-            .to_string();
-        match buffer.as_str() {
-            "" => {
-                if let Some(dir) = dirs::home_dir() {
-                    set_current_dir(dir)?;
-                }
-            }
-            "-" => {
-                set_current_dir(self.global_variables.get_pwd())?;
-            }
-            path => {
-                let mut dir = current_dir()?;
-                dir.push(path);
-                set_current_dir(dir)?;
+        let mut print_queue = PrintQueue::default();
+        for (idx, (entry, indices)) in matches.iter().enumerate() {
+            print_queue.push(PrinterItem::String(
+                format!("{:>3}: ", idx + 1),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            for (char_idx, c) in entry.chars().enumerate() {
+                // Multi-line entries are shown in one line
+                let c = if c == '\n' { ' ' } else { c };
+                let color = if indices.contains(&char_idx) {
+                    self.theme.output_color(OutputKind::Value)
+                } else {
+                    Color::White
+                };
+                print_queue.push(PrinterItem::Char(c, color));
             }
+            print_queue.add_new_line(1);
         }
-        // Update cwd and the terminal title accordingly
-        let cwd = current_dir()?;
-        self.global_variables.update_cwd(cwd.clone());
-        self.printer
-            .writer
-            .raw
-            .set_title(&format!("IRust: {}", cwd.display()))?;
+        self.printer.print_output(print_queue)?;
 
-        print_queue!(cwd.display().to_string(), self.options.ok_color)
+        let pick = self.read_line(&format!("Pick an entry to run [1-{}]: ", matches.len()))?;
+        let Some(entry) = pick
+            .and_then(|pick| pick.trim().parse::<usize>().ok())
+            .and_then(|pick| matches.get(pick.checked_sub(1)?))
+        else {
+            return print_queue!(
+                "Aborted".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        };
+        self.parse(entry.0.clone())
     }
 
-    fn time(&mut self, buffer: String) -> Result<PrintQueue> {
-        self.inner_time(buffer, ":time", false)
+    fn out(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :out 3
+        let index = buffer.strip_prefix(":out").expect("already checked").trim();
+        if self.eval_outputs.is_empty() {
+            return Err("No results yet, evaluate an expression first".into());
+        }
+        let index = if index.is_empty() {
+            self.eval_outputs.len()
+        } else {
+            index
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid index: `{index}`, usage: `:out [n]`"))?
+        };
+        let output = index
+            .checked_sub(1)
+            .and_then(|index| self.eval_outputs.get(index))
+            .ok_or_else(|| {
+                format!(
+                    "No result {index}, the results are numbered from 1 to {}",
+                    self.eval_outputs.len()
+                )
+            })?;
+        print_queue!(output.clone(), self.theme.output_color(OutputKind::Value))
     }
-    fn time_release(&mut self, buffer: String) -> Result<PrintQueue> {
-        self.inner_time(buffer, ":time_release", true)
+
+    fn copy(&mut self, buffer: String) -> Result<PrintQueue> {
+        let expression = buffer
+            .strip_prefix(":copy")
+            .expect("already checked")
+            .trim();
+        let text = if expression.is_empty() {
+            self.global_variables
+                .get_last_output()
+                .ok_or("Nothing to copy, evaluate an expression first")?
+                .clone()
+        } else {
+            self.before_compiling_hook();
+            let result = self.repl.eval_with_configuration(EvalConfig {
+                input: expression,
+                interactive_function: Some(ctrlc_cancel),
+                color: true,
+                evaluator: &self.options.evaluator,
+                compile_mode: self.options.compile_mode,
+            });
+            self.after_compiling_hook();
+            let EvalResult { output, status, .. } = result?;
+            if !status.success() {
+                return Ok(self.format_compile_error(output));
+            }
+            self.global_variables.set_last_output(output.clone());
+            output
+        };
+        crate::utils::write_clipboard(&text)?;
+        print_queue!(
+            format!("Copied: {}", crate::utils::preview(&text, 60)),
+            self.theme.output_color(OutputKind::Success)
+        )
     }
 
-    fn inner_time(
-        &mut self,
-        buffer: String,
-        pattern: &'static str,
-        release: bool,
-    ) -> Result<PrintQueue> {
-        let fnn = buffer
-            .split_once(pattern)
-            .map(|buf| buf.1)
-            .ok_or("No function specified")?;
+    fn paste(&mut self) -> Result<PrintQueue> {
+        self.paste_lines = Some(vec![]);
+        print_queue!(
+            "Entering paste mode, end with :end (or ctrl-d)".into(),
+            self.theme.output_color(OutputKind::Info)
+        )
+    }
 
-        if fnn.is_empty() {
-            return Err("No function specified".into());
+    fn paste_clipboard(&mut self) -> Result<PrintQueue> {
+        let clipboard = crate::utils::read_clipboard()?;
+        let (statements, expression) = crate::utils::split_snippet(&clipboard);
+        if statements.is_none() && expression.is_none() {
+            return Err("The clipboard is empty".into());
         }
 
-        let time = format!(
-            "\
-        use std::time::Instant;
-        let now = Instant::now();
-        {fnn};
-        println!(\"{{:?}}\", now.elapsed());
-        ",
-        );
+        let mut print_queue = PrintQueue::default();
+        if let Some(statements) = statements {
+            if self.options.echo_insert {
+                print_queue.append(&mut echo_insert(statements, &self.theme));
+            }
+            // Don't insert code that doesn't compile
+            let EvalResult { output, status, .. } = self.repl.eval_check(statements.to_string())?;
+            if !status.success() {
+                return Ok(self.format_compile_error(output));
+            }
+            self.repl.insert(statements);
+            self.repl.write_to_extern()?;
+        }
+        if let Some(expression) = expression {
+            // `:retry` should paste again, not only rerun the expression
+            let last_input = self.last_input.take();
+            let output = self.parse(expression.to_string());
+            self.last_input = last_input;
+            print_queue.append(&mut output?);
+            return Ok(print_queue);
+        }
+        print_queue.push(PrinterItem::Str(
+            SUCCESS,
+            self.theme.output_color(OutputKind::Success),
+        ));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
 
-        let toolchain = self.options.toolchain;
-        let mut raw_out = String::new();
-        let mut status = None;
+    fn clear(&mut self) -> Result<PrintQueue> {
+        // Like Ctrl-L, the prompt is printed again at the top after the (empty) output
+        self.printer.clear()?;
+        Ok(PrintQueue::default())
+    }
 
-        let cargo = self.repl.cargo.clone();
-        self.repl.eval_in_tmp_repl(time, |_| -> Result<()> {
-            let (s, out) = cargo.cargo_run(true, release, toolchain, Some(ctrlc_cancel))?;
-            raw_out = out;
-            status = Some(s);
-            Ok(())
-        })?;
+    fn refresh(&mut self) -> Result<PrintQueue> {
+        let options = Options::new()?;
+        let restart_required = self.options.restart_required(&options);
+        let old = std::mem::replace(&mut self.options, options);
 
-        let output_prompt = self.get_output_prompt();
-        // safe unwrap
-        Ok(format_eval_output(
-            status.unwrap(),
-            raw_out,
-            output_prompt,
-            self.options.show_warnings,
-            &self.repl.cargo.name,
-            self.options.new_lines_after_output,
-        )
-        .ok_or("failed to bench function")?)
-    }
+        // Most settings are read from the options when used, these ones need to be applied
+        if self.options.theme != old.theme {
+            self.theme =
+                super::highlight::theme::theme_or_create_default(self.options.theme.clone());
+        }
+        // scripts and `:prompt` can override the prompt
+        if self.script_mg.is_none()
+            && self.prompt_template.is_none()
+            && self.options.input_prompt != old.input_prompt
+        {
+            self.global_variables.prompt_len = self.options.input_prompt.chars().count();
+            self.printer.set_prompt(self.options.input_prompt.clone());
+        }
+        self.repl.set_toolchain(self.options.toolchain);
+        if self.options.executor.to_string() != old.executor.to_string() {
+            self.repl.set_executor(self.options.executor)?;
+        }
+        if self.options.main_result.to_string() != old.main_result.to_string() {
+            self.repl.set_main_result(self.options.main_result);
+        }
+        self.repl.set_track_alloc(self.options.track_alloc);
+        self.printer.pager = self.options.pager.clone();
 
-    fn bench(&mut self) -> Result<PrintQueue> {
-        //make sure we have the latest changes in main.rs
-        self.repl.write()?;
-        let out = self
-            .repl
-            .cargo
-            .cargo_bench(self.options.toolchain)?
-            .trim()
-            .to_owned();
+        if restart_required.is_empty() {
+            success!(self)
+        } else {
+            print_queue!(
+                format!(
+                    "IRust needs to be restarted to apply: {}",
+                    restart_required.join(", ")
+                ),
+                self.options.irust_warn_color
+            )
+        }
+    }
 
-        print_queue!(out, self.options.eval_color)
+    fn multiline(&mut self) -> Result<PrintQueue> {
+        self.multiline = true;
+        print_queue!(
+            "Multi-line input: Enter inserts a new line, Alt+Enter submits".into(),
+            self.theme.output_color(OutputKind::Info)
+        )
     }
 
-    fn asm(&mut self, buffer: String) -> Result<PrintQueue> {
-        let fnn = buffer.strip_prefix(":asm").expect("already checked").trim();
-        if fnn.is_empty() {
-            return Err("No function specified".into());
+    fn bind(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :bind --force ll ::ls -la
+        const USAGE: &str = "Usage: `:bind [--force] <name> [<command>]`";
+        let mut args = buffer
+            .strip_prefix(":bind")
+            .or_else(|| buffer.strip_prefix(":alias"))
+            .expect("already checked")
+            .split_whitespace()
+            .peekable();
+        let force = args.next_if_eq(&"--force").is_some();
+        let Some(name) = args.next() else {
+            if self.options.aliases.is_empty() {
+                return print_queue!(
+                    "No aliases".into(),
+                    self.theme.output_color(OutputKind::Warning)
+                );
+            }
+            let aliases = self
+                .options
+                .aliases
+                .iter()
+                .map(|(name, command)| format!(":{name} => {command}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return print_queue!(aliases, self.theme.output_color(OutputKind::Value));
+        };
+        let name = name.trim_start_matches(':');
+        if name.is_empty() {
+            return Err(USAGE.into());
         }
+        let command = args.collect::<Vec<_>>().join(" ");
 
-        let asm = self
-            .repl
-            .with_lib(|| self.repl.cargo.cargo_asm(fnn, self.options.toolchain))??;
-
-        print_queue!(asm, self.options.eval_color)
+        // `:bind <name>` removes the alias
+        if command.is_empty() {
+            return match self.options.aliases.remove(name) {
+                Some(_) => success!(self),
+                None => Err(format!("No alias named `{name}`").into()),
+            };
+        }
+        if !force && is_builtin_command(&format!(":{name}")) {
+            return Err(format!(
+                "`:{name}` would shadow a builtin command, use `:bind --force {name} {command}` to bind it anyway"
+            )
+            .into());
+        }
+        self.options.aliases.insert(name.to_string(), command);
+        success!(self)
     }
 
-    fn executor(&mut self, buffer: String) -> Result<PrintQueue> {
-        let executor = buffer.split_whitespace().nth(1);
-        if let Some(executor) = executor {
-            let executor = Executor::from_str(executor.trim())?;
-            self.repl.set_executor(executor)?;
-            // save executor
-            self.options.executor = executor;
-            success!()
-        } else {
-            print_queue!(self.options.executor.to_string(), Color::Blue)
+    fn alias_type(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :alias-type MyLong std::collections::HashMap<String, Vec<i32>>
+        let args = buffer
+            .strip_prefix(":alias-type")
+            .expect("already checked")
+            .trim();
+        if args.is_empty() {
+            if self.options.type_display_aliases.is_empty() {
+                return print_queue!(
+                    "No type aliases".into(),
+                    self.theme.output_color(OutputKind::Warning)
+                );
+            }
+            let aliases = self
+                .options
+                .type_display_aliases
+                .iter()
+                .map(|(alias, ty)| format!("{alias} => {ty}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return print_queue!(aliases, self.theme.output_color(OutputKind::Value));
         }
+
+        let (alias, ty) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let ty = ty.trim();
+        // `:alias-type <alias>` removes the alias
+        if ty.is_empty() {
+            return match self.options.type_display_aliases.remove(alias) {
+                Some(_) => success!(self),
+                None => Err(format!("No type alias named `{alias}`").into()),
+            };
+        }
+        self.options
+            .type_display_aliases
+            .insert(alias.to_string(), ty.to_string());
+        success!(self)
     }
 
-    fn evaluator(&mut self, buffer: String) -> Result<PrintQueue> {
-        let evaluator = buffer
-            .strip_prefix(":evaluator")
+    fn display(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :display Meters as display
+        const USAGE: &str = "Usage: `:display on|off` or `:display <type> as <debug|display|json>`";
+        let args = buffer
+            .strip_prefix(":display")
             .expect("already checked")
             .trim();
-        // get
-        if evaluator.is_empty() {
-            return print_queue!(self.options.evaluator.join("$$"), Color::Blue);
+        match args {
+            "on" => {
+                self.options.display_values = true;
+                return success!(self);
+            }
+            "off" => {
+                self.options.display_values = false;
+                return success!(self);
+            }
+            _ => (),
+        }
+        if args.is_empty() {
+            let state = if self.options.display_values {
+                "on (the other types use display when they implement it)"
+            } else {
+                "off (the other types use debug)"
+            };
+            let strategies = std::iter::once(format!("display: {state}"))
+                .chain(
+                    self.options
+                        .display_strategies
+                        .iter()
+                        .map(|(ty, strategy)| format!("{ty} => {strategy}")),
+                )
+                .collect::<Vec<_>>()
+                .join("\n");
+            return print_queue!(strategies, self.theme.output_color(OutputKind::Value));
         }
 
-        // reset
-        if evaluator == "reset" {
-            self.options.reset_evaluator();
-            return success!();
+        let (ty, strategy) = args.rsplit_once(" as ").ok_or(USAGE)?;
+        let ty = ty.split_whitespace().collect::<Vec<_>>().join(" ");
+        if ty.is_empty() {
+            return Err(USAGE.into());
         }
+        // debug is the default, but it's kept to opt out of `:display on` (even if it's turned on later)
+        let strategy = DisplayStrategy::from_str(strategy.trim())?;
+        self.options.display_strategies.insert(ty, strategy);
+        success!(self)
+    }
 
-        // Sanity checks
-        // set
-        if !evaluator.contains("$$") {
-            return Err(
-                "evaluator must contain `$$`, `$$` will be replaced by IRust by the code input"
-                    .into(),
-            );
+    fn grep(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :grep -i --history fn foo
+        const USAGE: &str = "Usage: `:grep [-i] [--history] <pattern>`";
+        let mut args = crate::utils::split_args(buffer);
+        args.remove(0); // drop :grep
+        let (mut ignore_case, mut history) = (false, false);
+        let flags = args
+            .iter()
+            .take_while(|arg| match arg.as_str() {
+                "-i" => {
+                    ignore_case = true;
+                    true
+                }
+                "--history" => {
+                    history = true;
+                    true
+                }
+                _ => false,
+            })
+            .count();
+        let pattern = args[flags..].join(" ");
+        if pattern.is_empty() {
+            return Err(USAGE.into());
         }
-        if !evaluator.ends_with(';') {
-            return Err("evaluator must end with ;".into());
+        let pattern = pattern.as_str();
+
+        let (info_color, match_color, line_color) = (
+            self.theme.output_color(OutputKind::Info),
+            self.theme.output_color(OutputKind::Warning),
+            self.theme.output_color(OutputKind::Value),
+        );
+        let mut print_queue = PrintQueue::default();
+        let mut print_match = |prefix: String, line: &str, indices: Vec<usize>| {
+            print_queue.push(PrinterItem::String(prefix, info_color));
+            for (char_idx, c) in line.chars().enumerate() {
+                // Multi-line history entries are shown in one line
+                let c = if c == '\n' { ' ' } else { c };
+                let color = if indices.contains(&char_idx) {
+                    match_color
+                } else {
+                    line_color
+                };
+                print_queue.push(PrinterItem::Char(c, color));
+            }
+            print_queue.add_new_line(1);
+        };
+
+        let mut found = false;
+        // The line numbers are the same ones used by `:del`
+        for (line_num, line) in self.repl.lines().enumerate() {
+            let indices = crate::utils::find_matches(line, pattern, ignore_case);
+            if !indices.is_empty() {
+                found = true;
+                print_match(format!("{line_num:>3}: "), line, indices);
+            }
+        }
+        if history {
+            for entry in self.history.entries() {
+                let indices = crate::utils::find_matches(entry, pattern, ignore_case);
+                // don't show previous searches
+                if !indices.is_empty() && !entry.starts_with(":grep") {
+                    found = true;
+                    print_match("hist: ".into(), entry, indices);
+                }
+            }
         }
 
-        let evaluator: Vec<String> = evaluator.split("$$").map(ToOwned::to_owned).collect();
-        if evaluator.len() != 2 {
-            return Err("evaluator requires two parts".into());
+        if !found {
+            return print_queue!(
+                "No matches".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
         }
+        Ok(print_queue)
+    }
 
-        self.options.evaluator = evaluator;
-        success!()
+    fn errors(&mut self, buffer: String) -> Result<PrintQueue> {
+        let arg = buffer
+            .strip_prefix(":errors")
+            .expect("already checked")
+            .trim();
+        match arg {
+            "" | "--full" => (),
+            // shortcut for `:config error-verbosity summary|full`
+            "short" => {
+                self.options.error_verbosity = ErrorVerbosity::Summary;
+                return success!(self);
+            }
+            "full" => {
+                self.options.error_verbosity = ErrorVerbosity::Full;
+                return success!(self);
+            }
+            _ => return Err("Usage: `:errors [--full|short|full]`".into()),
+        }
+        let last_error = self.last_error.as_ref().ok_or("No error yet")?;
+        Ok(format_err_printqueue(
+            last_error,
+            self.options.show_warnings,
+            &self.repl.cargo.name,
+        ))
     }
 
-    fn scripts(&mut self, buffer: String) -> Result<PrintQueue> {
-        let scripts_list = if let Some(scripts_list) = self.scripts_list() {
-            scripts_list
-        } else {
-            return Err("No scripts found".into());
-        };
+    pub fn sync(&mut self) -> Result<PrintQueue> {
+        match self.repl.update_from_extern_main_file() {
+            Ok(_) => success!(self),
+            Err(e) => {
+                self.repl.reset()?;
+                Err(e)
+            }
+        }
+    }
 
-        let buffer: Vec<&str> = buffer
-            .strip_prefix(":scripts")
-            .expect("already checked")
-            .split_whitespace()
-            .collect();
+    fn extern_edit(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :edit code --wait
+        let mut args = crate::utils::split_args(buffer);
+        args.remove(0); // drop :edit
 
-        //TODO: This code can be improved *a lot* by doing formatting here, instead of letting each script manager do its own thing
+        // Write repl to disk
+        self.repl.write_to_extern()?;
 
-        match buffer.len() {
-            0 => {
-                //NOTE: Hack formatting because its less code
-                //Assumes that script state is the last column
+        // beautify code
+        self.repl
+            .cargo
+            .cargo_fmt_file(&self.repl.cargo.paths.main_file_extern);
 
-                if scripts_list.is_empty() {
-                    return print_queue!("Script list is empty".to_string(), Color::Blue);
-                }
+        let main_file_extern = self.repl.cargo.paths.main_file_extern.clone();
+        self.open_in_editor(args, &main_file_extern)?;
 
-                let mut queue = PrintQueue::default();
-                let mut scripts_list = scripts_list.lines();
+        if self.options.fmt_on_edit {
+            // if rustfmt fails (exp: invalid syntax) the edited file is kept as is
+            self.repl
+                .cargo
+                .cargo_fmt_file(&self.repl.cargo.paths.main_file_extern);
+        }
 
-                let header = scripts_list.next().expect("exists");
-                queue.push(PrinterItem::String(header.to_string(), Color::Blue));
-                queue.add_new_line(2);
+        self.sync()
+    }
 
-                let mut list: Vec<_> = scripts_list.collect();
-                list.sort_by_key(|line| line.ends_with("false"));
-                list.iter().for_each(|line| {
-                    if line.ends_with("true") {
-                        queue.push(PrinterItem::String(line.to_string(), Color::Green))
-                    } else {
-                        queue.push(PrinterItem::String(line.to_string(), Color::Red))
-                    }
-                    queue.add_new_line(1);
-                });
+    fn fmt(&mut self) -> Result<PrintQueue> {
+        self.repl.write_to_extern()?;
+        self.repl
+            .cargo
+            .try_cargo_fmt_file(&self.repl.cargo.paths.main_file_extern)?;
+        self.sync()
+    }
 
-                Ok(queue)
+    fn edit_last(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :edit-last code --wait
+        let mut args = crate::utils::split_args(buffer);
+        args.remove(0); // drop :edit-last
+
+        let block_len = crate::utils::last_block_len(self.repl.inserted_lines());
+        if block_len == 0 {
+            return Err("Nothing to edit".into());
+        }
+        let inserted_lines = self.repl.inserted_lines();
+        let block = inserted_lines[inserted_lines.len() - block_len..].join("\n");
+
+        let block_file = self.repl.cargo.paths.irust_dir.join("last_block.rs");
+        std::fs::write(&block_file, block + "\n")?;
+        let edited = self
+            .open_in_editor(args, &block_file)
+            .and_then(|()| Ok(std::fs::read_to_string(&block_file)?));
+        let _ = std::fs::remove_file(&block_file);
+        let edited = edited?;
+
+        let (body, cursor) = (self.repl.body(), self.repl.cursor());
+        self.repl.remove_last_lines(block_len);
+        self.repl.insert(edited.trim_end());
+
+        self.before_compiling_hook();
+        let result = self.repl.eval_build(String::new());
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.repl.hard_load(body, cursor);
+                return Err(e);
+            }
+        };
+        // The edited block broke the code, go back to the previous code
+        if !status.success() {
+            self.repl.hard_load(body, cursor);
+            let mut print_queue = self.format_compile_error(output);
+            print_queue.push(PrinterItem::String(
+                "The edit was reverted".into(),
+                self.theme.output_color(OutputKind::Warning),
+            ));
+            print_queue.add_new_line(1);
+            return Ok(print_queue);
+        }
+        self.repl.write_to_extern()?;
+        success!(self)
+    }
+
+    fn toml(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :toml code --wait
+        let mut args = crate::utils::split_args(buffer);
+        args.remove(0); // drop :toml
+
+        self.repl.snapshot_manifest(":toml")?;
+        let cargo_toml_file = self.repl.cargo.paths.cargo_toml_file.clone();
+        self.open_in_editor(args, &cargo_toml_file)?;
+        // The edited toml is kept so it can be fixed with another `:toml`
+        self.reload_deps()
+    }
+
+    /// Build the repl code with the current Cargo.toml, exp: after editing it outside of IRust
+    /// Nothing changes if it fails, the error is shown
+    fn reload_deps(&mut self) -> Result<PrintQueue> {
+        self.before_compiling_hook();
+        let result = self.repl.eval_build(String::new());
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+
+        if let Some(ra) = self.completer.as_mut() {
+            ra.rust_analyzer.reload_workspace()?;
+        }
+
+        success!(self)
+    }
+
+    /// Open `file` in the editor from the args (or $VISUAL/$EDITOR) and wait for it to exit
+    fn open_in_editor(&mut self, args: Vec<String>, file: &std::path::Path) -> Result<()> {
+        let (editor, editor_args) = crate::utils::editor_command(args, |var| env::var(var).ok())
+            .ok_or("No editor specified (and $VISUAL and $EDITOR are not set)")?;
+
+        self.printer.writer.raw.write_with_color(
+            format!("waiting for {editor}..."),
+            self.theme.output_color(OutputKind::Info),
+        )?;
+
+        // some commands are not detected from path but still works  with cmd /C
+        #[cfg(windows)]
+        std::process::Command::new("cmd")
+            .arg("/C")
+            .arg(editor)
+            .args(editor_args)
+            .arg(file)
+            .spawn()?
+            .wait()?;
+
+        #[cfg(not(windows))]
+        std::process::Command::new(editor)
+            .args(editor_args)
+            .arg(file)
+            .spawn()?
+            .wait()?;
+
+        Ok(())
+    }
+
+    fn irust(&mut self) -> Result<PrintQueue> {
+        print_queue!(self.ferris(), Color::Red)
+    }
+
+    fn pwd(&mut self) -> Result<PrintQueue> {
+        print_queue!(
+            std::env::current_dir()?.display().to_string(),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn cd(&mut self, buffer: String) -> Result<PrintQueue> {
+        use std::env::*;
+        let buffer = buffer
+            .split(":cd")
+            .skip(1)
+            .collect::<String>()
+            .trim()
+            .to_string();
+        let dir = match buffer.as_str() {
+            "" | "~" => match dirs::home_dir() {
+                Some(dir) => dir,
+                None => current_dir()?,
+            },
+            "-" => return self.popd(),
+            path if path.starts_with('~') => dirs::home_dir()
+                .and_then(|home| crate::utils::expand_home(path, &home))
+                .ok_or_else(|| format!("Can't expand `{path}`"))?,
+            path => {
+                let dir = current_dir()?.join(path);
+                // as a fallback, search the `CDPATH` base directories
+                match var_os("CDPATH") {
+                    Some(cdpath) if !dir.exists() => {
+                        crate::utils::cdpath_dir(path, &cdpath).unwrap_or(dir)
+                    }
+                    _ => dir,
+                }
+            }
+        };
+        let previous_dir = current_dir()?;
+        let output = self.change_dir(dir)?;
+        self.dir_stack.push(previous_dir);
+        Ok(output)
+    }
+
+    fn popd(&mut self) -> Result<PrintQueue> {
+        let dir = self
+            .dir_stack
+            .last()
+            .cloned()
+            .ok_or("The directory stack is empty")?;
+        // it's only popped if it still exists
+        let output = self.change_dir(dir)?;
+        self.dir_stack.pop();
+        Ok(output)
+    }
+
+    fn dirs(&mut self) -> Result<PrintQueue> {
+        // the current directory first, then the stack from the top like the `dirs` shell builtin
+        let dirs = std::iter::once(std::env::current_dir()?)
+            .chain(self.dir_stack.iter().rev().cloned())
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        print_queue!(dirs, self.theme.output_color(OutputKind::Value))
+    }
+
+    fn mem(&mut self, buffer: String) -> Result<PrintQueue> {
+        match buffer.strip_prefix(":mem").expect("already checked").trim() {
+            "" => (),
+            "clean" => {
+                self.repl.cargo.cargo_clean(self.options.toolchain)?;
+                return success!(self);
+            }
+            arg => return Err(format!("Invalid argument: `{arg}`, usage: `:mem [clean]`").into()),
+        }
+        let paths = &self.repl.cargo.paths;
+        let (project_size, _) = crate::utils::dir_size(&paths.irust_dir)?;
+        let (target_size, crates) = crate::utils::dir_size(&paths.irust_target_dir)?;
+
+        let mut outputs = PrintQueue::default();
+        let mut line = |name: &str, value: String| {
+            if !outputs.is_empty() {
+                outputs.add_new_line(1);
+            }
+            outputs.push(PrinterItem::String(
+                format!("{name}: "),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            outputs.push(PrinterItem::String(
+                value,
+                self.theme.output_color(OutputKind::Value),
+            ));
+        };
+        line("project", crate::utils::human_size(project_size));
+        line(
+            "target",
+            format!(
+                "{} ({})",
+                crate::utils::human_size(target_size),
+                paths.irust_target_dir.display()
+            ),
+        );
+        line("compiled crates", crates.to_string());
+        Ok(outputs)
+    }
+
+    fn change_dir(&mut self, dir: std::path::PathBuf) -> Result<PrintQueue> {
+        std::env::set_current_dir(dir)?;
+        // Update cwd and the terminal title accordingly
+        let cwd = std::env::current_dir()?;
+        self.global_variables.update_cwd(cwd.clone());
+        self.printer
+            .writer
+            .raw
+            .set_title(&format!("IRust: {}", cwd.display()))?;
+
+        print_queue!(
+            cwd.display().to_string(),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn await_expression(&mut self, buffer: String) -> Result<PrintQueue> {
+        let expression = buffer
+            .strip_prefix(":await")
+            .expect("already checked")
+            .trim();
+        if expression.is_empty() {
+            return Err("Usage: `:await <future>`".into());
+        }
+        self.parse_second_order(format!("({expression}).await"))
+    }
+
+    fn time(&mut self, buffer: String) -> Result<PrintQueue> {
+        self.inner_time(buffer, ":time", false)
+    }
+    fn time_release(&mut self, buffer: String) -> Result<PrintQueue> {
+        self.inner_time(buffer, ":time_release", true)
+    }
+
+    fn time_build(&mut self, buffer: String) -> Result<PrintQueue> {
+        let input = buffer
+            .strip_prefix(":time-build")
+            .expect("already checked")
+            .trim();
+        if input.is_empty() {
+            return Err("Usage: `:time-build <item or expression>`".into());
+        }
+        let input = if crate::utils::is_statement(input, self.options.auto_insert_semicolon) {
+            input.to_string()
+        } else {
+            format!("let _ = {{ {input} }};")
+        };
+
+        self.printer.writer.raw.write_with_color(
+            "Building cold then warm...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+        let toolchain = self.options.toolchain;
+        let cargo = self.repl.cargo.clone();
+        let (cold, warm) = self
+            .repl
+            .eval_in_tmp_repl(input, |_| cargo.cargo_build_times(toolchain))?;
+
+        let mut print_queue = PrintQueue::default();
+        for (kind, duration) in [("cold", cold), ("warm", warm)] {
+            print_queue.push(PrinterItem::String(
+                format!("{kind}: "),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            print_queue.push(PrinterItem::String(
+                format!("{duration:?}"),
+                self.theme.output_color(OutputKind::Value),
+            ));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
+    }
+
+    fn inner_time(
+        &mut self,
+        buffer: String,
+        pattern: &'static str,
+        release: bool,
+    ) -> Result<PrintQueue> {
+        let fnn = buffer
+            .split_once(pattern)
+            .map(|buf| buf.1)
+            .ok_or("No function specified")?;
+
+        if fnn.is_empty() {
+            return Err("No function specified".into());
+        }
+
+        let toolchain = self.options.toolchain;
+        let cargo = self.repl.cargo.clone();
+        let (status, raw_out) = self
+            .repl
+            .eval_in_tmp_repl(crate::probe::timed_code(fnn), |_| {
+                cargo.cargo_run(true, release, toolchain, Some(ctrlc_cancel))
+            })?;
+
+        let output_prompt = self.get_output_prompt();
+        // An error or a panic is shown without a time
+        let Some((output, time)) =
+            crate::probe::parse_timed_output(&raw_out).filter(|_| status.success())
+        else {
+            return Ok(format_eval_output(
+                status,
+                raw_out,
+                output_prompt,
+                self.options.show_warnings,
+                &self.repl.cargo.name,
+                self.options.new_lines_after_output,
+            )
+            .ok_or("failed to time the expression")?);
+        };
+
+        let mut print_queue = format_eval_output(
+            status,
+            output.to_string(),
+            output_prompt,
+            self.options.show_warnings,
+            &self.repl.cargo.name,
+            self.options.new_lines_after_output,
+        )
+        .unwrap_or_default();
+        print_queue.push(PrinterItem::String(
+            format!("took {time}"),
+            self.theme.output_color(OutputKind::Info),
+        ));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn bench(&mut self, buffer: String) -> Result<PrintQueue> {
+        let args = buffer.strip_prefix(":bench").expect("already checked");
+        if !args.trim().is_empty() {
+            if !args.starts_with(char::is_whitespace) {
+                return Err("Usage: `:bench` or `:bench [runs] <expression>`".into());
+            }
+            return self.bench_expression(args);
+        }
+        //make sure we have the latest changes in main.rs
+        self.repl.write()?;
+        let out = self
+            .repl
+            .cargo
+            .cargo_bench(self.options.toolchain)?
+            .trim()
+            .to_owned();
+
+        print_queue!(out, self.options.eval_color)
+    }
+
+    fn bench_expression(&mut self, args: &str) -> Result<PrintQueue> {
+        let (runs, expression) = crate::probe::parse_bench_args(args);
+
+        self.printer
+            .writer
+            .raw
+            .write_with_color("Benchmarking...", self.theme.output_color(OutputKind::Info))?;
+        self.before_compiling_hook();
+        // Evaluated in a temporary repl like every expression, the session code is not changed
+        let result = self.repl.eval_with_configuration(EvalConfig {
+            input: crate::probe::bench_code(expression, runs),
+            interactive_function: Some(ctrlc_cancel),
+            color: true,
+            evaluator: &crate::utils::unit_macro_evaluator(),
+            compile_mode: CompileMode::Release,
+        });
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+        // A runtime error (exp: a panic) is shown as is
+        let Some(samples) = crate::probe::parse_bench_samples(&output) else {
+            return print_queue!(output, self.theme.output_color(OutputKind::Error));
+        };
+        print_queue!(
+            crate::probe::format_bench(runs, crate::probe::BenchStats::new(&samples)),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn benchmark_compare(&mut self, buffer: String) -> Result<PrintQueue> {
+        use crate::probe::BenchStats;
+
+        // exp: :benchmark-compare v.iter().sum::<u64>() ;; v.iter().fold(0, |a, b| a + b)
+        let (a, b) = buffer
+            .strip_prefix(":benchmark-compare")
+            .expect("already checked")
+            .split_once(";;")
+            .ok_or("Usage: `:benchmark-compare <expression> ;; <expression>`")?;
+        let (a, b) = (a.trim(), b.trim());
+
+        // Check each side first, to show which one doesn't compile
+        for expression in [a, b] {
+            let check = self
+                .repl
+                .eval_check(format!("let _ = {{ {expression} }};"))?;
+            if check_is_err(&check.output) {
+                let mut print_queue = PrintQueue::default();
+                print_queue.push(PrinterItem::String(
+                    format!("`{expression}` doesn't compile:"),
+                    self.theme.output_color(OutputKind::Error),
+                ));
+                print_queue.add_new_line(1);
+                print_queue.append(&mut self.format_compile_error(check.output));
+                return Ok(print_queue);
+            }
+        }
+
+        self.printer
+            .writer
+            .raw
+            .write_with_color("Benchmarking...", self.theme.output_color(OutputKind::Info))?;
+        self.before_compiling_hook();
+        let result = self.repl.eval_with_configuration(EvalConfig {
+            input: crate::probe::benchmark_compare_code(a, b),
+            interactive_function: Some(ctrlc_cancel),
+            color: true,
+            evaluator: &crate::utils::unit_macro_evaluator(),
+            compile_mode: CompileMode::Release,
+        });
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+        // A runtime error (exp: a panic) is shown as is
+        let Some((a_samples, b_samples)) = crate::probe::parse_benchmark_samples(&output) else {
+            return print_queue!(output, self.theme.output_color(OutputKind::Error));
+        };
+        print_queue!(
+            crate::probe::format_benchmark_compare(
+                (a, BenchStats::new(&a_samples)),
+                (b, BenchStats::new(&b_samples)),
+            ),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn asm(&mut self, buffer: String) -> Result<PrintQueue> {
+        let fnn = buffer.strip_prefix(":asm").expect("already checked").trim();
+        if fnn.is_empty() {
+            return Err("No function specified".into());
+        }
+
+        let asm = self
+            .repl
+            .with_lib(|| self.repl.cargo.cargo_asm(fnn, self.options.toolchain))??;
+
+        print_queue!(asm, self.options.eval_color)
+    }
+
+    fn executor(&mut self, buffer: String) -> Result<PrintQueue> {
+        let executor = buffer.split_whitespace().nth(1);
+        if let Some(executor) = executor {
+            let executor = Executor::from_str(executor.trim())?;
+            self.repl.set_executor(executor)?;
+            // save executor
+            self.options.executor = executor;
+            success!(self)
+        } else {
+            print_queue!(
+                self.options.executor.to_string(),
+                self.theme.output_color(OutputKind::Value)
+            )
+        }
+    }
+
+    fn evaluator(&mut self, buffer: String) -> Result<PrintQueue> {
+        let evaluator = buffer
+            .strip_prefix(":evaluator")
+            .expect("already checked")
+            .trim();
+        // get
+        if evaluator.is_empty() {
+            return print_queue!(
+                self.options.evaluator.join("$$"),
+                self.theme.output_color(OutputKind::Value)
+            );
+        }
+
+        // reset
+        if evaluator == "reset" {
+            self.options.reset_evaluator();
+            return success!(self);
+        }
+
+        // Sanity checks
+        // set
+        if !evaluator.contains("$$") {
+            return Err(
+                "evaluator must contain `$$`, `$$` will be replaced by IRust by the code input"
+                    .into(),
+            );
+        }
+        if !evaluator.ends_with(';') {
+            return Err("evaluator must end with ;".into());
+        }
+
+        let evaluator: Vec<String> = evaluator.split("$$").map(ToOwned::to_owned).collect();
+        if evaluator.len() != 2 {
+            return Err("evaluator requires two parts".into());
+        }
+
+        self.options.evaluator = evaluator;
+        success!(self)
+    }
+
+    fn scripts(&mut self, buffer: String) -> Result<PrintQueue> {
+        let scripts_list = if let Some(scripts_list) = self.scripts_list() {
+            scripts_list
+        } else {
+            return Err("No scripts found".into());
+        };
+
+        let buffer: Vec<&str> = buffer
+            .strip_prefix(":scripts")
+            .expect("already checked")
+            .split_whitespace()
+            .collect();
+
+        //TODO: This code can be improved *a lot* by doing formatting here, instead of letting each script manager do its own thing
+
+        match buffer.len() {
+            0 => {
+                //NOTE: Hack formatting because its less code
+                //Assumes that script state is the last column
+
+                if scripts_list.is_empty() {
+                    return print_queue!(
+                        "Script list is empty".to_string(),
+                        self.theme.output_color(OutputKind::Warning)
+                    );
+                }
+
+                let mut queue = PrintQueue::default();
+                let mut scripts_list = scripts_list.lines();
+
+                let header = scripts_list.next().expect("exists");
+                queue.push(PrinterItem::String(
+                    header.to_string(),
+                    self.theme.output_color(OutputKind::Info),
+                ));
+                queue.add_new_line(2);
+
+                let mut list: Vec<_> = scripts_list.collect();
+                list.sort_by_key(|line| line.ends_with("false"));
+                list.iter().for_each(|line| {
+                    if line.ends_with("true") {
+                        queue.push(PrinterItem::String(
+                            line.to_string(),
+                            self.theme.output_color(OutputKind::Success),
+                        ))
+                    } else {
+                        queue.push(PrinterItem::String(
+                            line.to_string(),
+                            self.theme.output_color(OutputKind::Error),
+                        ))
+                    }
+                    queue.add_new_line(1);
+                });
+
+                Ok(queue)
             }
             1 => {
                 // Print script
@@ -832,7 +3341,10 @@ impl IRust {
                         .next()
                         .expect("header should be always present")
                         .to_string();
-                    print_queue!(header + "\n" + script, Color::Blue)
+                    print_queue!(
+                        header + "\n" + script,
+                        self.theme.output_color(OutputKind::Value)
+                    )
                 } else {
                     Err(format!("script: {} not found", &buffer[0]).into())
                 }
@@ -845,220 +3357,1177 @@ impl IRust {
                     } else {
                         None
                     }
-                }) {
-                    match buffer[1] {
-                        "activate" => {
-                            if let Some(command) = self.activate_script(script)? {
-                                // script start up command
-                                self.execute(command)?;
-                            }
-                            success!()
+                }) {
+                    match buffer[1] {
+                        "activate" => {
+                            if let Some(command) = self.activate_script(script)? {
+                                // script start up command
+                                self.execute(command)?;
+                            }
+                            success!(self)
+                        }
+                        "deactivate" => {
+                            if let Some(command) = self.deactivate_script(script)? {
+                                // script clean up command
+                                self.execute(command)?;
+                            }
+                            success!(self)
+                        }
+                        _ => Err(format!("Unknown argument: {}", &buffer[1]).into()),
+                    }
+                } else {
+                    Err(format!("script: {} not found", &buffer[0]).into())
+                }
+            }
+            _ => Err("Incorrect number of arguments for `:scripts` command".into()),
+        }
+    }
+    fn compile_time(&mut self, buffer: String) -> Result<PrintQueue> {
+        let buffer: Vec<&str> = buffer
+            .strip_prefix(":compile_time")
+            .expect("already checked")
+            .split_whitespace()
+            .collect();
+        match buffer.len() {
+            0 => {
+                print_queue!(
+                    self.options.compile_time.to_string(),
+                    self.theme.output_color(OutputKind::Value)
+                )
+            }
+            1 => match buffer[0].to_lowercase().as_str() {
+                "on" => {
+                    self.options.compile_time = true;
+                    success!(self)
+                }
+                "off" => {
+                    self.options.compile_time = false;
+                    success!(self)
+                }
+                _ => Err("Invalid argument (only accepts on/off)".into()),
+            },
+            _ => Err("Invalid number of arguments".into()),
+        }
+    }
+
+    fn compile_mode(&mut self, buffer: String) -> Result<PrintQueue> {
+        let mode = buffer
+            .strip_prefix(":compile_mode")
+            .expect("already checked")
+            .trim();
+
+        if mode.is_empty() {
+            return print_queue!(
+                self.options.compile_mode.to_string(),
+                self.theme.output_color(OutputKind::Value)
+            );
+        }
+
+        self.options.compile_mode = CompileMode::from_str(mode)?;
+        success!(self)
+    }
+
+    fn profile(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :profile on
+        let compile_mode = match buffer
+            .strip_prefix(":profile")
+            .expect("already checked")
+            .trim()
+        {
+            "on" => CompileMode::Release,
+            "off" => CompileMode::Debug,
+            arg => {
+                return Err(format!("Invalid argument: `{arg}`, usage: `:profile on|off`").into())
+            }
+        };
+        self.options.compile_mode = compile_mode;
+        if matches!(compile_mode, CompileMode::Debug) {
+            return success!(self);
+        }
+
+        // Build the session now so the next evaluation doesn't pay for it
+        self.printer.writer.raw.write_with_color(
+            "Building in release mode, the first release build may be slow...",
+            self.theme.output_color(OutputKind::Warning),
+        )?;
+        self.repl.write()?;
+        self.before_compiling_hook();
+        let result = self
+            .repl
+            .cargo
+            .cargo_build_output(true, true, self.options.toolchain);
+        self.after_compiling_hook();
+        let (status, output) = result?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+        success!(self)
+    }
+
+    fn assert(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :assert 2 + 2 == 4
+        let expression = buffer
+            .strip_prefix(":assert")
+            .expect("already checked")
+            .trim();
+        if expression.is_empty() {
+            return Err("Usage: `:assert <boolean expression>`".into());
+        }
+
+        self.before_compiling_hook();
+        // the annotation makes a non boolean expression a type error
+        let result = self.repl.eval_with_configuration(EvalConfig {
+            input: format!("{{ let irust_assert: bool = {{ {expression} }}; irust_assert }}"),
+            interactive_function: Some(ctrlc_cancel),
+            color: true,
+            evaluator: &*DEFAULT_EVALUATOR,
+            compile_mode: self.options.compile_mode,
+        });
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+
+        // the value is printed after the output of the expression
+        let (stdout, value) = output.rsplit_once('\n').unwrap_or(("", &output));
+        let mut print_queue = PrintQueue::default();
+        if !stdout.is_empty() {
+            print_queue.push(PrinterItem::String(
+                stdout.to_string(),
+                self.options.eval_color,
+            ));
+            print_queue.add_new_line(1);
+        }
+        if value == "true" {
+            print_queue.push(PrinterItem::Str(
+                "PASS",
+                self.theme.output_color(OutputKind::Success),
+            ));
+        } else {
+            let error_color = self.theme.output_color(OutputKind::Error);
+            print_queue.push(PrinterItem::Str("FAIL", error_color));
+            print_queue.push(PrinterItem::String(format!(": {expression}"), error_color));
+        }
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn dbg(&mut self, buffer: String) -> Result<PrintQueue> {
+        let expression = buffer
+            .strip_prefix(":dbg")
+            .expect("already checked")
+            .trim()
+            .to_owned();
+        let expression = if expression.is_empty() {
+            "println!(); // Compiler black box".into()
+        } else {
+            format!("print!(\"{{:?}}\", {expression}); // Print to make sure that the compiler doesn't remove the expression (blackbox requires nightly)")
+        };
+
+        let (debugger, debugger_arg) = match self.options.debugger {
+            crate::irust::options::Debugger::LLDB => ("rust-lldb", "-s"),
+            crate::irust::options::Debugger::GDB => ("rust-gdb", "-x"),
+        };
+
+        self.printer.writer.raw.write_with_color(
+            "Waiting for debugger...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+
+        let expr_line_num = self.repl.lines_count();
+
+        let cargo = self.repl.cargo.clone();
+        self.repl.eval_in_tmp_repl(expression, |_| -> Result<()> {
+            let (status, _out) = cargo.cargo_build_output(true, false, self.options.toolchain)?;
+            if !status.success() {
+                return Err("Failed to execute expression".into());
+            }
+
+            let dbg_cmds_path = env::temp_dir().join("irust_dbg_cmds");
+            {
+                let mut dbg_cmds = std::fs::File::create(&dbg_cmds_path)?;
+                writeln!(&mut dbg_cmds, "b {expr_line_num}")?;
+                writeln!(&mut dbg_cmds, "r")?;
+            }
+
+            crossterm::terminal::disable_raw_mode()?;
+            println!();
+            crossterm::execute!(std::io::stdout(), crossterm::cursor::Show)?;
+
+            #[cfg(windows)]
+            std::process::Command::new("cmd")
+                .arg("/C")
+                .arg(debugger)
+                .args([debugger_arg, &dbg_cmds_path.display().to_string()])
+                .arg(&cargo.paths.exe_path)
+                .spawn()?
+                .wait()?;
+
+            #[cfg(not(windows))]
+            {
+                std::process::Command::new(debugger)
+                    .args([debugger_arg, &dbg_cmds_path.display().to_string()])
+                    .arg(&cargo.paths.exe_path)
+                    .spawn()?
+                    .wait()?;
+            }
+
+            crossterm::terminal::enable_raw_mode()?;
+            Ok(())
+        })?;
+
+        success!(self)
+    }
+    fn run_main(&mut self) -> Result<PrintQueue> {
+        if !crate::utils::defines_main(&self.repl.user_code()) {
+            return Err("No `fn main` is defined in the repl".into());
+        }
+        // the user main shadows the repl main, report its result like a real program does
+        let call_main = "if std::process::Termination::report(main()) != std::process::ExitCode::SUCCESS { std::process::exit(1) }";
+        let release = self.options.compile_mode.is_release();
+        let exe_path = if release {
+            self.repl.cargo.paths.release_exe_path.clone()
+        } else {
+            self.repl.cargo.paths.exe_path.clone()
+        };
+
+        self.before_compiling_hook();
+        let cargo = self.repl.cargo.clone();
+        let toolchain = self.options.toolchain;
+        let build = self.repl.eval_in_tmp_repl(call_main.to_string(), |_| {
+            Ok(cargo.cargo_build_output(true, release, toolchain)?)
+        });
+        self.after_compiling_hook();
+        let (status, output) = build?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+
+        // the program gets the terminal, so interactive programs work
+        crossterm::terminal::disable_raw_mode()?;
+        println!();
+        crossterm::execute!(std::io::stdout(), crossterm::cursor::Show)?;
+        let status = process::Command::new(&exe_path)
+            .current_dir(self.global_variables.get_cwd())
+            .status();
+        crossterm::terminal::enable_raw_mode()?;
+        let status = status?;
+
+        let color = if status.success() {
+            self.theme.output_color(OutputKind::Success)
+        } else {
+            self.theme.output_color(OutputKind::Warning)
+        };
+        print_queue!(format!("main exited with {status}"), color)
+    }
+
+    fn shell_interpolate(&mut self, buffer: String) -> Result<String> {
+        // Replace shell expression with rust expression
+        // The shell expression is delimited by `$$` and `$$`
+        // There could be multiple shell expressions
+        let mut buffer = buffer.chars().peekable();
+        let mut res = String::new();
+
+        let mut maybe_error = None;
+        (|| -> Option<()> {
+            loop {
+                let c = buffer.next()?;
+
+                if c == '$' && buffer.peek() == Some(&'$') {
+                    buffer.next();
+                    let mut shell_expr = String::new();
+                    while let Some(s) = buffer.next() {
+                        if s == '$' && buffer.peek() == Some(&'$') {
+                            buffer.next();
+                            break;
+                        }
+                        shell_expr.push(s);
+                    }
+                    let mut shell_expr = shell_expr.split_whitespace();
+                    let shell_res = if cfg!(windows) {
+                        process::Command::new("cmd")
+                            .arg("/C")
+                            .arg(shell_expr.next()?)
+                            .args(shell_expr.collect::<Vec<_>>())
+                            .output()
+                    } else {
+                        process::Command::new(shell_expr.next()?)
+                            .args(shell_expr.collect::<Vec<_>>())
+                            .output()
+                    };
+                    let shell_res = match shell_res {
+                        Ok(res) => stdout_and_stderr(res),
+                        Err(e) => {
+                            maybe_error = Some(e);
+                            break None;
                         }
-                        "deactivate" => {
-                            if let Some(command) = self.deactivate_script(script)? {
-                                // script clean up command
-                                self.execute(command)?;
+                    };
+
+                    struct F<'a>(&'a [u8]);
+                    impl std::fmt::Display for F<'_> {
+                        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                            write!(f, "String::from_utf8_lossy(&[")?;
+                            for b in self.0.iter() {
+                                write!(f, "{b},")?;
                             }
-                            success!()
+                            write!(f, "]).to_string()")
                         }
-                        _ => Err(format!("Unknown argument: {}", &buffer[1]).into()),
                     }
+                    let shell_res = format!("{}", F(shell_res.as_bytes()));
+                    res.push_str(&shell_res);
                 } else {
-                    Err(format!("script: {} not found", &buffer[0]).into())
+                    res.push(c);
                 }
             }
-            _ => Err("Incorrect number of arguments for `:scripts` command".into()),
+        })();
+        if let Some(e) = maybe_error {
+            return Err(e.into());
         }
+
+        Ok(res)
     }
-    fn compile_time(&mut self, buffer: String) -> Result<PrintQueue> {
-        let buffer: Vec<&str> = buffer
-            .strip_prefix(":compile_time")
+
+    fn expand(&mut self, buffer: String) -> Result<PrintQueue> {
+        let fnn = buffer
+            .strip_prefix(":expand")
+            .expect("already checked")
+            .trim();
+        // a function name or an expression, exp: `:expand vec![1, 2]`
+        if !fnn.is_empty() && !crate::utils::is_ident(fnn) {
+            return self.expand_expression(fnn);
+        }
+        if !fnn.is_empty() {
+            let r = self.repl.with_lib(|| {
+                self.repl
+                    .cargo
+                    .cargo_expand(Some(fnn), self.options.toolchain)
+            })??;
+            print_queue!(r, Color::White)
+        } else {
+            print_queue!(
+                self.repl.cargo.cargo_expand(None, self.options.toolchain)?,
+                Color::White
+            )
+        }
+    }
+
+    fn expand_expression(&mut self, expression: &str) -> Result<PrintQueue> {
+        if !crate::dependencies::dep_installed("cargo-expand") {
+            return Err(
+                "cargo-expand is required, install it with `cargo install cargo-expand`".into(),
+            );
+        }
+        let toolchain = self.options.toolchain;
+        let expanded = self
+            .repl
+            .eval_in_tmp_repl(crate::probe::expand_probe(expression), |repl| {
+                repl.cargo.cargo_expand_bin(toolchain)
+            })?;
+        let expanded = crate::probe::expanded_expression(&expanded)
+            .ok_or_else(|| format!("Failed to find the expanded expression:\n{expanded}"))?;
+        let mut print_queue = highlight(&expanded.into(), &self.theme);
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn env_file(&mut self, buffer: String) -> Result<PrintQueue> {
+        let path = buffer
+            .strip_prefix(":env-file")
+            .expect("already checked")
+            .trim();
+        let path = if path.is_empty() { ".env" } else { path };
+
+        let vars = crate::utils::parse_dotenv(&std::fs::read_to_string(path)?)?;
+        if vars.is_empty() {
+            return Err(format!("No variables found in {path}").into());
+        }
+
+        let mut names = vec![];
+        for (key, value) in vars {
+            // The eval process and shell commands inherit irust environment
+            env::set_var(&key, value);
+            names.push(key);
+        }
+
+        print_queue!(
+            format!("Loaded {} variables: {}", names.len(), names.join(", ")),
+            self.theme.output_color(OutputKind::Success)
+        )
+    }
+
+    fn config(&mut self, buffer: String) -> Result<PrintQueue> {
+        const CONFIG_KEYS: &[&str] = &[
+            "echo-insert",
+            "async-runtime",
+            "bt-filter",
+            "persist-target",
+            "confirm-destructive",
+            "trait-impls",
+            "error-verbosity",
+            "track-alloc",
+            "fmt-on-edit",
+            "quiet",
+            "sandbox",
+            "pager",
+            "echo-unit-macros",
+            "stack-size",
+            "compare-toolchains",
+        ];
+
+        let args: Vec<&str> = buffer
+            .strip_prefix(":config")
             .expect("already checked")
             .split_whitespace()
             .collect();
-        match buffer.len() {
-            0 => {
-                print_queue!(self.options.compile_time.to_string(), Color::Blue)
+
+        match args.as_slice() {
+            [] => {
+                let config = CONFIG_KEYS
+                    .iter()
+                    .map(|key| Ok(format!("{key} = {}", self.get_config(key)?)))
+                    .collect::<Result<Vec<_>>>()?
+                    .join("\n");
+                print_queue!(config, self.theme.output_color(OutputKind::Value))
+            }
+            [key] => print_queue!(
+                self.get_config(key)?,
+                self.theme.output_color(OutputKind::Value)
+            ),
+            [key, value @ ..] => {
+                self.set_config(key, &value.join(" "))?;
+                if *key == "sandbox" && self.options.sandbox {
+                    return print_queue!(
+                        format!(
+                            "Sandbox mode is active, evaluations run in {} with an empty environment{}",
+                            self.repl.cargo.paths.sandbox_dir.display(),
+                            if cfg!(target_os = "linux") {
+                                ", without network and without file system access outside of it"
+                            } else {
+                                " (the file system and the network are not restricted on this platform)"
+                            }
+                        ),
+                        self.theme.output_color(OutputKind::Info)
+                    );
+                }
+                success!(self)
+            }
+        }
+    }
+
+    fn get_config(&self, key: &str) -> Result<String> {
+        let on_off = |v: bool| if v { "on" } else { "off" }.to_string();
+        Ok(match key {
+            "echo-insert" => on_off(self.options.echo_insert),
+            "async-runtime" => self.options.async_runtime.to_string(),
+            "bt-filter" => self.options.bt_filter.to_string(),
+            "persist-target" => on_off(self.options.persist_target),
+            "confirm-destructive" => on_off(self.options.confirm_destructive),
+            "trait-impls" => self.options.trait_impls.join(", "),
+            "error-verbosity" => self.options.error_verbosity.to_string(),
+            "track-alloc" => on_off(self.options.track_alloc),
+            "fmt-on-edit" => on_off(self.options.fmt_on_edit),
+            "quiet" => on_off(self.options.quiet),
+            "sandbox" => on_off(self.options.sandbox),
+            "pager" => self.options.pager.clone().unwrap_or_else(|| "off".into()),
+            "echo-unit-macros" => on_off(self.options.echo_unit_macros),
+            "compare-toolchains" => match self.options.compare_toolchains.as_slice() {
+                [] => "installed".into(),
+                toolchains => toolchains
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            },
+            "stack-size" => self
+                .options
+                .stack_size
+                .map_or_else(|| "default".into(), |size| size.to_string()),
+            _ => return Err(format!("Unknown config key: {key}").into()),
+        })
+    }
+
+    fn set_config(&mut self, key: &str, value: &str) -> Result<()> {
+        let on_off = || -> Result<bool> {
+            match value.to_lowercase().as_str() {
+                "on" | "true" => Ok(true),
+                "off" | "false" => Ok(false),
+                _ => Err(format!("Invalid value for {key} (only accepts on/off)").into()),
+            }
+        };
+        match key {
+            "echo-insert" => self.options.echo_insert = on_off()?,
+            "async-runtime" => {
+                let runtime = AsyncRuntime::from_str(value)?;
+                crate::eval::check_async_runtime(&self.repl, runtime)?;
+                self.options.async_runtime = runtime;
+            }
+            "bt-filter" => self.options.bt_filter = BacktraceFilter::from_str(value)?,
+            "persist-target" => self.options.persist_target = on_off()?,
+            "confirm-destructive" => self.options.confirm_destructive = on_off()?,
+            "error-verbosity" => self.options.error_verbosity = ErrorVerbosity::from_str(value)?,
+            "track-alloc" => {
+                self.options.track_alloc = on_off()?;
+                self.repl.set_track_alloc(self.options.track_alloc);
+            }
+            "fmt-on-edit" => self.options.fmt_on_edit = on_off()?,
+            "quiet" => self.options.quiet = on_off()?,
+            "echo-unit-macros" => self.options.echo_unit_macros = on_off()?,
+            "stack-size" => {
+                self.options.stack_size = match value.trim() {
+                    "default" => None,
+                    size => Some(
+                        size.parse::<usize>()
+                            .ok()
+                            .filter(|size| *size > 0)
+                            .ok_or("Usage: `:config stack-size <bytes>|default`")?,
+                    ),
+                };
+                self.repl.set_stack_size(self.options.stack_size);
+            }
+            "sandbox" => {
+                self.options.sandbox = on_off()?;
+                self.repl.set_sandbox(self.options.sandbox)?;
+            }
+            "pager" => {
+                self.options.pager = match value.trim() {
+                    "" => return Err("Usage: `:config pager <command>|off`".into()),
+                    "off" => None,
+                    pager => Some(pager.to_string()),
+                };
+                self.printer.pager = self.options.pager.clone();
+            }
+            "compare-toolchains" => {
+                self.options.compare_toolchains = match value.trim() {
+                    "installed" => vec![],
+                    toolchains => toolchains
+                        .split(',')
+                        .map(|toolchain| ToolChain::from_str(toolchain.trim()))
+                        .collect::<Result<_>>()?,
+                }
+            }
+            "trait-impls" => {
+                self.options.trait_impls = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tr| !tr.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            }
+            _ => return Err(format!("Unknown config key: {key}").into()),
+        }
+        Ok(())
+    }
+
+    fn doc(&mut self, buffer: String) -> Result<PrintQueue> {
+        let arg = buffer.strip_prefix(":doc").expect("already checked").trim();
+        if arg.is_empty() {
+            return Err("Usage: `:doc <crate or path>` or `:doc --private`".into());
+        }
+        if arg != "--private" {
+            let url = crate::utils::doc_url(arg)?;
+            crate::utils::open_in_browser(&url)?;
+            return print_queue!(url, self.theme.output_color(OutputKind::Value));
+        }
+
+        self.printer.writer.raw.write_with_color(
+            "Generating docs...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+        let index = self
+            .repl
+            .with_lib(|| self.repl.cargo.cargo_doc(true, self.options.toolchain))??;
+
+        crate::utils::open_in_browser(&index.display().to_string())?;
+        print_queue!(
+            index.display().to_string(),
+            self.theme.output_color(OutputKind::Value)
+        )
+    }
+
+    fn eval_with(&mut self, buffer: String) -> Result<PrintQueue> {
+        const USAGE: &str = "Usage: `:eval-with <crate> -- <expression>`";
+        let (dep, expression) = buffer
+            .strip_prefix(":eval-with")
+            .expect("already checked")
+            .split_once(" -- ")
+            .ok_or(USAGE)?;
+        let dep = crate::utils::split_args(dep.trim().to_string());
+        if dep.is_empty() || expression.trim().is_empty() {
+            return Err(USAGE.into());
+        }
+
+        self.printer.writer.raw.write_with_color(
+            &format!("Evaluating with temporary dependency {}...", dep[0]),
+            self.theme.output_color(OutputKind::Info),
+        )?;
+
+        self.before_compiling_hook();
+        let result = self.repl.with_temporary_dep(&dep, |repl| {
+            repl.eval_with_configuration(EvalConfig {
+                input: expression,
+                interactive_function: Some(ctrlc_cancel),
+                color: true,
+                evaluator: &self.options.evaluator,
+                compile_mode: self.options.compile_mode,
+            })
+        });
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result??;
+        let output = filter_backtrace(&output, self.options.bt_filter, &self.repl.cargo.name);
+
+        Ok(format_eval_output(
+            status,
+            output,
+            self.get_output_prompt(),
+            self.options.show_warnings,
+            &self.repl.cargo.name,
+            self.options.new_lines_after_output,
+        )
+        .unwrap_or_default())
+    }
+
+    fn explain_type(&mut self, buffer: String) -> Result<PrintQueue> {
+        use crate::probe::{
+            explain_type_probe, field_types_probe, parse_explain_type, EXPLAIN_TYPE_METHODS,
+        };
+
+        let ty = buffer
+            .strip_prefix(":explain-type")
+            .expect("already checked")
+            .trim();
+        if ty.is_empty() {
+            return Err("Usage: `:explain-type <type>`".into());
+        }
+
+        let toolchain = self.options.toolchain;
+        let cargo = self.repl.cargo.clone();
+        let (_status, output) = self.repl.eval_in_tmp_repl(
+            explain_type_probe(ty, EXPLAIN_TYPE_METHODS),
+            |_| -> Result<_> { Ok(cargo.cargo_build_output(false, false, toolchain)?) },
+        )?;
+        let Some((fields, methods)) = parse_explain_type(&output, EXPLAIN_TYPE_METHODS) else {
+            return Ok(format_err_printqueue(
+                &output,
+                self.options.show_warnings,
+                &self.repl.cargo.name,
+            ));
+        };
+
+        let field_types: Vec<String> = if fields.is_empty() {
+            vec![]
+        } else {
+            let EvalResult { output, status, .. } =
+                self.repl.eval(field_types_probe(ty, &fields))?;
+            if !status.success() {
+                return Ok(format_err_printqueue(
+                    &output,
+                    self.options.show_warnings,
+                    &self.repl.cargo.name,
+                ));
+            }
+            serde_json::from_str(&output)
+                .map_err(|e| format!("failed to get the fields types: {e}"))?
+        };
+
+        let value_color = self.theme.output_color(OutputKind::Value);
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::String(ty.to_string(), value_color));
+        print_queue.add_new_line(1);
+        print_queue.push(PrinterItem::Str(
+            "fields:",
+            self.theme.output_color(OutputKind::Info),
+        ));
+        if fields.is_empty() {
+            print_queue.push(PrinterItem::Str(
+                " none",
+                self.theme.output_color(OutputKind::Info),
+            ));
+        }
+        print_queue.add_new_line(1);
+        for (field, field_type) in fields.iter().zip(field_types) {
+            let field_type = crate::utils::apply_type_display_aliases(
+                &field_type,
+                &self.options.type_display_aliases,
+            );
+            print_queue.push(PrinterItem::String(
+                format!("  {field}: {field_type}"),
+                value_color,
+            ));
+            print_queue.add_new_line(1);
+        }
+        print_queue.push(PrinterItem::Str(
+            "methods:",
+            self.theme.output_color(OutputKind::Info),
+        ));
+        let methods = if methods.is_empty() {
+            " none".to_string()
+        } else {
+            format!(" {}", methods.join(", "))
+        };
+        print_queue.push(PrinterItem::String(methods, value_color));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn inspect(&mut self, buffer: String) -> Result<PrintQueue> {
+        let expression = buffer
+            .strip_prefix(":inspect")
+            .expect("already checked")
+            .trim();
+        if expression.is_empty() {
+            return Err("Usage: `:inspect <expression>`".into());
+        }
+
+        self.before_compiling_hook();
+        let result = self.repl.eval_with_configuration(EvalConfig {
+            input: expression,
+            interactive_function: Some(ctrlc_cancel),
+            color: true,
+            evaluator: &crate::probe::inspect_evaluator(),
+            compile_mode: self.options.compile_mode,
+        });
+        self.after_compiling_hook();
+        let EvalResult { output, status, .. } = result?;
+        if !status.success() {
+            return Ok(self.format_compile_error(output));
+        }
+        // The status is the build status, a runtime error (exp: a panic) is shown as is
+        let Some((stdout, ty, value)) = crate::probe::parse_inspect_output(&output) else {
+            return print_queue!(output, self.theme.output_color(OutputKind::Error));
+        };
+
+        let mut print_queue = PrintQueue::default();
+        if !stdout.is_empty() {
+            print_queue.push(PrinterItem::String(stdout.to_string(), Color::White));
+        }
+        print_queue.push(PrinterItem::String(
+            crate::utils::apply_type_display_aliases(&ty, &self.options.type_display_aliases),
+            self.theme.output_color(OutputKind::Value),
+        ));
+        print_queue.add_new_line(1);
+        print_queue.push(PrinterItem::String(
+            value.to_string(),
+            self.theme.output_color(OutputKind::Value),
+        ));
+        print_queue.add_new_line(1);
+        Ok(print_queue)
+    }
+
+    fn compare_toolchains(&mut self, buffer: String) -> Result<PrintQueue> {
+        let expression = buffer
+            .strip_prefix(":compare-toolchains")
+            .expect("already checked")
+            .trim();
+        if expression.is_empty() {
+            return Err("Usage: `:compare-toolchains <expression>`".into());
+        }
+        let toolchains = if self.options.compare_toolchains.is_empty() {
+            let output = stdout_and_stderr(
+                process::Command::new("rustup")
+                    .args(["toolchain", "list"])
+                    .output()
+                    .map_err(|e| format!("failed to list the toolchains with rustup: {e}"))?,
+            );
+            crate::utils::installed_toolchains(&output)
+        } else {
+            self.options.compare_toolchains.clone()
+        };
+        if toolchains.is_empty() {
+            return Err(
+                "No toolchains to compare, set them with `:config compare-toolchains stable, nightly`"
+                    .into(),
+            );
+        }
+
+        let mut print_queue = PrintQueue::default();
+        let toolchain = self.repl.toolchain();
+        for compared in toolchains {
+            self.printer.writer.raw.write_with_color(
+                &format!("Evaluating with {compared}..."),
+                self.theme.output_color(OutputKind::Info),
+            )?;
+            self.printer.writer.raw.flush()?;
+            self.repl.set_toolchain(compared);
+            self.before_compiling_hook();
+            let result = self.repl.eval_with_configuration(EvalConfig {
+                input: expression,
+                interactive_function: Some(ctrlc_cancel),
+                color: true,
+                evaluator: &self.options.evaluator,
+                compile_mode: self.options.compile_mode,
+            });
+            self.after_compiling_hook();
+            self.repl.set_toolchain(toolchain);
+            self.printer.write_newline(&self.buffer);
+
+            print_queue.push(PrinterItem::String(
+                format!("[{compared}]"),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            print_queue.add_new_line(1);
+            let EvalResult { output, status, .. } = result?;
+            if status.success() {
+                print_queue.push(PrinterItem::String(
+                    output,
+                    self.theme.output_color(OutputKind::Value),
+                ));
+                print_queue.add_new_line(1);
+            } else {
+                // A toolchain failing to compile the expression doesn't stop the comparison
+                print_queue.append(&mut self.format_compile_error(output));
             }
-            1 => match buffer[0].to_lowercase().as_str() {
-                "on" => {
-                    self.options.compile_time = true;
-                    success!()
-                }
-                "off" => {
-                    self.options.compile_time = false;
-                    success!()
-                }
-                _ => Err("Invalid argument (only accepts on/off)".into()),
-            },
-            _ => Err("Invalid number of arguments".into()),
         }
+        Ok(print_queue)
     }
 
-    fn compile_mode(&mut self, buffer: String) -> Result<PrintQueue> {
-        let mode = buffer
-            .strip_prefix(":compile_mode")
+    fn trait_impls(&mut self, buffer: String) -> Result<PrintQueue> {
+        let ty = buffer
+            .strip_prefix(":trait-impls")
             .expect("already checked")
             .trim();
+        if ty.is_empty() {
+            return Err("Usage: `:trait-impls <type>`".into());
+        }
+        let traits = &self.options.trait_impls;
 
-        if mode.is_empty() {
-            return print_queue!(self.options.compile_mode.to_string(), Color::Blue);
+        let EvalResult { output, status, .. } = self.repl.eval_with_configuration(EvalConfig {
+            input: crate::probe::trait_impls_probe(ty, traits),
+            interactive_function: None,
+            color: true,
+            evaluator: &*irust_repl::DEFAULT_EVALUATOR,
+            compile_mode: CompileMode::Debug,
+        })?;
+        if !status.success() {
+            return Ok(format_err_printqueue(
+                &output,
+                self.options.show_warnings,
+                &self.repl.cargo.name,
+            ));
         }
+        let implemented: Vec<bool> = output
+            .trim_matches(['[', ']'])
+            .split(", ")
+            .map(|b| b == "true")
+            .collect();
 
-        self.options.compile_mode = CompileMode::from_str(mode)?;
-        success!()
+        let mut print_queue = PrintQueue::default();
+        for (tr, implemented) in traits.iter().zip(implemented) {
+            let (mark, color) = if implemented {
+                ("[x]", self.theme.output_color(OutputKind::Success))
+            } else {
+                ("[ ]", self.theme.output_color(OutputKind::Error))
+            };
+            print_queue.push(PrinterItem::String(format!("{mark} {tr}"), color));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
     }
 
-    fn dbg(&mut self, buffer: String) -> Result<PrintQueue> {
-        let expression = buffer
-            .strip_prefix(":dbg")
-            .expect("already checked")
-            .trim()
-            .to_owned();
-        let expression = if expression.is_empty() {
-            "println!(); // Compiler black box".into()
-        } else {
-            format!("print!(\"{{:?}}\", {expression}); // Print to make sure that the compiler doesn't remove the expression (blackbox requires nightly)")
-        };
+    fn deps_outdated(&mut self) -> Result<PrintQueue> {
+        if std::env::var("CARGO_NET_OFFLINE").is_ok_and(|v| v == "true") {
+            return print_queue!(
+                "Offline mode, skipping the registry query".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        self.printer.writer.raw.write_with_color(
+            "Looking for newer versions...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
 
-        let (debugger, debugger_arg) = match self.options.debugger {
-            crate::irust::options::Debugger::LLDB => ("rust-lldb", "-s"),
-            crate::irust::options::Debugger::GDB => ("rust-gdb", "-x"),
-        };
+        // Use cargo-outdated if it's installed
+        if let Ok(output) = std::process::Command::new("cargo-outdated")
+            .current_dir(&self.repl.cargo.paths.irust_dir)
+            .args(["outdated", "--root-deps-only", "--color", "never"])
+            .output()
+        {
+            let output = String::from_utf8_lossy(&output.stdout).to_string()
+                + &String::from_utf8_lossy(&output.stderr);
+            return print_queue!(output.trim_end().to_string(), Color::White);
+        }
 
-        self.printer
-            .writer
-            .raw
-            .write_with_color("Waiting for debugger...", crossterm::style::Color::Magenta)?;
+        let locked = self.repl.cargo.locked_dependencies()?;
+        let mut failures = 0;
+        let outdated = crate::utils::outdated_dependencies(&locked, |krate| {
+            let latest = self.repl.cargo.latest_version(krate).ok();
+            failures += latest.is_none() as usize;
+            latest
+        });
+        if !locked.is_empty() && failures == locked.len() {
+            return print_queue!(
+                "Failed to query the registry (offline?), skipping".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        if outdated.is_empty() {
+            return print_queue!(
+                "All dependencies are up to date".into(),
+                self.theme.output_color(OutputKind::Info)
+            );
+        }
 
-        let expr_line_num = self.repl.lines_count();
+        let mut print_queue = PrintQueue::default();
+        for (krate, current, latest) in outdated {
+            print_queue.push(PrinterItem::String(format!("{krate} "), Color::White));
+            print_queue.push(PrinterItem::String(
+                current,
+                self.theme.output_color(OutputKind::Warning),
+            ));
+            print_queue.push(PrinterItem::Str(" -> ", Color::White));
+            print_queue.push(PrinterItem::String(
+                latest,
+                self.theme.output_color(OutputKind::Success),
+            ));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
+    }
 
-        let cargo = self.repl.cargo.clone();
-        self.repl.eval_in_tmp_repl(expression, |_| -> Result<()> {
-            let (status, _out) = cargo.cargo_build_output(true, false, self.options.toolchain)?;
-            if !status.success() {
-                return Err("Failed to execute expression".into());
-            }
+    fn search(&mut self, buffer: String) -> Result<PrintQueue> {
+        // exp: :search serde
+        const SEARCH_LIMIT: usize = 5;
+        let query = buffer
+            .strip_prefix(":search")
+            .expect("already checked")
+            .trim();
+        if query.is_empty() {
+            return Err("Usage: `:search <query>`".into());
+        }
+        if std::env::var("CARGO_NET_OFFLINE").is_ok_and(|v| v == "true") {
+            return Err("Offline mode, can't query crates.io".into());
+        }
+        self.printer.writer.raw.write_with_color(
+            "Searching crates.io...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+        let output = self.repl.cargo.search(query, SEARCH_LIMIT).map_err(|e| {
+            format!(
+                "Failed to query crates.io (offline?): {}",
+                e.to_string().trim()
+            )
+        })?;
 
-            let dbg_cmds_path = env::temp_dir().join("irust_dbg_cmds");
-            {
-                let mut dbg_cmds = std::fs::File::create(&dbg_cmds_path)?;
-                writeln!(&mut dbg_cmds, "b {expr_line_num}")?;
-                writeln!(&mut dbg_cmds, "r")?;
+        let results = crate::utils::search_results(&output);
+        if results.is_empty() {
+            return print_queue!(
+                format!("No crates match `{query}`"),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        let mut print_queue = PrintQueue::default();
+        for (name, version, description) in results {
+            print_queue.push(PrinterItem::String(format!("{name} "), Color::White));
+            print_queue.push(PrinterItem::String(
+                version,
+                self.theme.output_color(OutputKind::Value),
+            ));
+            if !description.is_empty() {
+                print_queue.push(PrinterItem::String(
+                    format!(" {description}"),
+                    self.theme.output_color(OutputKind::Info),
+                ));
             }
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
+    }
 
-            crossterm::terminal::disable_raw_mode()?;
-            println!();
-            crossterm::execute!(std::io::stdout(), crossterm::cursor::Show)?;
+    fn version_pin(&mut self) -> Result<PrintQueue> {
+        self.printer.writer.raw.write_with_color(
+            "Pinning dependencies...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+        self.repl.snapshot_manifest(":version-pin")?;
+        let pinned = self.repl.cargo.pin_dependencies()?;
+        if pinned.is_empty() {
+            return print_queue!(
+                "No dependencies to pin".into(),
+                self.theme.output_color(OutputKind::Info)
+            );
+        }
 
-            #[cfg(windows)]
-            std::process::Command::new("cmd")
-                .arg("/C")
-                .arg(debugger)
-                .args([debugger_arg, &dbg_cmds_path.display().to_string()])
-                .arg(&cargo.paths.exe_path)
-                .spawn()?
-                .wait()?;
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::String(
+            "Pinned:".into(),
+            self.theme.output_color(OutputKind::Success),
+        ));
+        for (krate, version) in pinned {
+            print_queue.add_new_line(1);
+            print_queue.push(PrinterItem::String(format!("  {krate} "), Color::White));
+            print_queue.push(PrinterItem::String(
+                format!("={version}"),
+                self.theme.output_color(OutputKind::Value),
+            ));
+        }
+        Ok(print_queue)
+    }
 
-            #[cfg(not(windows))]
-            {
-                std::process::Command::new(debugger)
-                    .args([debugger_arg, &dbg_cmds_path.display().to_string()])
-                    .arg(&cargo.paths.exe_path)
-                    .spawn()?
-                    .wait()?;
-            }
+    fn deps(&mut self) -> Result<PrintQueue> {
+        let cargo_toml = std::fs::read_to_string(&self.repl.cargo.paths.cargo_toml_file)?;
+        let dependencies = crate::utils::manifest_dependencies(&cargo_toml)?;
+        if dependencies.is_empty() {
+            return print_queue!(
+                "No dependencies".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
+        let mut print_queue = PrintQueue::default();
+        for (name, version) in dependencies {
+            print_queue.push(PrinterItem::String(
+                name,
+                self.theme.output_color(OutputKind::Value),
+            ));
+            print_queue.push(PrinterItem::Str(" ", Color::White));
+            print_queue.push(PrinterItem::String(
+                version,
+                self.theme.output_color(OutputKind::Value),
+            ));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
+    }
 
-            crossterm::terminal::enable_raw_mode()?;
-            Ok(())
-        })?;
+    fn undo_dep(&mut self) -> Result<PrintQueue> {
+        let Some(change) = self.repl.undo_dep()? else {
+            return print_queue!(
+                "No dependency change to undo".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        };
+        self.wait_add(self.repl.build()?, "Build")?;
+        if let Some(ra) = self.completer.as_mut() {
+            ra.rust_analyzer.reload_workspace()?;
+        }
+        print_queue!(
+            format!("Reverted `{change}`"),
+            self.theme.output_color(OutputKind::Success)
+        )
+    }
 
-        success!()
+    fn state_hash(&mut self) -> Result<PrintQueue> {
+        print_queue!(
+            format!("{:016x}", self.repl.state_hash()?),
+            self.theme.output_color(OutputKind::Value)
+        )
     }
-    fn shell_interpolate(&mut self, buffer: String) -> Result<String> {
-        // Replace shell expression with rust expression
-        // The shell expression is delimited by `$$` and `$$`
-        // There could be multiple shell expressions
-        let mut buffer = buffer.chars().peekable();
-        let mut res = String::new();
 
-        let mut maybe_error = None;
-        (|| -> Option<()> {
-            loop {
-                let c = buffer.next()?;
+    fn coverage(&mut self) -> Result<PrintQueue> {
+        if !crate::dependencies::dep_installed("cargo-llvm-cov") {
+            return Err(
+                "cargo-llvm-cov is required, install it with `cargo install cargo-llvm-cov`".into(),
+            );
+        }
+        self.printer.writer.raw.write_with_color(
+            "Running the tests with coverage...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+        let report = self
+            .repl
+            .with_lib(|| self.repl.cargo.cargo_llvm_cov(self.options.toolchain))??;
+        let lines = crate::utils::parse_lcov(&report, "src/lib.rs");
+        if lines.is_empty() {
+            return print_queue!(
+                "No instrumented code".into(),
+                self.theme.output_color(OutputKind::Warning)
+            );
+        }
 
-                if c == '$' && buffer.peek() == Some(&'$') {
-                    buffer.next();
-                    let mut shell_expr = String::new();
-                    while let Some(s) = buffer.next() {
-                        if s == '$' && buffer.peek() == Some(&'$') {
-                            buffer.next();
-                            break;
-                        }
-                        shell_expr.push(s);
-                    }
-                    let mut shell_expr = shell_expr.split_whitespace();
-                    let shell_res = if cfg!(windows) {
-                        process::Command::new("cmd")
-                            .arg("/C")
-                            .arg(shell_expr.next()?)
-                            .args(shell_expr.collect::<Vec<_>>())
-                            .output()
-                    } else {
-                        process::Command::new(shell_expr.next()?)
-                            .args(shell_expr.collect::<Vec<_>>())
-                            .output()
-                    };
-                    let shell_res = match shell_res {
-                        Ok(res) => stdout_and_stderr(res),
-                        Err(e) => {
-                            maybe_error = Some(e);
-                            break None;
-                        }
-                    };
+        let covered = lines.iter().filter(|(_, count)| *count > 0).count();
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::String(
+            format!(
+                "Coverage: {:.1}% ({covered}/{} lines)",
+                covered as f64 * 100. / lines.len() as f64,
+                lines.len()
+            ),
+            self.theme.output_color(OutputKind::Value),
+        ));
+        print_queue.add_new_line(1);
+        // the same line numbers as `:del`
+        let body: Vec<&String> = self.repl.lines().collect();
+        for (line, _) in lines.iter().filter(|(_, count)| *count == 0) {
+            let line = self.repl.lib_line_to_body_line(*line);
+            print_queue.push(PrinterItem::String(
+                format!("{line:>4}: "),
+                self.theme.output_color(OutputKind::Warning),
+            ));
+            print_queue.push(PrinterItem::String(
+                body.get(line).map(|l| l.to_string()).unwrap_or_default(),
+                Color::White,
+            ));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
+    }
 
-                    struct F<'a>(&'a [u8]);
-                    impl std::fmt::Display for F<'_> {
-                        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                            write!(f, "String::from_utf8_lossy(&[")?;
-                            for b in self.0.iter() {
-                                write!(f, "{b},")?;
-                            }
-                            write!(f, "]).to_string()")
-                        }
-                    }
-                    let shell_res = format!("{}", F(shell_res.as_bytes()));
-                    res.push_str(&shell_res);
-                } else {
-                    res.push(c);
-                }
-            }
-        })();
-        if let Some(e) = maybe_error {
-            return Err(e.into());
+    fn prof_startup(&mut self) -> Result<PrintQueue> {
+        let timings = &self.startup_timings;
+        let timings = crate::utils::format_startup_timings(&[
+            ("config load", Some(timings.config_load)),
+            ("project scaffold", Some(timings.scaffold)),
+            ("irust init", Some(timings.init)),
+            ("first cargo build", self.repl.warm_up_time()),
+        ]);
+        print_queue!(timings, self.theme.output_color(OutputKind::Value))
+    }
+
+    fn prof_build(&mut self) -> Result<PrintQueue> {
+        // Only the slowest crates are interesting
+        const TOP: usize = 10;
+
+        self.printer.writer.raw.write_with_color(
+            "Building from scratch with timings...",
+            self.theme.output_color(OutputKind::Info),
+        )?;
+        let report = self
+            .repl
+            .cargo
+            .cargo_build_timings(self.options.toolchain)?;
+        let timings = crate::utils::parse_build_timings(&report, &self.repl.cargo.name)?;
+        if timings.is_empty() {
+            return print_queue!(
+                "No dependencies to profile".into(),
+                self.theme.output_color(OutputKind::Info)
+            );
         }
 
-        Ok(res)
+        let mut print_queue = PrintQueue::default();
+        for (krate, duration) in timings.into_iter().take(TOP) {
+            print_queue.push(PrinterItem::String(
+                format!("{duration:>8.2}s "),
+                self.theme.output_color(OutputKind::Value),
+            ));
+            print_queue.push(PrinterItem::String(krate, Color::White));
+            print_queue.add_new_line(1);
+        }
+        Ok(print_queue)
     }
 
-    fn expand(&mut self, buffer: String) -> Result<PrintQueue> {
-        let fnn = buffer
-            .strip_prefix(":expand")
-            .expect("already checked")
-            .trim();
-        if !fnn.is_empty() {
-            let r = self.repl.with_lib(|| {
-                self.repl
-                    .cargo
-                    .cargo_expand(Some(fnn), self.options.toolchain)
-            })??;
-            print_queue!(r, Color::White)
-        } else {
-            print_queue!(
-                self.repl.cargo.cargo_expand(None, self.options.toolchain)?,
-                Color::White
-            )
+    fn check_unsafe(&mut self) -> Result<PrintQueue> {
+        let occurrences = crate::utils::find_unsafe(&self.repl.body());
+        if occurrences.is_empty() {
+            return print_queue!(
+                "No unsafe code found".into(),
+                self.theme.output_color(OutputKind::Info)
+            );
+        }
+
+        let mut print_queue = PrintQueue::default();
+        print_queue.push(PrinterItem::String(
+            format!("Found {} unsafe usage(s):", occurrences.len()),
+            self.theme.output_color(OutputKind::Warning),
+        ));
+        for (line_num, line) in occurrences {
+            print_queue.add_new_line(1);
+            print_queue.push(PrinterItem::String(
+                format!("  line {line_num}: "),
+                self.theme.output_color(OutputKind::Info),
+            ));
+            print_queue.append(&mut highlight(&line.into(), &self.theme));
         }
+        print_queue.add_new_line(1);
+        Ok(print_queue)
     }
 
     fn exit(&mut self) -> Result<PrintQueue> {
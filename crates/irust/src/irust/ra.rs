@@ -134,7 +134,7 @@ impl Completer {
                 .get(self.suggestion_idx - 1)
                 .map(ToOwned::to_owned)
         } else {
-            self.suggestions.get(0).map(ToOwned::to_owned)
+            self.suggestions.first().map(ToOwned::to_owned)
         }
     }
 
@@ -194,7 +194,6 @@ impl Completer {
             let buf_ref = &buffer;
             repl.eval_in_tmp_repl(buffer.clone(), move |repl| -> Result<()> {
                 ra.complete_code_ra(&repl.cargo.paths.main_file, repl.body(), buf_ref)
-                    .map_err(From::from)
             })?;
         }
 
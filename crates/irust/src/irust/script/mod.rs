@@ -37,8 +37,13 @@ impl super::IRust {
         if let Some(ref mut script_mg) = self.script_mg {
             if let Some(prompt) = script_mg.input_prompt(&self.global_variables) {
                 self.printer.set_prompt(prompt);
+                return;
             }
         }
+        if let Some(prompt) = self.templated_prompt() {
+            self.global_variables.prompt_len = prompt.chars().count();
+            self.printer.set_prompt(prompt);
+        }
     }
     pub fn get_output_prompt(&mut self) -> String {
         if let Some(ref mut script_mg) = self.script_mg {
@@ -107,7 +112,6 @@ impl super::IRust {
 
     // internal
     ///////////
-    ///
     pub fn choose_script_mg(options: &Options) -> Option<Box<dyn Script>> {
         if options.activate_scripting {
             ScriptManager::new().map(|script_mg| Box::new(script_mg) as Box<dyn Script>)
@@ -0,0 +1,133 @@
+// `irust --format json`: evaluate the stdin lines and emit a JSON record for each one (JSONL)
+// This is the non interactive mode of IRust, meant to be used by other programs
+use crate::eval::{prepare, Input};
+use crate::irust::{options::Options, Result};
+use crate::probe::{marked_evaluator, split_marked_output};
+use crate::utils::compile_errors;
+use irust_repl::{CompileMode, EvalConfig, Repl};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+/// Marks the start of the type and the value of the evaluated expression
+/// Anything printed before it is the stdout of the expression
+const VALUE_MARK: &str = "IRUST_JSON_VALUE";
+
+pub fn run(options: &Options) -> Result<()> {
+    let mut repl = crate::eval::configured_repl(options)?;
+    let mut stdout = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = eval_record(&mut repl, &line, options)
+            .unwrap_or_else(|e| message_record(&e.to_string()));
+        writeln!(stdout, "{record}")?;
+        stdout.flush()?;
+    }
+    repl.cargo.delete_project()?;
+    Ok(())
+}
+
+fn eval_record(repl: &mut Repl, input: &str, options: &Options) -> Result<Value> {
+    let input = match prepare(input.to_string(), options, repl)? {
+        // comments are kept like in the interactive session
+        None | Some(Input::Comment { doc: true, .. }) => return Ok(insert_record()),
+        Some(Input::Comment { code, .. }) => {
+            repl.insert(code);
+            return Ok(insert_record());
+        }
+        Some(Input::Statement(statement)) => {
+            let result = repl.eval_check(statement.clone())?;
+            if !result.status.success() {
+                return Ok(error_record(&result.output));
+            }
+            repl.insert(statement);
+            return Ok(insert_record());
+        }
+        Some(Input::Expression { code, .. }) => code,
+    };
+
+    let result = repl.eval_with_configuration(EvalConfig {
+        input: &input,
+        interactive_function: None,
+        color: false,
+        evaluator: &value_evaluator(),
+        compile_mode: CompileMode::Debug,
+    })?;
+    if !result.status.success() {
+        return Ok(error_record(&result.output));
+    }
+    Ok(value_record(&result.output))
+}
+
+/// Prints the type and the value of the expression after its stdout, parsed by `value_record`
+pub fn value_evaluator() -> [String; 2] {
+    marked_evaluator(VALUE_MARK, "{:?}")
+}
+
+/// The record of a successful build with `value_evaluator`
+pub fn value_record(output: &str) -> Value {
+    // The status is the build status, if the value is missing the evaluation failed at runtime (exp: a panic)
+    let Some((stdout, typed_value)) = split_marked_output(output, VALUE_MARK) else {
+        return error_record(output);
+    };
+    let (ty, value) = typed_value.split_once('\n').unwrap_or((typed_value, ""));
+    json!({
+        "kind": "value",
+        "type": ty,
+        "value": value,
+        "stdout": stdout,
+    })
+}
+
+pub fn insert_record() -> Value {
+    json!({"kind": "insert"})
+}
+
+/// The record of an IRust error (exp: an invalid command)
+pub fn message_record(message: &str) -> Value {
+    json!({"kind": "error", "message": message})
+}
+
+/// The record of a failed build or evaluation
+pub fn error_record(output: &str) -> Value {
+    message_record(&compile_errors(output))
+}
+
+#[test]
+fn eval_record_test() {
+    let options = Options::default();
+    let mut repl = Repl::default();
+    assert_eq!(
+        eval_record(&mut repl, "fn double(a: u8) -> u8 { a * 2 }", &options).unwrap(),
+        json!({"kind": "insert"})
+    );
+    assert_eq!(
+        eval_record(&mut repl, "{ println!(\"hello\"); double(2) }", &options).unwrap(),
+        json!({"kind": "value", "type": "u8", "value": "4", "stdout": "hello\n"})
+    );
+
+    let error = eval_record(&mut repl, "double(\"a\")", &options).unwrap();
+    assert_eq!(error["kind"], "error");
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("error[E0308]: mismatched types"));
+
+    let panic = eval_record(&mut repl, "double(200)", &options).unwrap();
+    assert_eq!(panic["kind"], "error");
+    assert!(panic["message"]
+        .as_str()
+        .unwrap()
+        .contains("attempt to multiply with overflow"));
+
+    // the `?` of an expression is handled like in the interactive session
+    assert_eq!(
+        eval_record(&mut repl, "\"4\".parse::<u8>()?", &options).unwrap(),
+        json!({"kind": "value", "type": "u8", "value": "4", "stdout": ""})
+    );
+
+    // every record is one line
+    assert!(!error.to_string().contains('\n'));
+}
@@ -1,6 +1,10 @@
 mod args;
+mod batch;
 mod dependencies;
+mod eval;
 mod irust;
+mod json_format;
+mod probe;
 mod utils;
 use crate::irust::IRust;
 use crate::{
@@ -13,7 +17,9 @@ use irust_repl::CompileMode;
 use std::process::exit;
 
 fn main() {
+    let now = std::time::Instant::now();
     let mut options = Options::new().unwrap_or_default();
+    let config_load = now.elapsed();
 
     // Handle args
     let args: Vec<String> = std::env::args().skip(1).collect();
@@ -28,6 +34,25 @@ fn main() {
         exit(0)
     }
 
+    if matches!(args_result, ArgsResult::JsonFormat) {
+        if let Err(e) = json_format::run(&options) {
+            eprintln!("IRust exited with error: {e}");
+            exit(1)
+        }
+        exit(0)
+    }
+
+    if let ArgsResult::Run(path) = &args_result {
+        match batch::run(&options, path) {
+            Ok(true) => exit(0),
+            Ok(false) => exit(1),
+            Err(e) => {
+                eprintln!("IRust exited with error: {e}");
+                exit(1)
+            }
+        }
+    }
+
     // If no argument are provided, check stdin for some oneshot usage
     if args.is_empty() {
         let mut stdin = std::io::stdin();
@@ -86,6 +111,7 @@ fn main() {
         }
         IRust::new(options)
     };
+    irust.set_config_load_time(config_load);
 
     // If a script path was provided try to load it
     if let ArgsResult::ProceedWithScriptPath(script) = args_result {
@@ -94,11 +120,7 @@ fn main() {
     }
 
     // Start IRust
-    let err = if let Err(e) = irust.run() {
-        Some(e)
-    } else {
-        None
-    };
+    let err = irust.run().err();
 
     // Now IRust has been dropped we can safely print to stderr
     if let Some(err) = err {
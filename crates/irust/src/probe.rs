@@ -0,0 +1,1118 @@
+// The probes: the code generated around an input so the compiler or the evaluation reports
+// something about it (exp: its type, its run time), and the parsing of what they report
+use crate::irust::Result;
+use crate::utils::{let_binding_name, strip_paths};
+#[cfg(test)]
+use irust_repl::{CompileMode, EvalConfig, EvalResult, Repl};
+
+/// `input` evaluated with `evaluator` in `repl`, without colors
+#[cfg(test)]
+pub fn eval_with(
+    repl: &mut Repl,
+    input: impl ToString,
+    evaluator: &[String],
+    compile_mode: CompileMode,
+) -> EvalResult {
+    repl.eval_with_configuration(EvalConfig {
+        input,
+        interactive_function: None,
+        color: false,
+        evaluator,
+        compile_mode,
+    })
+    .unwrap()
+}
+
+/// The build output of `code` in a temporary repl, for the probes reported by the compiler
+#[cfg(test)]
+fn build_output(repl: &mut Repl, code: String) -> String {
+    let toolchain = repl.toolchain();
+    let cargo = repl.cargo.clone();
+    repl.eval_in_tmp_repl(code, |_| -> Result<_> {
+        Ok(cargo.cargo_build_output(false, false, toolchain)?)
+    })
+    .unwrap()
+    .1
+}
+
+/// Expression that evaluates to an array of bools, one for each trait, telling if `ty` implements it
+///
+/// This relies on inherent associated items having priority over trait ones, the inherent `IMPL_N`
+/// only exists if `ty` satisfies the bound, otherwise the `false` default of `NotImpl` is used
+pub fn trait_impls_probe(ty: &str, traits: &[String]) -> String {
+    let mut probe = String::from(
+        "{\nstruct IRustProbe<T: ?Sized>(std::marker::PhantomData<T>);\ntrait NotImpl {\n",
+    );
+    for idx in 0..traits.len() {
+        probe += &format!("const IMPL_{idx}: bool = false;\n");
+    }
+    probe += "}\nimpl<T: ?Sized> NotImpl for IRustProbe<T> {}\n";
+    for (idx, tr) in traits.iter().enumerate() {
+        probe += &format!(
+            "#[allow(dead_code)] impl<T: ?Sized + {tr}> IRustProbe<T> {{ const IMPL_{idx}: bool = true; }}\n"
+        );
+    }
+    let checks: Vec<String> = (0..traits.len())
+        .map(|idx| format!("IRustProbe::<{ty}>::IMPL_{idx}"))
+        .collect();
+    probe + &format!("[{}]\n}}", checks.join(", "))
+}
+
+#[test]
+fn trait_impls_probe_test() {
+    let traits: Vec<String> = ["std::fmt::Debug", "Clone", "Copy"]
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect();
+    let mut repl = Repl::default();
+    repl.insert("#[derive(Debug, Clone)] struct Foo(String);");
+    let output = repl.eval(trait_impls_probe("Foo", &traits)).unwrap().output;
+    assert_eq!(output, "[true, true, false]");
+}
+
+/// Methods checked by `:explain-type`, rustc can't enumerate the methods of a type
+pub const EXPLAIN_TYPE_METHODS: &[&str] = &[
+    "len",
+    "is_empty",
+    "capacity",
+    "get",
+    "get_mut",
+    "first",
+    "last",
+    "iter",
+    "iter_mut",
+    "into_iter",
+    "keys",
+    "values",
+    "entry",
+    "contains",
+    "contains_key",
+    "insert",
+    "remove",
+    "push",
+    "pop",
+    "extend",
+    "clear",
+    "sort",
+    "as_ref",
+    "as_mut",
+    "as_str",
+    "as_slice",
+    "as_bytes",
+    "chars",
+    "lines",
+    "split",
+    "trim",
+    "map",
+    "unwrap",
+    "clone",
+    "to_string",
+    "to_owned",
+];
+
+/// Code that makes rustc report the fields of `ty` and which of `methods` it has
+///
+/// A struct pattern without fields lists the missing (accessible) fields, and taking the value of
+/// a method is a different error than accessing a missing field
+pub fn explain_type_probe(ty: &str, methods: &[&str]) -> String {
+    let mut probe = format!(
+        "{{\ntype IRustProbe = {ty};\nlet _ = |v: IRustProbe| {{\nlet IRustProbe {{}} = v;\n"
+    );
+    for method in methods {
+        probe += &format!("let _ = v.{method};\n");
+    }
+    probe + "};\n}"
+}
+
+/// Parse the (uncolored) build output of `explain_type_probe` into the fields and the methods of the type
+/// Returns `None` if the probe failed for another reason (exp: unknown type)
+pub fn parse_explain_type(output: &str, methods: &[&str]) -> Option<(Vec<String>, Vec<String>)> {
+    let mut fields = vec![];
+    let mut missing = vec![];
+    for error in output.lines().filter_map(|line| line.strip_prefix("error")) {
+        if let Some(error) = error.strip_prefix("[E0027]: pattern does not mention ") {
+            fields = error
+                .split('`')
+                .skip(1)
+                .step_by(2)
+                .map(ToOwned::to_owned)
+                .collect();
+        } else if let Some(error) = error.strip_prefix("[E0609]: no field `") {
+            missing.push(error.split('`').next()?);
+        } else if let Some(error) = error.strip_prefix("[E0616]: field `") {
+            // a private field, rustc mentions it if there is a method with the same name
+            let name = error.split('`').next()?;
+            if !output.contains(&format!("a method `{name}` also exists")) {
+                missing.push(name);
+            }
+        } else if !(error.starts_with("[E0071]") // not a struct
+            || error.starts_with("[E0615]") // a method
+            || error.starts_with(": pattern requires `..` due to inaccessible fields")
+            || error.starts_with(": aborting due to")
+            || error.starts_with(": could not compile"))
+        {
+            return None;
+        }
+    }
+    let methods = methods
+        .iter()
+        .filter(|method| !missing.contains(method) && !fields.iter().any(|f| f == *method))
+        .map(ToString::to_string)
+        .collect();
+    Some((fields, methods))
+}
+
+/// Expression that evaluates to an array of the type names of the `fields` of `ty`
+pub fn field_types_probe(ty: &str, fields: &[String]) -> String {
+    let types: Vec<String> = fields
+        .iter()
+        .map(|field| format!("field_type(|v| &v.{field})"))
+        .collect();
+    format!(
+        "{{\ntype IRustProbe = {ty};\nfn field_type<'a, F: ?Sized>(_: fn(&'a IRustProbe) -> &'a F) -> &'static str {{ std::any::type_name::<F>() }}\n[{}]\n}}",
+        types.join(", ")
+    )
+}
+
+#[test]
+fn explain_type_test() {
+    let methods = ["len", "iter", "frobnicate"];
+    let mut repl = Repl::default();
+    repl.insert("struct Point { x: i32, name: String }");
+    repl.insert("impl Point { fn len(&self) -> usize { 1 } }");
+    let output = build_output(&mut repl, explain_type_probe("Point", &methods));
+    let (fields, methods) = parse_explain_type(&output, &methods).unwrap();
+    assert_eq!(fields, ["x", "name"]);
+    assert_eq!(methods, ["len"]);
+
+    let output = repl
+        .eval(field_types_probe("Point", &fields))
+        .unwrap()
+        .output;
+    assert_eq!(output, r#"["i32", "alloc::string::String"]"#);
+
+    let output = build_output(&mut repl, explain_type_probe("NoSuchType", &[]));
+    assert!(parse_explain_type(&output, &[]).is_none());
+}
+
+/// The evaluator of the probes that report the value: after the stdout of the input it prints `mark` on its own line,
+/// the type of the value and the value formatted with `fmt` (exp: `{:?}`), the output is split by `split_marked_output`
+pub fn marked_evaluator(mark: &str, fmt: &str) -> [String; 2] {
+    [
+        "let irust_value = {\n".to_string(),
+        format!(
+            "\n}};\nprintln!(\"\\n{mark}\\n{{}}\\n{fmt}\", std::any::type_name_of_val(&irust_value), irust_value);"
+        ),
+    ]
+}
+
+/// Split the output of a probe that prints `\n{mark}\n` before its report into (the output before the mark, the report)
+/// The last mark is used, None if it's missing, exp: the evaluation panicked before printing it
+pub fn split_marked_output<'a>(output: &'a str, mark: &str) -> Option<(&'a str, &'a str)> {
+    let (before, after) = output.rsplit_once(&format!("\n{mark}"))?;
+    match after.strip_prefix('\n') {
+        Some(report) => Some((before, report)),
+        // the trailing newline of the output is trimmed when the report is empty
+        None => after.is_empty().then_some((before, after)),
+    }
+}
+
+#[test]
+fn split_marked_output_test() {
+    assert_eq!(
+        split_marked_output("hi\nIRUST_X\n\nIRUST_X\nreport", "IRUST_X"),
+        Some(("hi\nIRUST_X\n", "report"))
+    );
+    assert_eq!(
+        split_marked_output("hi\nIRUST_X", "IRUST_X"),
+        Some(("hi", ""))
+    );
+    assert_eq!(split_marked_output("hi\nIRUST_XY\n", "IRUST_X"), None);
+    assert_eq!(split_marked_output("hi", "IRUST_X"), None);
+}
+
+/// Marks the run time printed by `timed_code`
+const TIME_MARK: &str = "IRUST_TIME";
+
+/// Runs the expression and prints its value (if it implements `Debug`) then how long it took
+/// Only the expression is timed, not printing its value
+pub fn timed_code(expression: &str) -> String {
+    format!(
+        r#"struct IRustTimed<T>(T);
+trait IRustShowDebug {{ fn irust_show(&self) -> Option<String>; }}
+impl<T: std::fmt::Debug> IRustShowDebug for &IRustTimed<&T> {{ fn irust_show(&self) -> Option<String> {{ Some(format!("{{:?}}", self.0)) }} }}
+trait IRustShowNone {{ fn irust_show(&self) -> Option<String>; }}
+impl<T> IRustShowNone for IRustTimed<&T> {{ fn irust_show(&self) -> Option<String> {{ None }} }}
+let irust_now = std::time::Instant::now();
+let irust_value = {{
+{expression}
+}};
+let irust_elapsed = irust_now.elapsed();
+if let Some(irust_value) = (&&IRustTimed(&irust_value)).irust_show() {{
+    println!("{{irust_value}}");
+}}
+println!("\n{TIME_MARK}\n{{irust_elapsed:?}}");"#
+    )
+}
+
+/// Split the output of `timed_code` into (the output with the value, the run time)
+/// None if the time is missing, exp: the expression panicked
+pub fn parse_timed_output(output: &str) -> Option<(&str, &str)> {
+    let (output, time) = split_marked_output(output, TIME_MARK)?;
+    Some((output.strip_suffix('\n').unwrap_or(output), time.trim()))
+}
+
+#[test]
+fn timed_code_test() {
+    let mut repl = Repl::default();
+    repl.insert("struct NoDebug;");
+    let mut run = |expression: &str| {
+        let cargo = repl.cargo.clone();
+        repl.eval_in_tmp_repl(timed_code(expression), |_| {
+            cargo.cargo_run(false, false, irust_repl::ToolChain::Default, None)
+        })
+        .unwrap()
+        .1
+    };
+
+    let output = run("(1..=10).sum::<i32>()");
+    let (value, time) = parse_timed_output(&output).unwrap();
+    assert_eq!(value, "55");
+    assert!(time.ends_with('s'), "{time}");
+
+    let output = run("{ println!(\"hi\"); NoDebug }");
+    assert_eq!(parse_timed_output(&output).unwrap().0, "hi");
+    let output = run("std::thread::sleep(std::time::Duration::from_millis(5))");
+    assert_eq!(parse_timed_output(&output).unwrap().0, "()");
+
+    assert_eq!(parse_timed_output(&run("panic!(\"oops\")")), None);
+}
+
+/// The code compiled by `:type`, the compiler reports the type of the expression as a mismatch with `()`
+pub fn type_probe(expression: &str) -> String {
+    // `{ x; }` would be `()`, the new lines keep a trailing comment from commenting out the block end
+    format!(
+        "let _: () = {{\n{}\n}};",
+        expression.trim().trim_end_matches(';')
+    )
+}
+
+/// The code compiled by `:type let x = ..;`: the binding followed by the `type_probe` of its name
+/// Only a single name is supported, not a pattern (exp: `let (a, b) = ..;`)
+pub fn let_type_probe(statement: &str) -> Result<String> {
+    let statement = statement.trim().trim_end_matches(';');
+    let name = let_binding_name(statement).ok_or(
+        "Only the bindings of a single name are supported (exp: `:type let x = foo();`), not patterns",
+    )?;
+    Ok(format!("{statement};\n{}", type_probe(name)))
+}
+
+#[test]
+fn let_type_probe_test() {
+    assert_eq!(
+        let_type_probe("let mut x = foo();").unwrap(),
+        "let mut x = foo();\nlet _: () = {\nx\n};"
+    );
+    assert!(let_type_probe("let (a, b) = (1, 2);").is_err());
+}
+
+/// The type found by `type_probe` in the compiler output
+/// exp: "expected `()`, found `HashMap<String, Vec<(i32, u8)>>`" => `HashMap<String, Vec<(i32, u8)>>`
+pub fn parse_found_type(output: &str) -> Option<String> {
+    const FOUND: &str = "expected `()`, found ";
+    let start = output.find(FOUND)?;
+    // Only the probe error, the other diagnostics could mention other types
+    let error = &output[start + FOUND.len()..];
+    let error = error
+        .find("\nerror")
+        .or_else(|| error.find("\nwarning"))
+        .map_or(error, |end| &error[..end]);
+    let label = error.lines().next().unwrap_or_default().trim();
+    // a truncated label could have only one backtick
+    let backticked = |s: &str| Some(s.get(s.find('`')? + 1..s.rfind('`')?)?.to_string());
+
+    if label.starts_with('`') {
+        return backticked(label);
+    }
+    match label {
+        "integer" => return Some("{integer}".into()),
+        "floating-point number" => return Some("{float}".into()),
+        _ => (),
+    }
+    // exp: "found struct `Foo`" in the notes
+    error
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("found "))
+        .find(|found| !found.contains("{closure@"))
+        .and_then(backticked)
+        .or_else(|| Some(label.to_string()))
+}
+
+#[test]
+fn parse_found_type_test() {
+    assert_eq!(type_probe("a + b;\n"), "let _: () = {\na + b\n};");
+
+    let output = "\
+warning: unused variable: `x`
+ --> src/main.rs:2:5
+  |
+  = note: expected `()`, found `u8` in an unrelated warning
+
+error[E0308]: mismatched types
+ --> src/main.rs:3:13
+  |
+3 | let _: () = {
+  |        --   ^ expected `()`, found `HashMap<String, Vec<(i32, u8)>>`
+  |
+  = note: expected unit type `()`
+                found struct `HashMap<String, Vec<(i32, u8)>>`
+
+error: could not compile `irust_host_repl` due to previous error";
+    // the first found message is in the warning
+    assert_eq!(parse_found_type(output).as_deref(), Some("u8"));
+    assert_eq!(
+        parse_found_type(output.split_once("\n\n").unwrap().1).as_deref(),
+        Some("HashMap<String, Vec<(i32, u8)>>")
+    );
+    assert_eq!(
+        parse_found_type("^^^^^ expected `()`, found integer").as_deref(),
+        Some("{integer}")
+    );
+    assert_eq!(
+        parse_found_type("^ expected `()`, found closure\n  = note: expected unit type `()`\n  found closure `{closure@src/main.rs:7:13: 7:20}`").as_deref(),
+        Some("closure")
+    );
+    assert_eq!(parse_found_type("error: could not compile"), None);
+    // the references and the lifetimes are kept verbatim
+    assert_eq!(
+        parse_found_type("^ expected `()`, found `&'static str`").as_deref(),
+        Some("&'static str")
+    );
+    assert_eq!(
+        parse_found_type("^ expected `()`, found `&mut Vec<i32>`").as_deref(),
+        Some("&mut Vec<i32>")
+    );
+    assert_eq!(parse_found_type("^ expected `()`, found `&"), None);
+
+    // with the real compiler
+    let mut repl = Repl::default();
+    let mut found_type =
+        |expression: &str| parse_found_type(&build_output(&mut repl, type_probe(expression)));
+    assert_eq!(found_type("1 + 2").as_deref(), Some("{integer}"));
+    assert_eq!(
+        found_type("vec![1,2,3].iter().sum::<i32>()").as_deref(),
+        Some("i32")
+    );
+    assert_eq!(
+        found_type("std::collections::HashMap::<String, Vec<(i32, u8)>>::new()").as_deref(),
+        Some("HashMap<String, Vec<(i32, u8)>>")
+    );
+    assert_eq!(
+        found_type("Some(vec![1u8]) // a comment").as_deref(),
+        Some("Option<Vec<u8>>")
+    );
+    assert_eq!(
+        found_type("&String::from(\"a\")").as_deref(),
+        Some("&String")
+    );
+    assert_eq!(
+        found_type("&mut vec![1i32]").as_deref(),
+        Some("&mut Vec<i32>")
+    );
+    assert_eq!(found_type("()"), None);
+}
+
+/// Marks the type printed by `full_type_probe`
+const FULL_TYPE_MARK: &str = "IRUST_FULL_TYPE";
+
+/// The code evaluated by `:type --full`, it prints the fully qualified type of the expression
+/// The expression is in a closure that is never called, so it has no side effects
+pub fn full_type_probe(expression: &str) -> String {
+    format!(
+        "{{
+fn irust_type_name<T>(_: impl FnOnce() -> T) -> &'static str {{
+    std::any::type_name::<T>()
+}}
+println!(\"\\n{FULL_TYPE_MARK}\\n{{}}\", irust_type_name(|| {{
+{}
+}}));
+}}",
+        expression.trim().trim_end_matches(';')
+    )
+}
+
+/// The type printed by `full_type_probe`, with the `core` and `alloc` paths shown as their `std` re-exports
+/// exp: `alloc::vec::Vec<alloc::string::String>` => `std::vec::Vec<std::string::String>`
+pub fn parse_full_type(output: &str) -> Option<String> {
+    let ty = split_marked_output(output, FULL_TYPE_MARK)?.1.trim();
+    let mut full = String::with_capacity(ty.len());
+    let mut rest = ty;
+    while !rest.is_empty() {
+        let at_boundary = !full.ends_with(|c: char| c.is_alphanumeric() || c == '_');
+        match ["core::", "alloc::"]
+            .into_iter()
+            .find(|krate| at_boundary && rest.starts_with(krate))
+        {
+            Some(krate) => {
+                full.push_str("std::");
+                rest = &rest[krate.len()..];
+            }
+            None => {
+                let c = rest.chars().next().expect("not empty");
+                full.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    Some(full)
+}
+
+#[test]
+fn full_type_test() {
+    assert_eq!(
+        parse_full_type("\nIRUST_FULL_TYPE\nalloc::vec::Vec<core::option::Option<mycore::A>>")
+            .as_deref(),
+        Some("std::vec::Vec<std::option::Option<mycore::A>>")
+    );
+    assert_eq!(parse_full_type("error"), None);
+
+    // with the real compiler
+    let mut repl = Repl::default();
+    repl.insert("let s = String::new();");
+    let mut full_type = |expression: &str| {
+        let output = eval_with(
+            &mut repl,
+            full_type_probe(expression),
+            &crate::utils::unit_macro_evaluator(),
+            CompileMode::Debug,
+        )
+        .output;
+        parse_full_type(&output)
+    };
+    assert_eq!(full_type("s").as_deref(), Some("std::string::String"));
+    assert_eq!(
+        full_type("std::collections::HashMap::<u8, Vec<&str>>::new();").as_deref(),
+        Some("std::collections::hash::map::HashMap<u8, std::vec::Vec<&str>>")
+    );
+    // the expression is not evaluated
+    assert_eq!(
+        full_type("{ panic!(\"no\"); #[allow(unreachable_code)] 1u8 }").as_deref(),
+        Some("u8")
+    );
+}
+
+/// Marks the start of the type and the pretty value printed by `inspect_evaluator`
+const INSPECT_MARK: &str = "IRUST_INSPECT_VALUE";
+
+/// Evaluates the input and prints its type followed by its pretty debug value, used by `:inspect`
+pub fn inspect_evaluator() -> [String; 2] {
+    marked_evaluator(INSPECT_MARK, "{:#?}")
+}
+
+/// Split the output of `inspect_evaluator` into (stdout, type without paths, pretty value)
+/// None if the value is missing, exp: the evaluation panicked
+pub fn parse_inspect_output(output: &str) -> Option<(&str, String, &str)> {
+    let (stdout, typed_value) = split_marked_output(output, INSPECT_MARK)?;
+    let (ty, value) = typed_value.split_once('\n').unwrap_or((typed_value, ""));
+    Some((stdout, strip_paths(ty), value))
+}
+
+#[test]
+fn inspect_test() {
+    let mut repl = Repl::default();
+    repl.insert("#[derive(Debug)] struct Point { x: i32, y: i32 }");
+    repl.insert("#[derive(Debug)] struct Line { from: Point, to: Point }");
+    let result = eval_with(
+        &mut repl,
+        "{ println!(\"hi\"); Line { from: Point { x: 0, y: 1 }, to: Point { x: 2, y: 3 } } }",
+        &inspect_evaluator(),
+        CompileMode::Debug,
+    );
+    let (stdout, ty, value) = parse_inspect_output(&result.output).unwrap();
+    assert_eq!(stdout, "hi\n");
+    assert_eq!(ty, "Line");
+    assert_eq!(
+        value,
+        "Line {\n    from: Point {\n        x: 0,\n        y: 1,\n    },\n    to: Point {\n        x: 2,\n        y: 3,\n    },\n}"
+    );
+
+    assert!(parse_inspect_output("thread 'main' panicked").is_none());
+}
+
+/// Marks the type of the evaluated value printed by `typed_input`
+const EVAL_TYPE_MARK: &str = "IRUST_EVAL_TYPE";
+
+/// The input of an evaluation that also prints the type of its value (before the evaluator prints it), used by `:show-types on`
+/// The input is wrapped instead of the evaluator so it works with any evaluator
+pub fn typed_input(input: &str) -> String {
+    format!(
+        "{{
+fn irust_typed<T>(value: T) -> T {{
+    println!(\"\\n{EVAL_TYPE_MARK}\\n{{}}\", std::any::type_name::<T>());
+    value
+}}
+irust_typed({{
+{input}
+}})
+}}"
+    )
+}
+
+/// Split the output of a `typed_input` evaluation into the output without the type and the type (without paths)
+/// The type is None if it's missing, exp: the evaluation panicked
+pub fn split_eval_type(output: &str) -> (String, Option<String>) {
+    let Some((stdout, typed_value)) = split_marked_output(output, EVAL_TYPE_MARK) else {
+        return (output.to_string(), None);
+    };
+    let (ty, value) = typed_value.split_once('\n').unwrap_or((typed_value, ""));
+    (format!("{stdout}{value}"), Some(strip_paths(ty)))
+}
+
+/// The output followed by the type of its value, exp: `4: i32`, `()` is not shown so it doesn't get a type
+pub fn with_eval_type(output: &str, ty: &str) -> String {
+    let value = output.trim_end();
+    if value == "()" || value.is_empty() {
+        return output.to_string();
+    }
+    format!("{value}: {ty}{}", &output[value.len()..])
+}
+
+#[test]
+fn with_eval_type_test() {
+    let mut repl = Repl::default();
+    let eval = |repl: &mut irust_repl::Repl, input: &str| {
+        let (output, ty) = split_eval_type(&repl.eval(typed_input(input)).unwrap().output);
+        with_eval_type(&output, &ty.unwrap())
+    };
+    assert_eq!(eval(&mut repl, "1 + 2"), "3: i32");
+    assert_eq!(
+        eval(&mut repl, "{ println!(\"hi\"); vec![String::new()] }"),
+        "hi\n[\"\"]: Vec<String>"
+    );
+    assert_eq!(eval(&mut repl, "\"a\""), "\"a\": &str");
+    assert_eq!(eval(&mut repl, "()"), "()");
+    assert_eq!(
+        split_eval_type("thread 'main' panicked"),
+        ("thread 'main' panicked".into(), None)
+    );
+}
+
+/// Marks the message of a panic caught by `panic_catching_input`
+const PANIC_MARK: &str = "IRUST_PANIC";
+
+/// Marks the end of the stdout of a `capturing_input` evaluation
+const CAPTURE_MARK: &str = "IRUST_CAPTURE_END";
+/// The most lines of a captured stdout that are shown, the other lines are dropped and only counted
+pub const CAPTURE_MAX_LINES: usize = 500;
+
+/// The input of an evaluation that marks the end of its stdout, so it's shown apart from the value, used by `:capture on`
+/// The stdout isn't redirected: the whole output is read when the evaluation ends, like any evaluation,
+/// then `split_captured_stdout` splits what the expression printed from its value at the mark
+pub fn capturing_input(input: &str) -> String {
+    format!(
+        "{{
+let irust_captured = {{
+{input}
+}};
+println!(\"\\n{CAPTURE_MARK}\");
+irust_captured
+}}"
+    )
+}
+
+/// Split the output of a `capturing_input` evaluation into its stdout and the rest (the value),
+/// None if the mark is missing (exp: it panicked)
+pub fn split_captured_stdout(output: &str) -> Option<(String, String)> {
+    let (stdout, rest) = split_marked_output(output, CAPTURE_MARK)?;
+    Some((stdout.to_string(), rest.to_string()))
+}
+
+/// The first `max` lines of `text` and the number of the other lines
+pub fn head_lines(text: &str, max: usize) -> (String, usize) {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() <= max {
+        return (text.to_string(), 0);
+    }
+    (lines[..max].join("\n"), lines.len() - max)
+}
+
+#[test]
+fn capturing_input_test() {
+    let mut repl = Repl::default();
+    let mut eval =
+        |input: &str| split_captured_stdout(&repl.eval(capturing_input(input)).unwrap().output);
+    assert_eq!(
+        eval("{ println!(\"hi\"); print!(\"there\"); 1 + 2 }"),
+        Some(("hi\nthere".into(), "3".into()))
+    );
+    assert_eq!(eval("1"), Some(("".into(), "1".into())));
+    assert_eq!(eval("panic!()"), None);
+
+    assert_eq!(head_lines("a\nb\nc", 2), ("a\nb".into(), 1));
+    assert_eq!(head_lines("a\nb", 2), ("a\nb".into(), 0));
+}
+
+/// The input of an evaluation that catches its panic with `catch_unwind`, used by `:panic-catch on`
+/// The panic message is printed on stdout after `PANIC_MARK` instead of the default panic report,
+/// `AssertUnwindSafe` lets any expression be caught since the process exits right after the panic
+pub fn panic_catching_input(input: &str) -> String {
+    format!(
+        "{{
+std::panic::set_hook(Box::new(|info| {{
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| \"Box<dyn Any>\".to_string());
+    println!(\"\\n{PANIC_MARK}\\n{{message}}\");
+}}));
+match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {{
+{input}
+}})) {{
+    Ok(irust_value) => irust_value,
+    Err(_) => std::process::exit(101),
+}}
+}}"
+    )
+}
+
+/// Split the output of a `panic_catching_input` evaluation into its stdout and the panic message,
+/// None if it didn't panic
+pub fn split_caught_panic(output: &str) -> Option<(String, String)> {
+    let (stdout, message) = split_marked_output(output, PANIC_MARK)?;
+    Some((stdout.to_string(), message.trim_end().to_string()))
+}
+
+#[test]
+fn panic_catching_input_test() {
+    let mut repl = Repl::default();
+    let mut eval =
+        |input: &str| split_caught_panic(&repl.eval(panic_catching_input(input)).unwrap().output);
+    assert_eq!(eval("1 + 2"), None);
+    assert_eq!(
+        eval("{ println!(\"hi\"); let v: Vec<u8> = vec![]; v[1] }"),
+        Some((
+            "hi\n".into(),
+            "index out of bounds: the len is 0 but the index is 1".into()
+        ))
+    );
+    assert_eq!(
+        eval("{ panic!(\"{} failed\", 1); 1 }"),
+        Some(("".into(), "1 failed".into()))
+    );
+    // not UnwindSafe
+    assert_eq!(
+        eval("{ let mut a = 1; let r = &mut a; if *r == 1 { panic!(\"oops\") }; *r }"),
+        Some(("".into(), "oops".into()))
+    );
+}
+
+/// Marks the samples (in nanoseconds per iteration) printed by `benchmark_compare_code`
+const BENCH_MARK: &str = "IRUST_BENCH";
+
+/// Benchmarks the two expressions in the same program: the iterations of each sample are calibrated
+/// to take ~10ms, then the samples of `a` and `b` are taken alternately so they run under the same conditions
+pub fn benchmark_compare_code(a: &str, b: &str) -> String {
+    format!(
+        r#"{{
+fn irust_iterations<T>(f: &mut impl FnMut() -> T) -> u64 {{
+    let mut iterations = 1u64;
+    loop {{
+        let now = std::time::Instant::now();
+        for _ in 0..iterations {{
+            std::hint::black_box(f());
+        }}
+        if now.elapsed() > std::time::Duration::from_millis(10) || iterations >= 1 << 32 {{
+            return iterations;
+        }}
+        iterations *= 2;
+    }}
+}}
+fn irust_sample<T>(f: &mut impl FnMut() -> T, iterations: u64) -> f64 {{
+    let now = std::time::Instant::now();
+    for _ in 0..iterations {{
+        std::hint::black_box(f());
+    }}
+    now.elapsed().as_nanos() as f64 / iterations as f64
+}}
+let mut irust_a = || {{ {a} }};
+let mut irust_b = || {{ {b} }};
+let (irust_a_iterations, irust_b_iterations) = (irust_iterations(&mut irust_a), irust_iterations(&mut irust_b));
+let (mut irust_a_samples, mut irust_b_samples) = (vec![], vec![]);
+for _ in 0..20 {{
+    irust_a_samples.push(irust_sample(&mut irust_a, irust_a_iterations));
+    irust_b_samples.push(irust_sample(&mut irust_b, irust_b_iterations));
+}}
+println!("\n{BENCH_MARK}\n{{irust_a_samples:?}}\n{{irust_b_samples:?}}");
+}}"#
+    )
+}
+
+/// The samples of `a` and `b` in the output of `benchmark_compare_code`
+pub fn parse_benchmark_samples(output: &str) -> Option<(Vec<f64>, Vec<f64>)> {
+    let mut samples = split_marked_output(output, BENCH_MARK)?
+        .1
+        .lines()
+        .map(|line| serde_json::from_str::<Vec<f64>>(line).ok());
+    Some((samples.next()??, samples.next()??))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchStats {
+    pub median: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl BenchStats {
+    pub fn new(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let len = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / len;
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / len;
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.
+        } else {
+            sorted[mid]
+        };
+        Self {
+            median,
+            mean,
+            stddev: variance.sqrt(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Format a duration in nanoseconds with a readable unit, exp: `1520.0` => `1.52µs`
+pub fn format_nanos(nanos: f64) -> String {
+    match nanos {
+        n if n < 1e3 => format!("{n:.2}ns"),
+        n if n < 1e6 => format!("{:.2}µs", n / 1e3),
+        n if n < 1e9 => format!("{:.2}ms", n / 1e6),
+        n => format!("{:.2}s", n / 1e9),
+    }
+}
+
+/// The statistics of each expression followed by the winner (compared by median)
+pub fn format_benchmark_compare(a: (&str, BenchStats), b: (&str, BenchStats)) -> String {
+    let stats = |(expression, stats): (&str, BenchStats)| {
+        format!(
+            "{expression}\n  median {}  mean {} ± {}  min {}",
+            format_nanos(stats.median),
+            format_nanos(stats.mean),
+            format_nanos(stats.stddev),
+            format_nanos(stats.min)
+        )
+    };
+    let (faster, slower) = if a.1.median <= b.1.median {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    // a median of 0 (exp: the expression was optimized away) has no meaningful ratio
+    let winner = if slower.1.median == 0. {
+        format!("`{}` and `{}` take no measurable time", faster.0, slower.0)
+    } else if faster.1.median == 0. {
+        format!(
+            "`{}` is faster than `{}` (it takes no measurable time)",
+            faster.0, slower.0
+        )
+    } else {
+        format!(
+            "`{}` is {:.2}x faster than `{}`",
+            faster.0,
+            slower.1.median / faster.1.median,
+            slower.0
+        )
+    };
+    format!("{}\n{}\n{winner}", stats(a), stats(b))
+}
+
+/// The number of runs of `:bench <expression>` without an explicit count
+pub const BENCH_DEFAULT_RUNS: usize = 100;
+
+/// The runs and the expression of `:bench [runs] <expression>`, exp: `1000 v.sort()` => (1000, `v.sort()`)
+/// A lone number is the expression, exp: `5` => (100, `5`)
+pub fn parse_bench_args(args: &str) -> (usize, &str) {
+    let args = args.trim();
+    match args.split_once(char::is_whitespace) {
+        Some((runs, expression)) => match runs.parse::<usize>() {
+            Ok(runs) if runs > 0 => (runs, expression.trim()),
+            _ => (BENCH_DEFAULT_RUNS, args),
+        },
+        None => (BENCH_DEFAULT_RUNS, args),
+    }
+}
+
+/// The code evaluated by `:bench`, it times every run and prints the samples after `BENCH_MARK`
+pub fn bench_code(expression: &str, runs: usize) -> String {
+    format!(
+        r#"{{
+let mut irust_f = || {{ {expression} }};
+let mut irust_samples = Vec::with_capacity({runs});
+for _ in 0..{runs} {{
+    let now = std::time::Instant::now();
+    std::hint::black_box(irust_f());
+    irust_samples.push(now.elapsed().as_nanos() as f64);
+}}
+println!("\n{BENCH_MARK}\n{{irust_samples:?}}");
+}}"#
+    )
+}
+
+/// The samples in the output of `bench_code`
+pub fn parse_bench_samples(output: &str) -> Option<Vec<f64>> {
+    serde_json::from_str(split_marked_output(output, BENCH_MARK)?.1.trim()).ok()
+}
+
+pub fn format_bench(runs: usize, stats: BenchStats) -> String {
+    format!(
+        "{runs} runs\n  mean {} ± {}  min {}  max {}",
+        format_nanos(stats.mean),
+        format_nanos(stats.stddev),
+        format_nanos(stats.min),
+        format_nanos(stats.max)
+    )
+}
+
+#[test]
+fn bench_test() {
+    assert_eq!(parse_bench_args(" v.sort() "), (100, "v.sort()"));
+    assert_eq!(parse_bench_args("1000 v.sort()"), (1000, "v.sort()"));
+    assert_eq!(parse_bench_args("5"), (100, "5"));
+    assert_eq!(parse_bench_args("0 x"), (100, "0 x"));
+
+    let mut repl = Repl::default();
+    let result = eval_with(
+        &mut repl,
+        bench_code("(0..100u64).sum::<u64>()", 50),
+        &crate::utils::unit_macro_evaluator(),
+        CompileMode::Release,
+    );
+    assert!(result.status.success(), "{}", result.output);
+    let samples = parse_bench_samples(&result.output).unwrap();
+    assert_eq!(samples.len(), 50);
+    assert!(format_bench(50, BenchStats::new(&samples)).starts_with("50 runs\n  mean "));
+
+    let stats = BenchStats::new(&[4., 1., 3., 2.]);
+    assert_eq!((stats.min, stats.max), (1., 4.));
+    assert_eq!(
+        format_bench(4, stats),
+        "4 runs\n  mean 2.50ns ± 1.12ns  min 1.00ns  max 4.00ns"
+    );
+}
+
+#[test]
+fn benchmark_compare_test() {
+    let a = "(0..100u64).sum::<u64>()";
+    let b = "(0..100u64).fold(0, |acc, x| acc + x)";
+    let mut repl = Repl::default();
+    let result = eval_with(
+        &mut repl,
+        benchmark_compare_code(a, b),
+        &crate::utils::unit_macro_evaluator(),
+        CompileMode::Release,
+    );
+    assert!(result.status.success(), "{}", result.output);
+    let (a_samples, b_samples) = parse_benchmark_samples(&result.output).unwrap();
+    assert_eq!((a_samples.len(), b_samples.len()), (20, 20));
+    let report = format_benchmark_compare(
+        (a, BenchStats::new(&a_samples)),
+        (b, BenchStats::new(&b_samples)),
+    );
+    assert!(report.starts_with(&format!("{a}\n  median ")));
+    assert!(report.contains(&format!("\n{b}\n  median ")));
+    assert!(report.lines().last().unwrap().contains("x faster than"));
+
+    let stats = BenchStats::new(&[4., 1., 3., 2.]);
+    assert_eq!((stats.median, stats.mean, stats.min), (2.5, 2.5, 1.));
+    assert_eq!(
+        format_benchmark_compare(
+            ("a", BenchStats::new(&[30.])),
+            ("b", BenchStats::new(&[10.]))
+        )
+        .lines()
+        .last(),
+        Some("`b` is 3.00x faster than `a`")
+    );
+    let last_line = |a: &[f64], b: &[f64]| {
+        format_benchmark_compare(("a", BenchStats::new(a)), ("b", BenchStats::new(b)))
+            .lines()
+            .last()
+            .map(ToString::to_string)
+    };
+    assert_eq!(
+        last_line(&[10.], &[0.]).as_deref(),
+        Some("`b` is faster than `a` (it takes no measurable time)")
+    );
+    assert_eq!(
+        last_line(&[0.], &[0.]).as_deref(),
+        Some("`a` and `b` take no measurable time")
+    );
+    assert_eq!(format_nanos(1520.), "1.52µs");
+    assert_eq!(format_nanos(2.5e9), "2.50s");
+}
+
+/// The code compiled by `:vars`, all the variables are probed with one build
+/// exp: [`a`, `b`] => the type of `(&a, &b,)`
+pub fn vars_probe(names: &[String]) -> String {
+    let refs: String = names.iter().map(|name| format!("&{name}, ")).collect();
+    type_probe(&format!("({})", refs.trim_end()))
+}
+
+/// The types of the `vars_probe` tuple, exp: `(&i32, &Vec<(u8, u8)>)` => [`i32`, `Vec<(u8, u8)>`]
+pub fn split_tuple_type(ty: &str) -> Vec<String> {
+    let Some(inner) = ty
+        .trim()
+        .strip_prefix('(')
+        .and_then(|ty| ty.strip_suffix(')'))
+    else {
+        return vec![];
+    };
+    let mut types = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in inner.char_indices() {
+        match c {
+            '<' | '(' | '[' | '{' => depth += 1,
+            // `->` in fn types
+            '>' if inner[..idx].ends_with('-') => (),
+            '>' | ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(&inner[start..idx]);
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    types.push(&inner[start..]);
+    types
+        .into_iter()
+        .map(str::trim)
+        .filter(|ty| !ty.is_empty())
+        .map(|ty| {
+            let ty = ty.strip_prefix('&').unwrap_or(ty);
+            if ty.starts_with("{closure@") {
+                "closure".to_string()
+            } else {
+                ty.to_string()
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn split_tuple_type_test() {
+    assert_eq!(
+        vars_probe(&["a".into(), "b".into()]),
+        "let _: () = {\n(&a, &b,)\n};"
+    );
+    assert_eq!(
+        split_tuple_type("(&{integer}, &HashMap<String, (u8, u8)>, &fn(u8) -> u8)"),
+        ["{integer}", "HashMap<String, (u8, u8)>", "fn(u8) -> u8"]
+    );
+    assert_eq!(
+        split_tuple_type("(&{closure@src/main.rs:3:9: 3:14},)"),
+        ["closure"]
+    );
+    assert!(split_tuple_type("u8").is_empty());
+
+    // with the real compiler
+    let mut repl = Repl::default();
+    repl.insert("let a = 1;\nlet s = String::new();\nlet v = vec![(1u8, 'c')];");
+    let code_before_cursor = repl.lines().take(repl.cursor()).cloned();
+    let names = crate::utils::bound_variables(&code_before_cursor.collect::<Vec<_>>().join("\n"));
+    assert_eq!(names, ["a", "s", "v"]);
+    let output = build_output(&mut repl, vars_probe(&names));
+    assert_eq!(
+        split_tuple_type(&parse_found_type(&output).unwrap()),
+        ["{integer}", "String", "Vec<(u8, char)>"]
+    );
+}
+
+/// The binding of the expression in the code expanded by `:expand <expression>`
+const EXPAND_BINDING: &str = "irust_expanded";
+
+/// The statement of `:expand <expression>`, its value is found in the expansion by `expanded_expression`
+pub fn expand_probe(expression: &str) -> String {
+    format!("let {EXPAND_BINDING} = {{\n{expression}\n}};")
+}
+
+/// The expansion of the `expand_probe` expression in the `cargo expand` output, dedented
+pub fn expanded_expression(expanded: &str) -> Option<String> {
+    use rustc_lexer::TokenKind;
+    let mut pos = 0;
+    let mut tokens = rustc_lexer::tokenize(expanded).map(|token| {
+        let start = pos;
+        pos += token.len;
+        (token.kind, start, &expanded[start..pos])
+    });
+    tokens.find(|&(kind, _, text)| kind == TokenKind::Ident && text == EXPAND_BINDING)?;
+    let (_, eq, _) = tokens.find(|&(kind, ..)| kind != TokenKind::Whitespace)?;
+    let start = eq + 1;
+    let mut depth = 0usize;
+    let end = tokens.find_map(|(kind, start, _)| {
+        match kind {
+            TokenKind::OpenBrace | TokenKind::OpenParen | TokenKind::OpenBracket => depth += 1,
+            TokenKind::CloseBrace | TokenKind::CloseParen | TokenKind::CloseBracket => {
+                depth = depth.saturating_sub(1)
+            }
+            TokenKind::Semi if depth == 0 => return Some(start),
+            _ => (),
+        }
+        None
+    })?;
+
+    let value = expanded[start..end].trim();
+    let value = value
+        .strip_prefix('{')
+        .and_then(|value| value.strip_suffix('}'))
+        .unwrap_or(value);
+    let lines: Vec<&str> = value.lines().skip_while(|l| l.trim().is_empty()).collect();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    Some(
+        lines
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or(line.trim_start()))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim_end()
+            .to_string(),
+    )
+}
+#[test]
+fn expanded_expression_test() {
+    let expanded = "\
+fn main() {
+    let a = 1;
+    let irust_expanded = {
+        <[_]>::into_vec(
+            #[rustc_box]
+            ::alloc::boxed::Box::new([1, 2]),
+        )
+    };
+}";
+    assert_eq!(
+        expanded_expression(expanded).unwrap(),
+        "<[_]>::into_vec(\n    #[rustc_box]\n    ::alloc::boxed::Box::new([1, 2]),\n)"
+    );
+    // a `;` inside the expression doesn't end it
+    let expanded = "let irust_expanded = { { let b = \"};\"; b } };";
+    assert_eq!(
+        expanded_expression(expanded).unwrap(),
+        "{ let b = \"};\"; b }"
+    );
+    assert_eq!(expanded_expression("fn main() {}"), None);
+}
@@ -43,6 +43,776 @@ fn split_args_test() {
     );
 }
 
+/// The `cargo add` arguments of `:add`, the paths are canonicalized with `canonicalize` since the repl
+/// is in another directory, everything else (crate specs like `regex@1.5`, flags and their values) is kept as is
+/// Only the `--path` values and the positional arguments that look like a path (exp: `.`, `../foo`) are paths,
+/// so a feature like `serde/derive` is never mistaken for one, and a git url is never a path
+/// The flags without a value (exp: `--dev`, `--build`) are forwarded as is
+pub fn add_dep_args(
+    args: Vec<String>,
+    canonicalize: impl Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    // The `cargo add` options that take a value
+    const VALUE_FLAGS: &[&str] = &[
+        "--features",
+        "-F",
+        "--rename",
+        "--package",
+        "-p",
+        "--registry",
+        "--git",
+        "--branch",
+        "--tag",
+        "--rev",
+        "--target",
+        "--manifest-path",
+    ];
+    let looks_like_path = |arg: &str| {
+        matches!(arg, "." | "..")
+            || arg.contains(['/', '\\']) && !arg.contains('@') && !arg.contains("://")
+    };
+    let mut canonical_args = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--path" {
+            canonical_args.push(arg);
+            if let Some(path) = args.next() {
+                canonical_args.push(canonicalize(&path).unwrap_or(path));
+            }
+        } else if let Some(path) = arg.strip_prefix("--path=") {
+            canonical_args.push(format!(
+                "--path={}",
+                canonicalize(path).unwrap_or_else(|| path.to_string())
+            ));
+        } else if VALUE_FLAGS.contains(&arg.as_str()) {
+            canonical_args.push(arg);
+            canonical_args.extend(args.next());
+        } else if !arg.starts_with('-') && looks_like_path(&arg) {
+            canonical_args.push(canonicalize(&arg).unwrap_or(arg));
+        } else {
+            canonical_args.push(arg);
+        }
+    }
+    canonical_args
+}
+
+#[test]
+fn add_dep_args_test() {
+    let add_args = |cmd: &str| {
+        let mut args = split_args(cmd.to_string());
+        args.remove(0);
+        // every argument exists as a path
+        add_dep_args(args, |path| Some(format!("/abs/{path}")))
+    };
+    assert_eq!(
+        add_args(":add tokio --features full"),
+        ["tokio", "--features", "full"]
+    );
+    assert_eq!(add_args(":add regex@1.5"), ["regex@1.5"]);
+    // the dependency kind flags are forwarded to cargo add
+    assert_eq!(add_args(":add --dev proptest"), ["--dev", "proptest"]);
+    assert_eq!(
+        add_args(":add --build cc --features parallel"),
+        ["--build", "cc", "--features", "parallel"]
+    );
+    assert_eq!(
+        add_args(":add regex@1.5 --no-default-features -F unicode-perl"),
+        ["regex@1.5", "--no-default-features", "-F", "unicode-perl"]
+    );
+    // a feature with a slash is not a path
+    assert_eq!(
+        add_args(":add serde_with --features serde/std,chrono"),
+        ["serde_with", "--features", "serde/std,chrono"]
+    );
+    assert_eq!(
+        add_args(r#":add tokio --features "rt macros""#),
+        ["tokio", "--features", "rt macros"]
+    );
+    // the paths are canonicalized
+    assert_eq!(add_args(":add ."), ["/abs/."]);
+    assert_eq!(add_args(":add ../foo"), ["/abs/../foo"]);
+    assert_eq!(
+        add_args(":add foo --path ./foo"),
+        ["foo", "--path", "/abs/./foo"]
+    );
+    assert_eq!(add_args(":add --path=./foo"), ["--path=/abs/./foo"]);
+    // a path that doesn't exist is kept as is
+    assert_eq!(add_dep_args(vec!["./nope".into()], |_| None), ["./nope"]);
+    // the git urls and their branch/tag/rev are kept as is
+    assert_eq!(
+        add_args(":add anyhow --git https://github.com/dtolnay/anyhow"),
+        ["anyhow", "--git", "https://github.com/dtolnay/anyhow"]
+    );
+    assert_eq!(
+        add_args(":add anyhow --git https://github.com/dtolnay/anyhow --branch feature/x"),
+        [
+            "anyhow",
+            "--git",
+            "https://github.com/dtolnay/anyhow",
+            "--branch",
+            "feature/x"
+        ]
+    );
+    assert_eq!(
+        add_args(":add anyhow --git=https://github.com/dtolnay/anyhow --tag 1.0.0"),
+        [
+            "anyhow",
+            "--git=https://github.com/dtolnay/anyhow",
+            "--tag",
+            "1.0.0"
+        ]
+    );
+    assert_eq!(
+        add_args(":add --git https://github.com/dtolnay/anyhow --rev 1a2b3c"),
+        [
+            "--git",
+            "https://github.com/dtolnay/anyhow",
+            "--rev",
+            "1a2b3c"
+        ]
+    );
+    assert_eq!(
+        add_args(":add https://github.com/dtolnay/anyhow"),
+        ["https://github.com/dtolnay/anyhow"]
+    );
+}
+
+/// The local crate paths of the `:add` arguments, with the same rules as `add_dep_args`
+/// The positional arguments are only paths if they exist (exp: `serde/derive` is a feature)
+pub fn add_dep_paths(args: &[String]) -> Vec<String> {
+    let paths = std::cell::RefCell::new(vec![]);
+    add_dep_args(args.to_vec(), |path| {
+        paths.borrow_mut().push(path.to_string());
+        None
+    });
+    let is_path_value = |path: &str| {
+        args.windows(2)
+            .any(|pair| pair[0] == "--path" && pair[1] == path)
+            || args.contains(&format!("--path={path}"))
+    };
+    paths
+        .into_inner()
+        .into_iter()
+        .filter(|path| is_path_value(path) || Path::new(path).exists())
+        .collect()
+}
+
+/// The `[package] name` of a crate Cargo.toml
+pub fn package_name(cargo_toml: &str) -> Result<String> {
+    let manifest: toml::Table = toml::from_str(cargo_toml)?;
+    manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| "The Cargo.toml has no `[package] name` (exp: a workspace root)".into())
+}
+
+#[test]
+fn add_dep_paths_test() {
+    let args = |cmd: &str| split_args(cmd.to_string())[1..].to_vec();
+    assert_eq!(
+        add_dep_paths(&args(":add --path ./mylib serde/derive")),
+        ["./mylib"]
+    );
+    assert_eq!(add_dep_paths(&args(":add .. --path=b")), ["..", "b"]);
+    assert!(add_dep_paths(&args(":add ../not_a_dir_of_irust")).is_empty());
+    assert!(add_dep_paths(&args(":add --dev proptest")).is_empty());
+    assert_eq!(
+        add_dep_paths(&args(":add --dev --path ./mylib")),
+        ["./mylib"]
+    );
+    assert!(add_dep_paths(&args(
+        ":add regex@1.5 --git https://github.com/rust-lang/regex"
+    ))
+    .is_empty());
+
+    assert_eq!(
+        package_name("[package]\nname = \"mylib\"\nversion = \"0.1.0\"\n").unwrap(),
+        "mylib"
+    );
+    assert!(package_name("[workspace]\nmembers = [\"a\"]\n").is_err());
+    assert!(package_name("[package\n").is_err());
+}
+
+/// The editor program and its arguments of `:edit` (without the `:edit` itself)
+/// Without arguments `$VISUAL` then `$EDITOR` are used, a command line in one argument (exp: `"emacsclient -nw"`)
+/// or in the environment variables is split on whitespace
+pub fn editor_command(
+    args: Vec<String>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<(String, Vec<String>)> {
+    let args = match args.as_slice() {
+        [] => {
+            let editor = env_var("VISUAL")
+                .filter(|editor| !editor.trim().is_empty())
+                .or_else(|| env_var("EDITOR"))?;
+            editor.split_whitespace().map(ToOwned::to_owned).collect()
+        }
+        [cmdline] => cmdline.split_whitespace().map(ToOwned::to_owned).collect(),
+        _ => args,
+    };
+    let mut args = args.into_iter();
+    Some((args.next()?, args.collect()))
+}
+
+#[test]
+fn editor_command_test() {
+    let no_env = |_: &str| None;
+    let edit = |cmd: &str, env: &dyn Fn(&str) -> Option<String>| {
+        let mut args = split_args(cmd.to_string());
+        args.remove(0);
+        editor_command(args, env)
+    };
+    let command = |program: &str, args: &[&str]| {
+        Some((
+            program.to_string(),
+            args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(),
+        ))
+    };
+    assert_eq!(edit(":edit vi", &no_env), command("vi", &[]));
+    assert_eq!(
+        edit(":edit code --wait", &no_env),
+        command("code", &["--wait"])
+    );
+    assert_eq!(
+        edit(r#":edit "emacsclient -nw""#, &no_env),
+        command("emacsclient", &["-nw"])
+    );
+    assert_eq!(edit(":edit", &no_env), None);
+
+    let env = |var: &str| match var {
+        "VISUAL" => Some("code --wait".to_string()),
+        "EDITOR" => Some("nano".to_string()),
+        _ => None,
+    };
+    assert_eq!(edit(":edit", &env), command("code", &["--wait"]));
+    let editor_only = |var: &str| (var == "EDITOR").then(|| "nano".to_string());
+    assert_eq!(edit(":edit", &editor_only), command("nano", &[]));
+}
+
+/// Parse a dotenv style file into a list of (key, value)
+/// Supports comments, `export` prefixes, single quoted (literal) and double quoted (escaped) values
+pub fn parse_dotenv(s: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = vec![];
+    for (idx, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid line {}: `{line}`", idx + 1))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("Invalid key at line {}: `{key}`", idx + 1).into());
+        }
+
+        let value = value.trim();
+        let value = if let Some(value) = value.strip_prefix('"') {
+            let mut unescaped = String::new();
+            let mut chars = value.chars();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => unescaped.push('\n'),
+                        Some('t') => unescaped.push('\t'),
+                        Some(c) => unescaped.push(c),
+                        None => break,
+                    },
+                    Some(c) => unescaped.push(c),
+                    None => return Err(format!("Unterminated quote at line {}", idx + 1).into()),
+                }
+            }
+            unescaped
+        } else if let Some(value) = value.strip_prefix('\'') {
+            value
+                .split_once('\'')
+                .ok_or_else(|| format!("Unterminated quote at line {}", idx + 1))?
+                .0
+                .to_string()
+        } else {
+            // unquoted values can have a trailing comment
+            value
+                .split_once(" #")
+                .map(|(v, _)| v)
+                .unwrap_or(value)
+                .trim_end()
+                .to_string()
+        };
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+#[test]
+fn parse_dotenv_test() {
+    let env = r#"
+# database config
+DB_HOST=localhost
+export DB_PORT=5432 # default port
+GREETING="hello \"world\"\n"
+RAW='no $escape\n here'
+EMPTY=
+"#;
+    assert_eq!(
+        vec![
+            ("DB_HOST", "localhost"),
+            ("DB_PORT", "5432"),
+            ("GREETING", "hello \"world\"\n"),
+            ("RAW", "no $escape\\n here"),
+            ("EMPTY", ""),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect::<Vec<_>>(),
+        parse_dotenv(env).unwrap()
+    );
+    assert!(parse_dotenv("NOT A VAR").is_err());
+    assert!(parse_dotenv("A=\"unterminated").is_err());
+
+    // `:env-file` sets the variables in irust environment, the evaluations inherit it
+    for (key, value) in parse_dotenv("IRUST_DOTENV_TEST=loaded").unwrap() {
+        std::env::set_var(key, value);
+    }
+    let mut repl = irust_repl::Repl::default();
+    assert_eq!(
+        repl.eval("std::env::var(\"IRUST_DOTENV_TEST\")")
+            .unwrap()
+            .output,
+        "Ok(\"loaded\")"
+    );
+}
+
+/// Short description of an inserted item, exp: `struct Foo`, `fn bar`, `impl Display for Foo`
+pub fn describe_item(code: &str) -> Option<String> {
+    let code = remove_comments(code);
+    // skip attributes
+    let code: String = code
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut words = code
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '!'))
+        .filter(|w| !w.is_empty())
+        .peekable();
+    loop {
+        let word = words.next()?;
+        match word {
+            "pub" | "crate" | "super" | "async" | "unsafe" | "extern" | "C" => continue,
+            "const" | "static" if words.peek() == Some(&"fn") => continue,
+            "impl" => {
+                let header = code[code.find("impl")?..]
+                    .split(['{', ';'])
+                    .next()?
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                return Some(header);
+            }
+            "fn" | "struct" | "enum" | "union" | "trait" | "type" | "mod" | "macro_rules!"
+            | "const" | "static" | "let" | "use" => {
+                let mut name = words.next()?;
+                if name == "mut" {
+                    name = words.next()?;
+                }
+                return Some(format!("{word} {name}"));
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[test]
+fn describe_item_test() {
+    assert_eq!(
+        describe_item("#[derive(Debug)]\npub struct Foo { a: u8 }"),
+        Some("struct Foo".into())
+    );
+    assert_eq!(
+        describe_item("pub(crate) const fn bar() {}"),
+        Some("fn bar".into())
+    );
+    assert_eq!(
+        describe_item("impl<T> std::fmt::Display for Foo<T> {}"),
+        Some("impl<T> std::fmt::Display for Foo<T>".into())
+    );
+    assert_eq!(describe_item("let mut a = 4;"), Some("let a".into()));
+    assert_eq!(describe_item("a += 1;"), None);
+}
+
+/// If the input is an integer literal (exp: `0xFFu8`, `-0b1010`, `1_000`),
+/// returns its value in decimal, hex and binary forms
+pub fn int_literal_bases(s: &str) -> Option<String> {
+    const SUFFIXES: &[&str] = &[
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    ];
+    let s = s.trim().replace('_', "");
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s.trim_start()),
+        None => (false, s.as_str()),
+    };
+    let (radix, digits) = if let Some(d) = s.strip_prefix("0x") {
+        (16, d)
+    } else if let Some(d) = s.strip_prefix("0o") {
+        (8, d)
+    } else if let Some(d) = s.strip_prefix("0b") {
+        (2, d)
+    } else {
+        (10, s)
+    };
+    let digits = SUFFIXES
+        .iter()
+        .find_map(|suffix| digits.strip_suffix(suffix))
+        .unwrap_or(digits);
+    let value = u128::from_str_radix(digits, radix).ok()?;
+
+    let sign = if negative { "-" } else { "" };
+    Some(format!(
+        "{sign}{value} = {sign}{value:#x} = {sign}{value:#b}"
+    ))
+}
+
+#[test]
+fn int_literal_bases_test() {
+    assert_eq!(
+        int_literal_bases("0b1010"),
+        Some("10 = 0xa = 0b1010".into())
+    );
+    assert_eq!(
+        int_literal_bases("0xFFu8"),
+        Some("255 = 0xff = 0b11111111".into())
+    );
+    assert_eq!(
+        int_literal_bases("-1_0"),
+        Some("-10 = -0xa = -0b1010".into())
+    );
+    assert_eq!(int_literal_bases("a + 1"), None);
+    assert_eq!(int_literal_bases("1.5"), None);
+}
+
+/// Returns the (1-based) line number and the trimmed line of every `unsafe` usage
+/// (blocks, functions, impls, ..), ignoring line comments and string literals
+pub fn find_unsafe(code: &str) -> Vec<(usize, String)> {
+    let strip_comments_and_strings = |line: &str| {
+        let mut stripped = String::new();
+        let mut d_quote = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => d_quote = !d_quote,
+                '\\' if d_quote => {
+                    chars.next();
+                }
+                '/' if !d_quote && chars.peek() == Some(&'/') => break,
+                c if !d_quote => stripped.push(c),
+                _ => (),
+            }
+        }
+        stripped
+    };
+
+    code.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            strip_comments_and_strings(line)
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|word| word == "unsafe")
+        })
+        .map(|(idx, line)| (idx + 1, line.trim().to_string()))
+        .collect()
+}
+
+#[test]
+fn find_unsafe_test() {
+    let code = r#"fn main() -> () {
+let p = &4 as *const i32;
+// unsafe in a comment
+let s = "unsafe in a string";
+let v = unsafe { *p };
+unsafe fn danger() {}
+let not_unsafe_ident = 1;
+}"#;
+    assert_eq!(
+        find_unsafe(code),
+        vec![
+            (5, "let v = unsafe { *p };".to_string()),
+            (6, "unsafe fn danger() {}".to_string())
+        ]
+    );
+}
+
+/// Parse a `cargo build --timings` html report into a list of (crate, compile time in seconds)
+/// sorted from the slowest to the fastest, `exclude` is the repl crate itself
+pub fn parse_build_timings(report: &str, exclude: &str) -> Result<Vec<(String, f64)>> {
+    const UNIT_DATA_TAG: &str = "const UNIT_DATA = ";
+    let start = report.find(UNIT_DATA_TAG).ok_or("invalid timings report")? + UNIT_DATA_TAG.len();
+    // `\n]` closes the array
+    let end = start
+        + report[start..]
+            .find("\n];")
+            .ok_or("invalid timings report")?
+        + 2;
+    let units: Vec<serde_json::Value> = serde_json::from_str(&report[start..end])?;
+
+    // A crate can have multiple units (exp: build script + lib)
+    let mut timings: Vec<(String, f64)> = vec![];
+    for unit in units {
+        let (Some(name), Some(version), Some(duration)) = (
+            unit["name"].as_str(),
+            unit["version"].as_str(),
+            unit["duration"].as_f64(),
+        ) else {
+            continue;
+        };
+        if name == exclude {
+            continue;
+        }
+        let krate = format!("{name} v{version}");
+        match timings.iter_mut().find(|(k, _)| *k == krate) {
+            Some((_, total)) => *total += duration,
+            None => timings.push((krate, duration)),
+        }
+    }
+    timings.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(timings)
+}
+
+#[test]
+fn parse_build_timings_test() {
+    let report = r#"<script>
+const UNIT_DATA = [
+  {"i": 0, "name": "libc", "version": "0.2.1", "target": " build-script", "duration": 0.25},
+  {"i": 1, "name": "syn", "version": "2.0.0", "target": "", "duration": 3.5},
+  {"i": 2, "name": "libc", "version": "0.2.1", "target": "", "duration": 1.0},
+  {"i": 3, "name": "irust_host_repl", "version": "0.1.0", "target": "", "duration": 9.0}
+];
+const CONCURRENCY_DATA = [
+];
+</script>"#;
+    assert_eq!(
+        parse_build_timings(report, "irust_host_repl").unwrap(),
+        vec![
+            ("syn v2.0.0".to_string(), 3.5),
+            ("libc v0.2.1".to_string(), 1.25)
+        ]
+    );
+    assert!(parse_build_timings("<html></html>", "irust_host_repl").is_err());
+}
+
+/// Checks if the input uses the `?` operator (string/char literals and comments are ignored)
+pub fn has_question_mark(input: &str) -> bool {
+    rustc_lexer::tokenize(input).any(|token| token.kind == rustc_lexer::TokenKind::Question)
+}
+
+/// Checks if the input uses `.await` outside of an async block, in that case it needs an async runtime
+/// exp: `fetch().await` but not `tokio::spawn(async { fetch().await })` (literals and comments are ignored)
+pub fn has_top_level_await(input: &str) -> bool {
+    use rustc_lexer::TokenKind;
+    let mut pos = 0;
+    let tokens: Vec<(TokenKind, &str)> = rustc_lexer::tokenize(input)
+        .map(|token| {
+            pos += token.len;
+            (token.kind, &input[pos - token.len..pos])
+        })
+        .filter(|(kind, _)| {
+            !matches!(
+                kind,
+                TokenKind::Whitespace
+                    | TokenKind::LineComment { .. }
+                    | TokenKind::BlockComment { .. }
+            )
+        })
+        .collect();
+
+    // is every open block an async block (or the body of an async closure)
+    let mut blocks: Vec<bool> = vec![];
+    // the next block is async, exp: after `async move` or `async |x|`
+    let mut async_pending = false;
+    for (idx, (kind, text)) in tokens.iter().enumerate() {
+        match (kind, *text) {
+            (TokenKind::Ident, "async") => async_pending = true,
+            (TokenKind::OpenBrace, _) => blocks.push(std::mem::take(&mut async_pending)),
+            (TokenKind::CloseBrace, _) => {
+                blocks.pop();
+            }
+            (TokenKind::Semi, _) => async_pending = false,
+            (TokenKind::Dot, _)
+                if tokens.get(idx + 1) == Some(&(TokenKind::Ident, "await"))
+                    && !blocks.contains(&true) =>
+            {
+                return true;
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+#[test]
+fn has_top_level_await_test() {
+    assert!(has_top_level_await("fetch().await"));
+    assert!(has_top_level_await("{ a().await.b().await }"));
+    assert!(has_top_level_await("async { 1 }.await"));
+    assert!(!has_top_level_await(
+        "tokio::spawn(async move { fetch().await })"
+    ));
+    assert!(!has_top_level_await("(|| async { fetch().await })()"));
+    assert!(!has_top_level_await(
+        "let f = async |x: u8| { g(x).await };"
+    ));
+    assert!(!has_top_level_await(
+        r#"println!("{}", ".await") // .await"#
+    ));
+    assert!(!has_top_level_await("a.awaited"));
+}
+
+/// Wrap an expression that uses `?` so it can be evaluated, the `Ok` value is returned
+/// and the error is printed (the evaluation exits with an error status)
+/// `is_async` should be set if the input contains `.await`, in that case an async block is used instead of a closure
+pub fn wrap_question_mark(input: &str, is_async: bool) -> String {
+    let (start, end) = if is_async {
+        ("(async {", "}).await")
+    } else {
+        ("(|| {", "})()")
+    };
+    format!(
+        "match {start} Ok::<_, Box<dyn std::error::Error>>({{
+{input}
+}}) {end} {{
+    Ok(value) => value,
+    Err(e) => {{
+        eprintln!(\"Error: {{e}}\");
+        std::process::exit(1)
+    }}
+}}"
+    )
+}
+
+#[test]
+fn wrap_question_mark_test() {
+    assert!(has_question_mark(r#"std::fs::read_to_string("x")?"#));
+    assert!(!has_question_mark(r#""is this a question?""#));
+
+    let mut repl = irust_repl::Repl::default();
+    let eval = |repl: &mut irust_repl::Repl, input: &str| {
+        repl.eval(wrap_question_mark(input, false)).unwrap().output
+    };
+    assert_eq!(eval(&mut repl, r#""4".parse::<u8>()? + 1"#), "5");
+    assert_eq!(eval(&mut repl, r#"{ "4".parse::<u8>()?; }"#), "()");
+    assert_eq!(
+        eval(&mut repl, r#""a".parse::<u8>()?"#),
+        "Error: invalid digit found in string"
+    );
+}
+
+/// `let` statement that binds `value` (a line of text entered by the user) to `name`
+/// The text is used as a `String`, or parsed with `FromStr` if a type is specified
+pub fn input_binding(name: &str, ty: Option<&str>, value: &str) -> Result<String> {
+    let is_ident = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !is_ident {
+        return Err(format!("Invalid variable name: `{name}`").into());
+    }
+    Ok(match ty {
+        None | Some("String") => format!("let {name} = String::from({value:?});"),
+        Some(ty) => {
+            format!("let {name}: {ty} = {value:?}.parse().expect(\"irust: invalid input\");")
+        }
+    })
+}
+
+#[test]
+fn input_binding_test() {
+    assert_eq!(
+        input_binding("s", None, "hello \"world\"").unwrap(),
+        r#"let s = String::from("hello \"world\"");"#
+    );
+    assert!(input_binding("1a", None, "").is_err());
+
+    let mut repl = irust_repl::Repl::default();
+    repl.insert(input_binding("n", Some("u32"), "42").unwrap());
+    assert_eq!(repl.eval("n + 1").unwrap().output, "43");
+}
+
+/// Fuzzy match `query` as a (case insensitive) subsequence of `candidate`
+/// Returns a score (higher is better) and the char indices of the matched characters
+/// Consecutive matches and matches at the start of a word are favoured, gaps are penalized
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut indices = vec![];
+    let mut prev: Option<char> = None;
+    for (idx, c) in candidate.chars().enumerate() {
+        let Some(q) = query.peek() else { break };
+        if c.to_lowercase().eq(std::iter::once(*q)) {
+            score += 1;
+            if indices.last().is_some_and(|last| last + 1 == idx) {
+                score += 5;
+            }
+            if prev.map_or(true, |p| !p.is_alphanumeric()) {
+                score += 3;
+            }
+            if let Some(last) = indices.last() {
+                score -= (idx - last - 1).min(5) as i64;
+            }
+            indices.push(idx);
+            query.next();
+        }
+        prev = Some(c);
+    }
+    query.peek().is_none().then_some((score, indices))
+}
+
+/// Rank the candidates that fuzzy match `query`, best first (the first candidate wins a tie)
+pub fn fuzzy_rank<'a>(
+    query: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Vec<(&'a str, Vec<usize>)> {
+    let mut matches: Vec<(i64, &str, Vec<usize>)> = candidates
+        .filter_map(|c| fuzzy_match(query, c).map(|(score, indices)| (score, c, indices)))
+        .collect();
+    // stable sort keeps the candidates order for equal scores
+    matches.sort_by_key(|m| std::cmp::Reverse(m.0));
+    matches
+        .into_iter()
+        .map(|(_, c, indices)| (c, indices))
+        .collect()
+}
+
+#[test]
+fn fuzzy_rank_test() {
+    let history = [
+        "let vector = vec![1, 2, 3];",
+        "println!(\"{}\", v);",
+        "vec.iter().count()",
+        "a + b",
+    ];
+    let ranked = fuzzy_rank("vec", history.into_iter());
+    assert_eq!(
+        ranked,
+        vec![
+            // same score, the first (the most recent) entry wins
+            ("let vector = vec![1, 2, 3];", vec![4, 5, 6]),
+            ("vec.iter().count()", vec![0, 1, 2]),
+        ]
+    );
+    assert_eq!(
+        fuzzy_rank("itc", history.into_iter()),
+        vec![("vec.iter().count()", vec![4, 5, 11])]
+    );
+    // consecutive matches rank higher
+    assert_eq!(
+        fuzzy_rank("ab", ["a_x_b", "xab"].into_iter()),
+        vec![("xab", vec![1, 2]), ("a_x_b", vec![0, 4])]
+    );
+    assert!(fuzzy_rank("xyz", history.into_iter()).is_empty());
+}
+
 pub fn stdout_and_stderr(out: std::process::Output) -> String {
     let out = if !out.stdout.is_empty() {
         out.stdout
@@ -53,308 +823,2863 @@ pub fn stdout_and_stderr(out: std::process::Output) -> String {
     String::from_utf8_lossy(&out).to_string()
 }
 
-fn _remove_main(script: &str) -> String {
-    const MAIN_FN: &str = "fn main() {";
-
-    let mut script = remove_comments(script);
-
-    let main_start = match script.find(MAIN_FN) {
-        Some(idx) if _balanced_quotes(&script[..idx]) => idx,
-        _ => return script,
+/// Run a `::` shell command line in `cwd` (the directory set with `:cd`), the arguments are split like a shell
+/// does with `shell_words`
+pub fn shell_output(cmdline: &str, cwd: &std::path::Path) -> std::io::Result<std::process::Output> {
+    let words = shell_words(cmdline);
+    let mut cmd = words.iter();
+    if cfg!(windows) {
+        std::process::Command::new("cmd")
+            .arg("/C")
+            .arg(cmd.next().map_or("", String::as_str))
+            .args(cmd)
+            .current_dir(cwd)
+            .output()
+    } else {
+        std::process::Command::new(cmd.next().map_or("", String::as_str))
+            .args(cmd)
+            .current_dir(cwd)
+            .output()
+    }
+}
+
+#[test]
+fn shell_output_test() {
+    // like `:cd /tmp` then `::pwd`
+    if cfg!(unix) {
+        let output = shell_output("pwd", std::path::Path::new("/tmp")).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "/tmp\n");
+    }
+}
+
+/// Split a command line into its arguments, with the shell quoting rules
+/// Single quotes keep everything as is, in double quotes `\` only escapes `"`, `\`, `$` and `` ` ``,
+/// and outside of quotes it escapes any character, exp: `echo "a b" c\ d` => [`echo`, `a b`, `c d`]
+/// `\` is a path separator on windows, so it's kept as is there
+pub fn shell_words(cmdline: &str) -> Vec<String> {
+    let escapes = !cfg!(windows);
+    let mut words = vec![];
+    let mut word = String::new();
+    // `""` is an empty argument
+    let mut in_word = false;
+    let mut chars = cmdline.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if escapes => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' if escapes => {
+                in_word = true;
+                word.push(chars.next().unwrap_or('\\'));
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[test]
+fn shell_words_test() {
+    assert_eq!(shell_words(r#"echo "a b""#), ["echo", "a b"]);
+    assert_eq!(
+        shell_words("grep 'hello world' file.txt"),
+        ["grep", "hello world", "file.txt"]
+    );
+    assert_eq!(
+        shell_words(r#"echo "" 'it''s'  "a\"b""#),
+        ["echo", "", "its", "a\"b"]
+    );
+    assert_eq!(shell_words("  ls  -la "), ["ls", "-la"]);
+    assert!(shell_words("").is_empty());
+    if cfg!(unix) {
+        assert_eq!(shell_words(r"echo a\ b"), ["echo", "a b"]);
+        assert_eq!(shell_words(r#"echo "a\nb\\""#), ["echo", "a\\nb\\"]);
+
+        // every argument is printed in brackets
+        let output =
+            shell_output(r#"printf [%s] "a b" c\ d e"#, std::path::Path::new(".")).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "[a b][c d][e]");
+        let output = shell_output(r#"echo "a  b""#, std::path::Path::new(".")).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "a  b\n");
+    }
+}
+
+/// Expand a leading `~` of a `:cd` path to the home directory, exp: `~/src` => `/home/user/src`
+/// Returns `None` if the path doesn't start with `~` (`~user` isn't supported)
+pub fn expand_home(path: &str, home: &std::path::Path) -> Option<std::path::PathBuf> {
+    let rest = path.strip_prefix('~')?;
+    if rest.is_empty() {
+        return Some(home.to_path_buf());
+    }
+    let rest = rest.strip_prefix(std::path::is_separator)?;
+    Some(home.join(rest))
+}
+
+#[test]
+fn expand_home_test() {
+    let home = std::path::Path::new("/home/user");
+    assert_eq!(expand_home("~", home), Some(home.to_path_buf()));
+    assert_eq!(expand_home("~/src", home), Some(home.join("src")));
+    assert_eq!(expand_home("~/", home), Some(home.join("")));
+    assert_eq!(expand_home("~user", home), None);
+    assert_eq!(expand_home("src/~", home), None);
+}
+
+/// Find a relative `:cd` path that doesn't exist in one of the `CDPATH` base directories, the first match wins
+/// Like in a shell, the paths that start with `.` or `..` are not searched
+pub fn cdpath_dir(path: &str, cdpath: &std::ffi::OsStr) -> Option<std::path::PathBuf> {
+    let relative = std::path::Path::new(path);
+    if relative.is_absolute()
+        || matches!(
+            relative.components().next(),
+            Some(std::path::Component::CurDir | std::path::Component::ParentDir)
+        )
+    {
+        return None;
+    }
+    std::env::split_paths(cdpath)
+        .filter(|base| !base.as_os_str().is_empty())
+        .map(|base| base.join(relative))
+        .find(|dir| dir.is_dir())
+}
+
+#[test]
+fn cdpath_dir_test() {
+    let root = std::env::temp_dir().join("irust_cdpath_test");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("a/project")).unwrap();
+    fs::create_dir_all(root.join("b/project")).unwrap();
+    fs::create_dir_all(root.join("b/other")).unwrap();
+    let cdpath =
+        std::env::join_paths([root.join("missing"), root.join("a"), root.join("b")]).unwrap();
+
+    assert_eq!(cdpath_dir("project", &cdpath), Some(root.join("a/project")));
+    assert_eq!(cdpath_dir("other", &cdpath), Some(root.join("b/other")));
+    assert_eq!(cdpath_dir("nope", &cdpath), None);
+    assert_eq!(cdpath_dir("./project", &cdpath), None);
+    assert_eq!(cdpath_dir("project", "".as_ref()), None);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+/// The biggest shell output that `:shell-bind` inserts in the code
+pub const SHELL_BIND_MAX_LEN: usize = 64 * 1024;
+
+/// The statement that binds a shell output to a `String` variable, exp: `let out = String::from("hi");`
+/// The trailing new lines are removed like in `$(cmd)`
+pub fn shell_bind_statement(name: &str, output: &str) -> Result<String> {
+    if !is_ident(name) {
+        return Err(format!("`{name}` is not a valid variable name").into());
+    }
+    let output = output.trim_end_matches(['\n', '\r']);
+    if output.len() > SHELL_BIND_MAX_LEN {
+        return Err(format!(
+            "The output is too large ({} bytes, the limit is {SHELL_BIND_MAX_LEN})",
+            output.len()
+        )
+        .into());
+    }
+    // The debug format of a str is a valid string literal
+    Ok(format!("let {name} = String::from({output:?});"))
+}
+
+pub fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && name != "_"
+}
+
+/// The variable and the command of a shell output assignment, exp: `let files = ::ls;` => ("files", "ls")
+pub fn shell_assignment(input: &str) -> Option<(&str, &str)> {
+    let (name, cmd) = input.trim().strip_prefix("let ")?.split_once('=')?;
+    let cmd = cmd.trim().strip_prefix("::")?;
+    let cmd = cmd.strip_suffix(';').unwrap_or(cmd).trim();
+    // `let a = ::std::f64::consts::PI;` is an absolute rust path
+    let program = cmd.split_whitespace().next()?;
+    if program.contains("::") || program.contains('(') {
+        return None;
+    }
+    is_ident(name.trim()).then_some((name.trim(), cmd))
+}
+
+/// Does the repl code define a (non async) `fn main`, it shadows the repl main in its body
+pub fn defines_main(code: &str) -> bool {
+    code.lines().any(|line| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("pub ").unwrap_or(line);
+        line.strip_prefix("fn main")
+            .is_some_and(|rest| rest.trim_start().starts_with('('))
+    })
+}
+
+#[test]
+fn defines_main_test() {
+    assert!(defines_main(
+        "let a = 1;\nfn main() {\n    println!(\"{a}\");\n}"
+    ));
+    assert!(defines_main(
+        "pub fn main () -> Result<(), String> { Ok(()) }"
+    ));
+    assert!(!defines_main("fn main_loop() {}"));
+    assert!(!defines_main("async fn main() {}"));
+    assert!(!defines_main("let main = 1;"));
+}
+
+#[test]
+fn shell_assignment_test() {
+    assert_eq!(shell_assignment("let files = ::ls;"), Some(("files", "ls")));
+    assert_eq!(
+        shell_assignment("let out=::grep \"a = b\" file.txt"),
+        Some(("out", "grep \"a = b\" file.txt"))
+    );
+    assert_eq!(shell_assignment("let a = b::c();"), None);
+    assert_eq!(shell_assignment("let a = ::std::f64::consts::PI;"), None);
+    assert_eq!(shell_assignment("let a = ::helper();"), None);
+    assert_eq!(shell_assignment("let (a, b) = ::ls;"), None);
+    assert_eq!(shell_assignment("let a = ::;"), None);
+}
+
+#[test]
+fn shell_bind_statement_test() {
+    let output = shell_output("echo hi", std::path::Path::new(".")).unwrap();
+    let statement = shell_bind_statement("out", &String::from_utf8_lossy(&output.stdout)).unwrap();
+    assert_eq!(statement, "let out = String::from(\"hi\");");
+
+    let mut repl = irust_repl::Repl::default();
+    repl.insert(statement);
+    assert_eq!(repl.eval("out.to_uppercase()").unwrap().output, "\"HI\"");
+
+    // the output is escaped
+    let tricky = "a \"quote\" \\ back\tslash\n{}\u{7}";
+    repl.insert(shell_bind_statement("tricky", tricky).unwrap());
+    assert_eq!(repl.eval("tricky").unwrap().output, format!("{tricky:?}"));
+
+    assert!(shell_bind_statement("1out", "").is_err());
+    assert!(shell_bind_statement("a b", "").is_err());
+    assert!(shell_bind_statement("out", &"a".repeat(SHELL_BIND_MAX_LEN + 1)).is_err());
+}
+
+/// The code of a loaded file without its `fn main() {` line and its closing brace, so its body is inserted with the rest
+/// `async fn main` and the attributes above it (exp: `#[tokio::main]`) are removed with it, the comments and strings are kept as is
+pub fn remove_main(script: &str) -> String {
+    use rustc_lexer::TokenKind;
+    let mut tokens = vec![];
+    let mut pos = 0;
+    for token in rustc_lexer::tokenize(script) {
+        if !matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        ) {
+            tokens.push((token.kind, &script[pos..pos + token.len], pos));
+        }
+        pos += token.len;
+    }
+    let texts: Vec<&str> = tokens.iter().map(|token| token.1).collect();
+
+    // `fn main() {` outside of any block
+    let mut depth = 0usize;
+    let main_idx = (0..tokens.len()).find(|&idx| {
+        let is_main = depth == 0 && texts[idx..].starts_with(&["fn", "main", "(", ")", "{"]);
+        match tokens[idx].0 {
+            TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseBrace => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+        is_main
+    });
+    let Some(main_idx) = main_idx else {
+        return script.to_string();
+    };
+    let open_idx = main_idx + 4;
+
+    let mut start_idx = main_idx;
+    if start_idx > 0 && texts[start_idx - 1] == "async" {
+        start_idx -= 1;
+    }
+    // the outer attributes, `#[..]` but not `#![..]`
+    while start_idx > 0 && tokens[start_idx - 1].0 == TokenKind::CloseBracket {
+        let mut depth = 0;
+        let Some(open_bracket) = (0..start_idx).rev().find(|&idx| {
+            match tokens[idx].0 {
+                TokenKind::CloseBracket => depth += 1,
+                TokenKind::OpenBracket => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        }) else {
+            break;
+        };
+        if open_bracket == 0 || tokens[open_bracket - 1].0 != TokenKind::Pound {
+            break;
+        }
+        start_idx = open_bracket - 1;
+    }
+
+    let mut depth = 0;
+    let Some(close_idx) = (open_idx..tokens.len()).find(|&idx| {
+        match tokens[idx].0 {
+            TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseBrace => depth -= 1,
+            _ => (),
+        }
+        depth == 0
+    }) else {
+        return script.to_string();
+    };
+
+    let start = tokens[start_idx].2;
+    let (body_start, body_end) = (tokens[open_idx].2 + 1, tokens[close_idx].2);
+    format!(
+        "{}{}{}",
+        &script[..start],
+        &script[body_start..body_end],
+        &script[body_end + 1..]
+    )
+}
+
+#[test]
+fn remove_main_test() {
+    assert_eq!(
+        remove_main("use std::fmt;\nfn main() {\n    let a = 1;\n}\n").trim(),
+        "use std::fmt;\n\n    let a = 1;"
+    );
+    assert_eq!(
+        remove_main("#[tokio::main]\nasync fn main() {\n    run().await;\n}\n").trim(),
+        "run().await;"
+    );
+    assert_eq!(
+        remove_main(
+            "fn run() {}\n#[async_std::main]\n#[allow(unused)]\nasync fn main() {\n    if true { run() }\n}\n"
+        )
+        .trim(),
+        "fn run() {}\n\n    if true { run() }"
+    );
+    // the other attributes are kept
+    assert_eq!(
+        remove_main("#[derive(Debug)]\nstruct A;\n\nfn main() {}\n").trim(),
+        "#[derive(Debug)]\nstruct A;"
+    );
+    assert_eq!(remove_main("fn run() {}").trim(), "fn run() {}");
+    // the strings and the comments are kept, the braces in them don't count
+    assert_eq!(
+        remove_main("fn main() {\n    let u = \"https://docs.rs\"; // c\n    let b = \"{\";\n}\n/// doc\nfn f() {}\n"),
+        "\n    let u = \"https://docs.rs\"; // c\n    let b = \"{\";\n\n/// doc\nfn f() {}\n"
+    );
+    // a `main` in a block or in a string is not the main function
+    let code = "mod a { fn main() {} }\nconst S: &str = \"fn main() {\";";
+    assert_eq!(remove_main(code), code);
+    // the crate attributes are kept
+    assert_eq!(
+        remove_main("#![allow(unused)]\nfn main() {}").trim(),
+        "#![allow(unused)]"
+    );
+}
+
+pub struct StringTools {}
+
+impl StringTools {
+    pub fn _insert_at_char_idx(buffer: &mut String, idx: usize, character: char) {
+        let mut buffer_chars: Vec<char> = buffer.chars().collect();
+        buffer_chars.insert(idx, character);
+        *buffer = buffer_chars.into_iter().collect();
+    }
+
+    pub fn _remove_at_char_idx(buffer: &mut String, idx: usize) -> Option<char> {
+        let mut buffer_chars: Vec<char> = buffer.chars().collect();
+
+        let removed_char = if buffer_chars.len() > idx {
+            Some(buffer_chars.remove(idx))
+        } else {
+            None
+        };
+
+        *buffer = buffer_chars.into_iter().collect();
+
+        removed_char
+    }
+
+    pub fn chars_count(buffer: &str) -> usize {
+        buffer.chars().count()
+    }
+
+    pub fn new_lines_count(buffer: &str) -> usize {
+        buffer.chars().filter(|c| c == &'\n').count()
+    }
+
+    pub fn _is_multiline(string: &str) -> bool {
+        string.chars().filter(|c| *c == '\n').count() > 1
+    }
+
+    pub fn strings_unique(s1: &str, s2: &mut String) {
+        let mut idx = s2.len();
+        loop {
+            if s2.get(..idx).is_some() && !s2[..idx].is_empty() && s1.ends_with(&s2[..idx]) {
+                for _ in 0..idx {
+                    s2.remove(0);
+                }
+                break;
+            }
+            if idx == 0 {
+                if let Some(last_char) = s1.chars().last() {
+                    if last_char.is_alphanumeric() {
+                        s2.clear();
+                    }
+                }
+                break;
+            }
+
+            idx -= 1;
+        }
+    }
+
+    /// Is the input incomplete: a bracket, a string or a block comment is still open
+    /// The brackets in the literals and the comments don't count
+    pub fn unmatched_brackets(s: &str) -> bool {
+        use rustc_lexer::{LiteralKind, RawStrError, TokenKind};
+        let mut depth = [0; 3];
+        for token in rustc_lexer::tokenize(s) {
+            match token.kind {
+                TokenKind::OpenParen => depth[0] += 1,
+                TokenKind::CloseParen => depth[0] -= 1,
+                TokenKind::OpenBracket => depth[1] += 1,
+                TokenKind::CloseBracket => depth[1] -= 1,
+                TokenKind::OpenBrace => depth[2] += 1,
+                TokenKind::CloseBrace => depth[2] -= 1,
+                TokenKind::BlockComment {
+                    terminated: false, ..
+                }
+                | TokenKind::Literal {
+                    kind:
+                        LiteralKind::Str { terminated: false }
+                        | LiteralKind::ByteStr { terminated: false }
+                        | LiteralKind::RawStr {
+                            err: Some(RawStrError::NoTerminator { .. }),
+                            ..
+                        }
+                        | LiteralKind::RawByteStr {
+                            err: Some(RawStrError::NoTerminator { .. }),
+                            ..
+                        },
+                    ..
+                } => return true,
+                _ => (),
+            }
+        }
+        depth != [0; 3]
+    }
+}
+
+#[test]
+fn unmatched_brackets_test() {
+    assert!(StringTools::unmatched_brackets("fn f() {"));
+    assert!(StringTools::unmatched_brackets("struct P {\n    x: u8,"));
+    assert!(!StringTools::unmatched_brackets(
+        "struct P {\n    x: u8,\n}"
+    ));
+    // the brackets in literals and comments
+    assert!(!StringTools::unmatched_brackets("let s = \"{\"; // (\n"));
+    assert!(!StringTools::unmatched_brackets("let c = '{';"));
+    assert!(!StringTools::unmatched_brackets(
+        "let s = r#\"a \" {\"#; /* } */"
+    ));
+    // lifetimes are not quotes
+    assert!(!StringTools::unmatched_brackets(
+        "fn f<'a>(x: &'a str) -> &'a str { x }"
+    ));
+    // unterminated strings and comments
+    assert!(StringTools::unmatched_brackets("let s = \"multi\nline"));
+    assert!(StringTools::unmatched_brackets("let s = r#\"a\"\n"));
+    assert!(StringTools::unmatched_brackets("/* a comment\n"));
+}
+
+pub fn _read_until_bytes<R: std::io::BufRead + ?Sized>(
+    r: &mut R,
+    delim: &[u8],
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let mut nn = 0;
+    let mut b = [0; 512];
+    loop {
+        let n = r.read(&mut b)?;
+        buffer.extend(&b[..n]);
+        nn += n;
+        if n == 0 || buffer.ends_with(delim) {
+            break Ok(nn);
+        }
+    }
+}
+
+fn remove_comments(s: &str) -> String {
+    s.lines()
+        .filter(|l| !l.trim_start().starts_with("//"))
+        .map(|l| {
+            let mut quote = false;
+            let mut d_quote = false;
+
+            let mut l = l.chars().peekable();
+            let mut purged_line = String::new();
+
+            loop {
+                match (l.next(), l.peek()) {
+                    (Some('/'), Some('/')) => {
+                        if !quote && !d_quote {
+                            break;
+                        }
+                    }
+                    (Some('\''), _) => {
+                        quote = !quote;
+                        purged_line.push('\'');
+                    }
+                    (Some('"'), _) => {
+                        d_quote = !d_quote;
+                        purged_line.push('"');
+                    }
+                    (Some(c), _) => purged_line.push(c),
+                    _ => break,
+                }
+            }
+            purged_line + "\n"
+        })
+        .collect()
+}
+
+/// Answer of a `[y/N]` prompt, `None` means the key should be ignored
+pub fn confirmation_answer(key: crossterm::event::KeyCode) -> Option<bool> {
+    use crossterm::event::KeyCode;
+    match key {
+        KeyCode::Char('y' | 'Y') => Some(true),
+        KeyCode::Char(_) | KeyCode::Enter | KeyCode::Esc => Some(false),
+        _ => None,
+    }
+}
+
+#[test]
+fn confirmation_answer_test() {
+    use crossterm::event::KeyCode;
+    assert_eq!(confirmation_answer(KeyCode::Char('y')), Some(true));
+    assert_eq!(confirmation_answer(KeyCode::Char('Y')), Some(true));
+    // No is the default
+    assert_eq!(confirmation_answer(KeyCode::Enter), Some(false));
+    assert_eq!(confirmation_answer(KeyCode::Char('n')), Some(false));
+    assert_eq!(confirmation_answer(KeyCode::Char('x')), Some(false));
+    assert_eq!(confirmation_answer(KeyCode::Left), None);
+}
+
+/// The answer to a destructive command confirmation when it doesn't need the prompt:
+/// yes if `confirm-destructive` is off or `--yes` is passed, no in a non-interactive session
+pub fn destructive_confirmation(enabled: bool, buffer: &str, interactive: bool) -> Option<bool> {
+    if !enabled || buffer.split_whitespace().any(|arg| arg == "--yes") {
+        Some(true)
+    } else if !interactive {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn destructive_confirmation_test() {
+    assert_eq!(destructive_confirmation(false, ":reset", false), Some(true));
+    assert_eq!(
+        destructive_confirmation(true, ":reset --yes", false),
+        Some(true)
+    );
+    assert_eq!(destructive_confirmation(true, ":reset", true), None);
+
+    // a script can't answer the prompt, so `:reset` is declined and the repl code is kept
+    let mut repl = irust_repl::Repl::default();
+    repl.insert("let kept = 1;");
+    let body = repl.body();
+    if destructive_confirmation(true, ":reset", false) != Some(false) {
+        repl.reset().unwrap();
+    }
+    assert_eq!(repl.body(), body);
+    if destructive_confirmation(true, ":reset --yes", false) == Some(true) {
+        repl.reset().unwrap();
+    }
+    assert!(!repl.body().contains("let kept = 1;"));
+}
+
+/// In `:multiline` mode, Enter inserts a new line and Alt+Enter submits the input
+pub fn multiline_submits(modifiers: crossterm::event::KeyModifiers) -> bool {
+    modifiers.contains(crossterm::event::KeyModifiers::ALT)
+}
+
+#[test]
+fn multiline_submits_test() {
+    use crossterm::event::KeyModifiers;
+    // Enter a multi-line function, auto-detection would have submitted `fn one() -> u8 { 1 }`
+    let keys = [
+        ("fn one() -> u8 { 1 }", KeyModifiers::NONE),
+        ("fn two() -> u8 { 2 }", KeyModifiers::NONE),
+        ("one() + two()", KeyModifiers::ALT),
+    ];
+    let mut buffer = printer::buffer::Buffer::new();
+    for (line, enter_modifiers) in keys {
+        line.chars().for_each(|c| buffer.insert(c));
+        if multiline_submits(enter_modifiers) {
+            break;
+        }
+        buffer.insert('\n');
+    }
+    assert_eq!(
+        buffer.to_string(),
+        "fn one() -> u8 { 1 }\nfn two() -> u8 { 2 }\none() + two()"
+    );
+    assert!(!multiline_submits(KeyModifiers::SHIFT));
+}
+
+pub fn ctrlc_cancel(process: &mut std::process::Child) -> Result<()> {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
+    // Running a command as Command::new().output takes at minimum 1ms
+    // So Polling should take a similar order of magnitude
+    if let Ok(event) = crossterm::event::poll(std::time::Duration::from_millis(1)) {
+        if event {
+            if let Ok(event) = crossterm::event::read() {
+                match event {
+                    Event::Key(KeyEvent {
+                        kind: KeyEventKind::Release,
+                        ..
+                    }) => (),
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('c'),
+                        modifiers: crossterm::event::KeyModifiers::CONTROL,
+                        ..
+                    }) => {
+                        process.kill()?;
+                        return Err("Cancelled!".into());
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char(a),
+                        ..
+                    }) => {
+                        use std::io::Write;
+                        // Ignore write errors (process might have ended)
+                        let _ = process.stdin.as_mut().unwrap().write_all(&[a as u8]);
+                    }
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Enter,
+                        ..
+                    }) => {
+                        use std::io::Write;
+                        // Ignore write errors (process might have ended)
+                        let _ = process.stdin.as_mut().unwrap().write_all(b"\n");
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Open a url or a file with the system default program
+pub fn open_in_browser(target: &str) -> Result<()> {
+    #[cfg(windows)]
+    std::process::Command::new("cmd")
+        .args(["/C", "start", "", target])
+        .spawn()?;
+    #[cfg(target_os = "macos")]
+    std::process::Command::new("open").arg(target).spawn()?;
+    #[cfg(all(unix, not(target_os = "macos")))]
+    std::process::Command::new("xdg-open")
+        .arg(target)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// The documentation url of `:doc <path>`, the std crates are on doc.rust-lang.org and the others on docs.rs
+/// An item path is searched since its page depends on its kind (exp: `struct.Vec.html`)
+/// exp: `serde` => `https://docs.rs/serde`, `std::vec::Vec` => `https://doc.rust-lang.org/std/?search=std::vec::Vec`
+pub fn doc_url(path: &str) -> Result<String> {
+    let path = path.trim().trim_start_matches("::");
+    let (krate, item) = path.split_once("::").unwrap_or((path, ""));
+    let is_path = |s: &str| {
+        s.split("::").all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+    };
+    if !is_path(path) {
+        return Err(format!("Invalid path: `{path}`").into());
+    }
+    Ok(match (krate, item) {
+        ("std" | "core" | "alloc" | "proc_macro" | "test", "") => {
+            format!("https://doc.rust-lang.org/{krate}/")
+        }
+        ("std" | "core" | "alloc" | "proc_macro" | "test", _) => {
+            format!("https://doc.rust-lang.org/{krate}/?search={path}")
+        }
+        (krate, "") => format!("https://docs.rs/{krate}"),
+        (krate, item) => {
+            let krate_path = krate.replace('-', "_");
+            format!("https://docs.rs/{krate}/latest/{krate_path}/?search={item}")
+        }
+    })
+}
+
+#[test]
+fn doc_url_test() {
+    assert_eq!(doc_url("serde").unwrap(), "https://docs.rs/serde");
+    assert_eq!(
+        doc_url("serde_json::Value").unwrap(),
+        "https://docs.rs/serde_json/latest/serde_json/?search=Value"
+    );
+    assert_eq!(
+        doc_url("async-std::task").unwrap(),
+        "https://docs.rs/async-std/latest/async_std/?search=task"
+    );
+    assert_eq!(doc_url("std").unwrap(), "https://doc.rust-lang.org/std/");
+    assert_eq!(
+        doc_url("std::vec::Vec").unwrap(),
+        "https://doc.rust-lang.org/std/?search=std::vec::Vec"
+    );
+    assert!(doc_url("std::").is_err());
+    assert!(doc_url("a b").is_err());
+}
+
+pub fn find_workpace_root(metadata: String) -> Option<String> {
+    let start = metadata.find("workspace_root")? + 17;
+    let end = metadata[start..].find('"')?;
+    Some(metadata[start..start + end].to_string())
+}
+
+pub fn patch_name_to(path: &Path, name: &str) -> Result<()> {
+    let toml = std::fs::read_to_string(path)?;
+    let mut patched: String = String::new();
+    for line in toml.lines() {
+        if line.starts_with("name =") {
+            patched.push_str(&format!("name = \"{name}\""));
+            patched.push('\n');
+        } else {
+            patched.push_str(line);
+            patched.push('\n');
+        }
+    }
+    std::fs::write(path, patched)?;
+    Ok(())
+}
+
+/// A cargo package name for the crate in `dir`, exp: `/tmp/my experiment` => `my_experiment`
+pub fn package_name_of(dir: &Path) -> String {
+    let name: String = dir
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // a package name can't start with a digit
+    if name.is_empty() {
+        "irust_export".to_string()
+    } else if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name
+    } else {
+        format!("irust_{name}")
+    }
+}
+
+#[test]
+fn package_name_of_test() {
+    assert_eq!(
+        package_name_of(Path::new("/tmp/my experiment")),
+        "my_experiment"
+    );
+    assert_eq!(package_name_of(Path::new("/tmp/my-app")), "my-app");
+    assert_eq!(package_name_of(Path::new("/tmp/2024")), "irust_2024");
+    assert_eq!(package_name_of(Path::new("/")), "irust_export");
+}
+
+pub fn copy_dir(src_path: &Path, out_path: &Path) -> Result<()> {
+    if src_path.is_file() {
+        panic!("Incorrect usage")
+    }
+    let convert_path =
+        |path: &Path| -> Result<PathBuf> { Ok(out_path.join(path.strip_prefix(src_path)?)) };
+    let dcb = |dp: PathBuf| Ok(std::fs::create_dir(convert_path(&dp)?)?);
+    let fcb = |fp: PathBuf| Ok(std::fs::copy(fp.clone(), convert_path(&fp)?)?);
+    visit_dirs(src_path, &dcb, &fcb)
+}
+
+fn visit_dirs(
+    dir: &Path,
+    dcb: &dyn Fn(PathBuf) -> Result<()>,
+    fcb: &dyn Fn(PathBuf) -> Result<u64>,
+) -> Result<()> {
+    if dir.is_dir() {
+        dcb(dir.to_path_buf())?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_dir() {
+                visit_dirs(&path, dcb, fcb)?;
+            } else {
+                fcb(entry.path())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The total size of the files under `dir` and the number of compiled crates (the rlibs in the `deps` dirs)
+pub fn dir_size(dir: &Path) -> std::io::Result<(u64, usize)> {
+    let (mut size, mut crates) = (0, 0);
+    if !dir.exists() {
+        return Ok((size, crates));
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // don't follow symlinks
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            let (dir_size, dir_crates) = dir_size(&entry.path())?;
+            size += dir_size;
+            crates += dir_crates;
+        } else {
+            size += metadata.len();
+            if dir.ends_with("deps") && entry.path().extension() == Some("rlib".as_ref()) {
+                crates += 1;
+            }
+        }
+    }
+    Ok((size, crates))
+}
+
+/// exp: 1536 => 1.5 KiB
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[test]
+fn dir_size_test() {
+    let dir = std::env::temp_dir().join("irust_dir_size_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("debug/deps")).unwrap();
+    fs::write(dir.join("a"), [0; 10]).unwrap();
+    fs::write(dir.join("debug/deps/libfoo.rlib"), [0; 100]).unwrap();
+    fs::write(dir.join("debug/deps/foo.d"), [0; 5]).unwrap();
+    assert_eq!(dir_size(&dir).unwrap(), (115, 1));
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(dir_size(&dir).unwrap(), (0, 0));
+
+    assert_eq!(human_size(10), "10 B");
+    assert_eq!(human_size(1536), "1.5 KiB");
+    assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+}
+
+/// The char indices of the (non overlapping) occurrences of `pattern` in `line`
+pub fn find_matches(line: &str, pattern: &str, ignore_case: bool) -> Vec<usize> {
+    let eq = |a: char, b: char| {
+        if ignore_case {
+            a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+    let line: Vec<char> = line.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    if pattern.is_empty() {
+        return vec![];
+    }
+
+    let mut indices = vec![];
+    let mut idx = 0;
+    while idx + pattern.len() <= line.len() {
+        if line[idx..idx + pattern.len()]
+            .iter()
+            .zip(&pattern)
+            .all(|(a, b)| eq(*a, *b))
+        {
+            indices.extend(idx..idx + pattern.len());
+            idx += pattern.len();
+        } else {
+            idx += 1;
+        }
+    }
+    indices
+}
+
+#[test]
+fn find_matches_test() {
+    let body = [
+        "fn main() {",
+        "fn add(a: u8) -> u8 { a + 1 }",
+        "let a = add(1);",
+        "}",
+    ];
+    let matches: Vec<_> = body
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| (idx, find_matches(line, "fn add", false)))
+        .filter(|(_, indices)| !indices.is_empty())
+        .collect();
+    assert_eq!(matches, vec![(1, vec![0, 1, 2, 3, 4, 5])]);
+
+    assert_eq!(find_matches("Add add", "add", false), vec![4, 5, 6]);
+    assert_eq!(find_matches("Add add", "add", true), vec![0, 1, 2, 4, 5, 6]);
+    assert_eq!(find_matches("aaa", "aa", false), vec![0, 1]);
+    assert!(find_matches("fn main() {", "", false).is_empty());
+}
+
+/// Is the input a statement that should be inserted in the repl (instead of evaluated)
+pub fn is_statement(buffer_trimmed: &str, auto_insert_semicolon: bool) -> bool {
+    buffer_trimmed.ends_with(';')
+        || auto_insert_semicolon
+            // These patterns are used to detect statements that don't require to be terminated with ';'
+            // Note: `loop` can return a value so we don't add it here, exp: `loop {break 4}`
+            && match buffer_trimmed
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                // async fn|const fn|unsafe fn
+                [_, "fn", ..]
+                | ["fn", ..]
+                | ["enum", ..]
+                | ["struct", ..]
+                | ["trait", ..]
+                | ["impl", ..]
+                | ["pub", ..]
+                | ["extern", ..]
+                | ["macro", ..] => true,
+                | ["macro_rules!", ..] => true,
+                // attribute exp:
+                // #[derive(Debug)]
+                // struct B{}
+                [tag, ..] if tag.starts_with('#') => true,
+                _ => false,
+            }
+}
+
+/// Read the system clipboard using the platform clipboard tool
+pub fn read_clipboard() -> Result<String> {
+    let tools: &[&[&str]] = if cfg!(windows) {
+        &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"]]
+    } else if cfg!(target_os = "macos") {
+        &[&["pbpaste"]]
+    } else {
+        &[
+            &["wl-paste", "--no-newline"],
+            &["xclip", "-selection", "clipboard", "-o"],
+            &["xsel", "--clipboard", "--output"],
+        ]
+    };
+    for tool in tools {
+        // Try the next tool if this one is not installed or failed (exp: wl-paste outside of wayland)
+        let Ok(output) = std::process::Command::new(tool[0])
+            .args(&tool[1..])
+            .output()
+        else {
+            continue;
+        };
+        if output.status.success() {
+            return String::from_utf8(output.stdout)
+                .map_err(|_| "The clipboard doesn't contain text".into());
+        }
+    }
+    let tools: Vec<_> = tools.iter().map(|tool| tool[0]).collect();
+    Err(format!(
+        "Failed to read the clipboard (requires one of: {})",
+        tools.join(", ")
+    )
+    .into())
+}
+
+/// Write `text` to the system clipboard using the platform clipboard tool
+pub fn write_clipboard(text: &str) -> Result<()> {
+    use std::io::Write;
+    let tools: &[&[&str]] = if cfg!(windows) {
+        &[&["clip"]]
+    } else if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    };
+    for tool in tools {
+        // Try the next tool if this one is not installed or failed (exp: wl-copy outside of wayland)
+        let Ok(mut child) = std::process::Command::new(tool[0])
+            .args(&tool[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        if child.wait().is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+    }
+    let tools: Vec<_> = tools.iter().map(|tool| tool[0]).collect();
+    Err(format!(
+        "Failed to write to the clipboard (requires one of: {})",
+        tools.join(", ")
+    )
+    .into())
+}
+
+/// The first line of `text` shortened to `max` chars, exp: to confirm what was copied
+pub fn preview(text: &str, max: usize) -> String {
+    let first_line = text.lines().next().unwrap_or_default();
+    if first_line.chars().count() > max || text.trim_end().lines().count() > 1 {
+        first_line.chars().take(max).collect::<String>() + "..."
+    } else {
+        first_line.to_string()
+    }
+}
+
+#[test]
+fn preview_test() {
+    assert_eq!(preview("42", 10), "42");
+    assert_eq!(preview("42\n", 10), "42");
+    assert_eq!(preview("0123456789abc", 10), "0123456789...");
+    assert_eq!(preview("a\nb", 10), "a...");
+    assert_eq!(preview("", 10), "");
+}
+
+/// How many of the last `lines` make up the last inserted code block, 0 if there are no lines
+/// It's the shortest suffix with balanced delimiters that follows the end of a statement,
+/// exp: a multiline function or a method chain split on multiple lines
+pub fn last_block_len(lines: &[String]) -> usize {
+    let is_balanced = |code: &str| {
+        let mut depth = 0i32;
+        for token in rustc_lexer::tokenize(code) {
+            match token.kind {
+                rustc_lexer::TokenKind::OpenBrace
+                | rustc_lexer::TokenKind::OpenParen
+                | rustc_lexer::TokenKind::OpenBracket => depth += 1,
+                rustc_lexer::TokenKind::CloseBrace
+                | rustc_lexer::TokenKind::CloseParen
+                | rustc_lexer::TokenKind::CloseBracket => {
+                    depth -= 1;
+                    if depth < 0 {
+                        return false;
+                    }
+                }
+                _ => (),
+            }
+        }
+        depth == 0
+    };
+    let ends_statement = |line: &str| {
+        let line = line.trim_end();
+        line.is_empty() || line.ends_with([';', '}', '{'])
+    };
+    (1..=lines.len())
+        .find(|&len| {
+            let start = lines.len() - len;
+            is_balanced(&lines[start..].join("\n"))
+                && (start == 0 || ends_statement(&lines[start - 1]))
+        })
+        .unwrap_or(lines.len())
+}
+
+#[test]
+fn last_block_len_test() {
+    let lines = |code: &str| code.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+    assert_eq!(last_block_len(&[]), 0);
+    assert_eq!(last_block_len(&lines("let a = 1;\nlet b = 2;")), 1);
+    assert_eq!(
+        last_block_len(&lines("let a = 1;\nfn b() -> u8 {\n    2\n}")),
+        3
+    );
+    assert_eq!(
+        last_block_len(&lines("let a = 1;\nlet v = vec![\n    1,\n];")),
+        3
+    );
+    // each line is balanced but it's one statement
+    assert_eq!(
+        last_block_len(&lines("let a = 1;\nlet s = a\n    .pow(2)\n    .min(3);")),
+        3
+    );
+    assert_eq!(
+        last_block_len(&lines("let a = 1;\n#[derive(Debug)]\nstruct A;")),
+        2
+    );
+}
+
+/// The ranges of the inserted code blocks of `lines` (see `last_block_len`), in order
+pub fn code_blocks(lines: &[String]) -> Vec<std::ops::Range<usize>> {
+    let mut blocks = vec![];
+    let mut end = lines.len();
+    while end > 0 {
+        let start = end - last_block_len(&lines[..end]);
+        blocks.push(start..end);
+        end = start;
+    }
+    blocks.reverse();
+    blocks
+}
+
+#[test]
+fn code_blocks_test() {
+    let lines = |code: &str| code.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+    assert!(code_blocks(&[]).is_empty());
+    assert_eq!(
+        code_blocks(&lines("let a = 1;\nfn b() -> u8 {\n    2\n}\nlet c = b();")),
+        [0..1, 1..4, 4..5]
+    );
+}
+
+/// The state set by an `on|off` command argument, None without an argument (to show the state)
+pub fn parse_toggle(arg: &str) -> Result<Option<bool>> {
+    match arg.trim() {
+        "on" => Ok(Some(true)),
+        "off" => Ok(Some(false)),
+        "" => Ok(None),
+        arg => Err(format!("Invalid argument: `{arg}`").into()),
+    }
+}
+
+#[test]
+fn parse_toggle_test() {
+    assert_eq!(parse_toggle(" on").unwrap(), Some(true));
+    assert_eq!(parse_toggle(" off ").unwrap(), Some(false));
+    assert_eq!(parse_toggle("").unwrap(), None);
+    assert!(parse_toggle(" yes").is_err());
+}
+
+/// The type annotation of the `let` binding of `name` that is in scope at the end of `code`
+/// exp: `let s: &'static str = "hi";` => `&'static str`, rustc diagnostics drop the lifetimes
+/// Returns `None` if that binding has no annotation
+pub fn annotated_type(code: &str, name: &str) -> Option<String> {
+    use rustc_lexer::TokenKind;
+    let mut tokens = vec![];
+    let mut pos = 0;
+    for token in rustc_lexer::tokenize(code) {
+        if !matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        ) {
+            tokens.push((token.kind, &code[pos..pos + token.len], pos));
+        }
+        pos += token.len;
+    }
+
+    // the annotation of `name` in every open block, a block closes its bindings
+    let mut scopes: Vec<Option<Option<String>>> = vec![None];
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match tokens[idx] {
+            (TokenKind::OpenBrace, ..) => scopes.push(None),
+            (TokenKind::CloseBrace, ..) if scopes.len() > 1 => {
+                scopes.pop();
+            }
+            (TokenKind::Ident, "let", _) => {
+                idx += 1;
+                if tokens.get(idx).map(|t| t.1) == Some("mut") {
+                    idx += 1;
+                }
+                if tokens.get(idx).map(|t| t.1) != Some(name) {
+                    continue;
+                }
+                let annotation = if tokens.get(idx + 1).map(|t| t.0) == Some(TokenKind::Colon) {
+                    let start = idx + 2;
+                    let mut end = start;
+                    let mut depth = 0;
+                    while let Some((kind, ..)) = tokens.get(end) {
+                        match kind {
+                            TokenKind::Lt | TokenKind::OpenParen | TokenKind::OpenBracket => {
+                                depth += 1
+                            }
+                            // `->` in fn types
+                            TokenKind::Gt if tokens[end - 1].0 == TokenKind::Minus => (),
+                            TokenKind::Gt | TokenKind::CloseParen | TokenKind::CloseBracket => {
+                                depth -= 1
+                            }
+                            TokenKind::Eq | TokenKind::Semi if depth == 0 => break,
+                            _ => (),
+                        }
+                        end += 1;
+                    }
+                    let (_, last, last_pos) = tokens.get(end.checked_sub(1)?)?;
+                    let ty = &code[tokens.get(start)?.2..last_pos + last.len()];
+                    // a partial annotation (exp: `Vec<_>`) doesn't tell the type
+                    Some(ty.split_whitespace().collect::<Vec<_>>().join(" "))
+                        .filter(|_| !tokens[start..end].iter().any(|t| t.1 == "_"))
+                } else {
+                    None
+                };
+                *scopes.last_mut().expect("there is always a scope") = Some(annotation);
+                continue;
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    scopes.into_iter().rev().flatten().next().flatten()
+}
+
+#[test]
+fn annotated_type_test() {
+    let code = "\
+let s: &'static str = \"hi\";
+let mut v: Vec<Option<u8>> = Vec::new();
+let f: fn(u8) -> u8 = |x| x;
+let m : std::collections::HashMap<String, [u8; 2]> = Default::default();
+fn g() { let s: u8 = 1; }
+let n = 1;
+{
+    let n: u16 = 2;
+}";
+    assert_eq!(annotated_type(code, "s").as_deref(), Some("&'static str"));
+    assert_eq!(
+        annotated_type(code, "v").as_deref(),
+        Some("Vec<Option<u8>>")
+    );
+    assert_eq!(annotated_type(code, "f").as_deref(), Some("fn(u8) -> u8"));
+    assert_eq!(
+        annotated_type(code, "m").as_deref(),
+        Some("std::collections::HashMap<String, [u8; 2]>")
+    );
+    // the annotation in the closed block doesn't apply
+    assert_eq!(annotated_type(code, "n"), None);
+    assert_eq!(annotated_type(code, "nope"), None);
+    assert_eq!(annotated_type("let p: Vec<_> = vec![1];", "p"), None);
+    // shadowing
+    let code = format!("{code}\nlet s = 5;");
+    assert_eq!(annotated_type(&code, "s"), None);
+}
+
+/// Show the types registered with `:alias-type` with their alias
+/// A type matches with or without its paths, since rustc doesn't always print them
+/// exp: with `MyMap = std::collections::HashMap<String, u8>`, `Vec<HashMap<String, u8>>` => `Vec<MyMap>`
+pub fn apply_type_display_aliases(
+    ty: &str,
+    aliases: &std::collections::BTreeMap<String, String>,
+) -> String {
+    let mut aliases: Vec<(&String, &String)> = aliases.iter().collect();
+    // the longest types first, so a type inside a longer one doesn't break it
+    aliases.sort_by_key(|(_, full)| std::cmp::Reverse(full.len()));
+    let mut ty = ty.to_string();
+    for (alias, full) in aliases {
+        let full = full.split_whitespace().collect::<Vec<_>>().join(" ");
+        ty = replace_type(&ty, &full, alias);
+        ty = replace_type(&ty, &strip_paths(&full), alias);
+    }
+    ty
+}
+
+/// Replace `from` with `to` where `from` is a whole type, exp: not the `u8` in `u80` or in `a::u8`
+fn replace_type(ty: &str, from: &str, to: &str) -> String {
+    if from.is_empty() {
+        return ty.to_string();
+    }
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut replaced = String::new();
+    let mut rest = ty;
+    while let Some(idx) = rest.find(from) {
+        let (before, after) = (&rest[..idx], &rest[idx + from.len()..]);
+        let whole = !before.ends_with(|c| is_ident(c) || c == ':') && !after.starts_with(is_ident);
+        replaced += before;
+        replaced += if whole { to } else { from };
+        rest = after;
+    }
+    replaced + rest
+}
+
+/// Remove the paths from a type, exp: `std::vec::Vec<std::string::String>` => `Vec<String>`
+pub fn strip_paths(ty: &str) -> String {
+    let mut stripped = String::new();
+    let mut ident = String::new();
+    let mut chars = ty.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || c == '_' {
+            ident.push(c);
+        } else if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            // `<T as Add>::Output` is an associated type, not a path
+            if ident.is_empty() && stripped.ends_with('>') {
+                stripped += "::";
+            }
+            ident.clear();
+        } else {
+            stripped += &ident;
+            ident.clear();
+            stripped.push(c);
+        }
+    }
+    stripped + &ident
+}
+
+#[test]
+fn apply_type_display_aliases_test() {
+    let aliases = [
+        ("MyLong", "std::collections::HashMap<String, Vec<i32>>"),
+        ("Bytes", "Vec<u8>"),
+    ]
+    .into_iter()
+    .map(|(alias, full)| (alias.to_string(), full.to_string()))
+    .collect();
+    assert_eq!(
+        apply_type_display_aliases("HashMap<String, Vec<i32>>", &aliases),
+        "MyLong"
+    );
+    assert_eq!(
+        apply_type_display_aliases(
+            "Option<std::collections::HashMap<String, Vec<i32>>>",
+            &aliases
+        ),
+        "Option<MyLong>"
+    );
+    assert_eq!(
+        apply_type_display_aliases("(Vec<u8>, alloc::vec::Vec<u8>)", &aliases),
+        "(Bytes, alloc::vec::Vec<u8>)"
+    );
+    assert_eq!(apply_type_display_aliases("u8", &aliases), "u8");
+
+    // the type as shown by `:type`
+    let mut repl = irust_repl::Repl::default();
+    let toolchain = repl.toolchain();
+    let cargo = repl.cargo.clone();
+    let (_, output) = repl
+        .eval_in_tmp_repl(
+            "let _:() = std::collections::HashMap::<String, Vec<i32>>::new();".into(),
+            |_| Ok(cargo.cargo_build_output(false, false, toolchain)?),
+        )
+        .unwrap();
+    let found = output
+        .lines()
+        .find_map(|line| line.split_once("expected `()`, found `"))
+        .and_then(|(_, ty)| ty.strip_suffix('`'))
+        .unwrap();
+    assert_eq!(apply_type_display_aliases(found, &aliases), "MyLong");
+}
+
+#[test]
+fn strip_paths_test() {
+    assert_eq!(
+        strip_paths("std::vec::Vec<std::string::String>"),
+        "Vec<String>"
+    );
+    assert_eq!(strip_paths("&'static str"), "&'static str");
+    assert_eq!(
+        strip_paths("<T as std::ops::Add>::Output"),
+        "<T as Add>::Output"
+    );
+    assert_eq!(
+        strip_paths("<std::vec::Vec<u8> as std::iter::IntoIterator>::IntoIter"),
+        "<Vec<u8> as IntoIterator>::IntoIter"
+    );
+}
+
+/// The (line, execution count) of the instrumented lines of the `file` in a lcov report
+pub fn parse_lcov(report: &str, file: &str) -> Vec<(usize, u64)> {
+    let mut lines = vec![];
+    let mut in_file = false;
+    for line in report.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            in_file = Path::new(path).ends_with(file);
+        } else if line == "end_of_record" {
+            in_file = false;
+        } else if let Some(data) = line.strip_prefix("DA:").filter(|_| in_file) {
+            let mut data = data.split(',');
+            if let (Some(Ok(line)), Some(Ok(count))) =
+                (data.next().map(str::parse), data.next().map(str::parse))
+            {
+                lines.push((line, count));
+            }
+        }
+    }
+    lines
+}
+
+#[test]
+fn parse_lcov_test() {
+    // fn covered() {} fn uncovered() {} and a test that calls `covered`
+    let report = "\
+SF:/tmp/irust_host_repl_x/src/lib.rs
+FN:1,covered
+FN:4,uncovered
+FNDA:1,covered
+FNDA:0,uncovered
+DA:1,1
+DA:2,1
+DA:4,0
+DA:5,0
+DA:8,1
+LF:5
+LH:3
+end_of_record
+SF:/tmp/irust_host_repl_x/src/other.rs
+DA:1,0
+end_of_record
+";
+    let lines = parse_lcov(report, "src/lib.rs");
+    assert_eq!(lines, [(1, 1), (2, 1), (4, 0), (5, 0), (8, 1)]);
+    let uncovered: Vec<_> = lines
+        .iter()
+        .filter(|(_, count)| *count == 0)
+        .map(|(line, _)| *line)
+        .collect();
+    assert_eq!(uncovered, [4, 5]);
+}
+
+/// Is the input a single call of a macro that returns `()` (exp: `println!("{x}")`)
+pub fn is_unit_macro_call(input: &str) -> bool {
+    use rustc_lexer::TokenKind;
+    const UNIT_MACROS: &[&str] = &[
+        "print",
+        "println",
+        "eprint",
+        "eprintln",
+        "assert",
+        "assert_eq",
+        "assert_ne",
+        "debug_assert",
+        "debug_assert_eq",
+        "debug_assert_ne",
+    ];
+    let input = input.trim();
+    let mut tokens = rustc_lexer::tokenize(input).filter(|token| {
+        !matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        )
+    });
+    let (Some(name), Some(bang)) = (tokens.next(), tokens.next()) else {
+        return false;
+    };
+    if !UNIT_MACROS.contains(&&input[..name.len]) || bang.kind != TokenKind::Bang {
+        return false;
+    }
+    // the macro arguments have to be the rest of the input
+    let mut depth = 0;
+    for token in tokens {
+        if depth == 0
+            && token.kind != TokenKind::OpenParen
+            && token.kind != TokenKind::OpenBracket
+            && token.kind != TokenKind::OpenBrace
+        {
+            return false;
+        }
+        match token.kind {
+            TokenKind::OpenParen | TokenKind::OpenBracket | TokenKind::OpenBrace => depth += 1,
+            TokenKind::CloseParen | TokenKind::CloseBracket | TokenKind::CloseBrace => depth -= 1,
+            _ => (),
+        }
+    }
+    depth == 0
+}
+
+/// Evaluates the input without printing its `()` result, exp: for `is_unit_macro_call` inputs
+pub fn unit_macro_evaluator() -> [String; 2] {
+    ["{\n".to_string(), "\n};".to_string()]
+}
+
+#[test]
+fn is_unit_macro_call_test() {
+    assert!(is_unit_macro_call("println!(\"{}\", x)"));
+    assert!(is_unit_macro_call(" print!(\"a\") "));
+    assert!(is_unit_macro_call("assert_eq!(1, 1)"));
+    assert!(is_unit_macro_call("println! { \"{}\", (1) }"));
+
+    assert!(!is_unit_macro_call("println!(\"a\"); 5"));
+    assert!(!is_unit_macro_call("println!(\"a\") == ()"));
+    assert!(!is_unit_macro_call("format!(\"a\")"));
+    assert!(!is_unit_macro_call("println"));
+    assert!(!is_unit_macro_call("()"));
+
+    let mut repl = irust_repl::Repl::default();
+    let eval = |repl: &mut irust_repl::Repl, evaluator: &[String]| {
+        repl.eval_with_configuration(irust_repl::EvalConfig {
+            input: "println!(\"hello\")",
+            interactive_function: None,
+            color: false,
+            evaluator,
+            compile_mode: irust_repl::CompileMode::Debug,
+        })
+        .unwrap()
+        .output
+    };
+    assert_eq!(
+        eval(&mut repl, &*irust_repl::DEFAULT_EVALUATOR),
+        "hello\n()"
+    );
+    assert_eq!(eval(&mut repl, &unit_macro_evaluator()), "hello");
+}
+
+/// One line per startup step with its duration, `None` is a step that is still running
+pub fn format_startup_timings(timings: &[(&str, Option<std::time::Duration>)]) -> String {
+    let width = timings
+        .iter()
+        .map(|(step, _)| step.len())
+        .max()
+        .unwrap_or(0);
+    timings
+        .iter()
+        .map(|(step, duration)| match duration {
+            Some(duration) => format!("{step:<width$}  {:>8.2}ms", duration.as_secs_f64() * 1000.),
+            None => format!("{step:<width$}  {:>10}", "running"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn format_startup_timings_test() {
+    use std::time::Duration;
+    assert_eq!(
+        format_startup_timings(&[
+            ("config load", Some(Duration::from_micros(1500))),
+            ("project scaffold", Some(Duration::from_millis(120))),
+            ("first build", None),
+        ]),
+        "\
+config load           1.50ms
+project scaffold    120.00ms
+first build          running"
+    );
+}
+
+/// The channel toolchains (stable, beta, nightly) in the output of `rustup toolchain list`
+/// The dated and custom toolchains are ignored since they can't be selected with `+channel`
+pub fn installed_toolchains(rustup_list: &str) -> Vec<irust_repl::ToolChain> {
+    use irust_repl::ToolChain;
+    let installed: Vec<&str> = rustup_list
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    [ToolChain::Stable, ToolChain::Beta, ToolChain::Nightly]
+        .into_iter()
+        .filter(|toolchain| {
+            let channel = toolchain.to_string();
+            installed.iter().any(|name| {
+                name.strip_prefix(&channel).is_some_and(|host| {
+                    // exp: `nightly-x86_64-unknown-linux-gnu` but not `nightly-2024-01-01-x86_64-unknown-linux-gnu`
+                    host.is_empty()
+                        || host
+                            .strip_prefix('-')
+                            .is_some_and(|host| !host.starts_with(|c: char| c.is_ascii_digit()))
+                })
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn installed_toolchains_test() {
+    use irust_repl::ToolChain;
+    let list = "\
+stable-x86_64-unknown-linux-gnu (default)
+nightly-2024-01-01-x86_64-unknown-linux-gnu
+nightly-x86_64-unknown-linux-gnu (active)
+my-custom-toolchain
+";
+    assert!(matches!(
+        installed_toolchains(list).as_slice(),
+        [ToolChain::Stable, ToolChain::Nightly]
+    ));
+    assert!(installed_toolchains("beta").len() == 1);
+    assert!(installed_toolchains("no installed toolchains").is_empty());
+
+    // the same expression evaluated with each installed toolchain
+    let Ok(output) = std::process::Command::new("rustup")
+        .args(["toolchain", "list"])
+        .output()
+    else {
+        return;
+    };
+    let mut repl = irust_repl::Repl::default();
+    for toolchain in installed_toolchains(&String::from_utf8_lossy(&output.stdout)) {
+        repl.set_toolchain(toolchain);
+        assert_eq!(repl.eval("1 + 2").unwrap().output, "3");
+        let error = repl.eval("1 + \"a\"").unwrap();
+        assert!(!error.status.success());
+        assert!(error.output.contains("E0277"));
+    }
+}
+
+/// One line per note, prefixed with the session time when it was added, exp: `[01:02:05] try with a Vec`
+pub fn format_notes(notes: &[(std::time::Duration, String)]) -> String {
+    notes
+        .iter()
+        .map(|(time, note)| {
+            let secs = time.as_secs();
+            format!(
+                "[{:02}:{:02}:{:02}] {note}",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn format_notes_test() {
+    use std::time::Duration;
+    let mut notes = vec![];
+    assert_eq!(format_notes(&notes), "");
+    notes.push((Duration::from_millis(5_900), "start".to_string()));
+    notes.push((Duration::from_secs(3725), "try with a Vec".to_string()));
+    assert_eq!(
+        format_notes(&notes),
+        "[00:00:05] start\n[01:02:05] try with a Vec"
+    );
+}
+
+/// The `let` statements of `code` that bind one of `names` and are in scope at its end
+/// exp: to insert the bindings kept with `:keep` again after `:reset --keep`
+pub fn binding_statements(code: &str, names: &std::collections::BTreeSet<String>) -> Vec<String> {
+    use rustc_lexer::TokenKind;
+    let mut tokens = vec![];
+    let mut pos = 0;
+    for token in rustc_lexer::tokenize(code) {
+        if !matches!(
+            token.kind,
+            TokenKind::Whitespace | TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        ) {
+            tokens.push((token.kind, &code[pos..pos + token.len], pos));
+        }
+        pos += token.len;
+    }
+
+    // the statements of every open block, a block closes its bindings
+    let mut scopes: Vec<Vec<String>> = vec![vec![]];
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match tokens[idx] {
+            (TokenKind::OpenBrace, ..) => scopes.push(vec![]),
+            (TokenKind::CloseBrace, ..) if scopes.len() > 1 => {
+                scopes.pop();
+            }
+            (TokenKind::Ident, "let", start) => {
+                let mut name = idx + 1;
+                if tokens.get(name).map(|t| t.1) == Some("mut") {
+                    name += 1;
+                }
+                let Some((_, name, _)) = tokens.get(name) else {
+                    break;
+                };
+                // the statement ends with the first `;` outside of its blocks
+                let mut depth = 0;
+                let mut end = idx;
+                while let Some((kind, ..)) = tokens.get(end) {
+                    match kind {
+                        TokenKind::OpenBrace | TokenKind::OpenParen | TokenKind::OpenBracket => {
+                            depth += 1
+                        }
+                        TokenKind::CloseBrace | TokenKind::CloseParen | TokenKind::CloseBracket => {
+                            depth -= 1
+                        }
+                        TokenKind::Semi if depth == 0 => break,
+                        _ => (),
+                    }
+                    end += 1;
+                }
+                let Some((_, _, semi)) = tokens.get(end) else {
+                    break;
+                };
+                if names.contains(*name) {
+                    scopes
+                        .last_mut()
+                        .expect("there is always a scope")
+                        .push(code[start..=*semi].to_string());
+                }
+                idx = end;
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    scopes.into_iter().flatten().collect()
+}
+
+#[test]
+fn binding_statements_test() {
+    let names = ["data", "v"].into_iter().map(ToOwned::to_owned).collect();
+    let code = "\
+fn main() {
+let data: Vec<u64> = (0..10).map(|x| {
+    x * 2
+}).collect();
+let other = 1;
+fn f() { let v = 1; }
+let data = data.len();
+let mut v = vec![other];";
+    assert_eq!(
+        binding_statements(code, &names),
+        [
+            "let data: Vec<u64> = (0..10).map(|x| {\n    x * 2\n}).collect();",
+            "let data = data.len();",
+            "let mut v = vec![other];"
+        ]
+    );
+
+    // a kept binding survives a reset
+    let mut repl = irust_repl::Repl::default();
+    repl.insert("let data = vec![1, 2];");
+    repl.insert("let scratch = 3;");
+    let code = repl
+        .lines()
+        .take(repl.cursor())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    let kept = binding_statements(&code, &names);
+    repl.reset().unwrap();
+    for statement in kept {
+        repl.insert(statement);
+    }
+    assert_eq!(repl.eval("data").unwrap().output, "[1, 2]");
+    assert!(!repl.eval("scratch").unwrap().status.success());
+}
+
+/// `return`/`break`/`continue` are not valid at the repl top level (the input is inside `main`)
+/// `return x` is evaluated as `x`, `break` and `continue` are an error
+/// Returns `None` if the input doesn't start with one of them
+pub fn top_level_control_flow(input: &str) -> Option<Result<String>> {
+    let input = input.trim();
+    let keyword_len = rustc_lexer::tokenize(input).next()?.len;
+    let (keyword, rest) = input.split_at(keyword_len);
+    match keyword {
+        "return" => {
+            let value = rest.trim().trim_end_matches(';').trim();
+            Some(Ok(if value.is_empty() { "()" } else { value }.to_string()))
+        }
+        "break" | "continue" => Some(Err(format!(
+            "`{keyword}` is only valid inside a loop, the repl input is not inside one"
+        )
+        .into())),
+        _ => None,
+    }
+}
+
+#[test]
+fn top_level_control_flow_test() {
+    let eval = |input| top_level_control_flow(input).map(|r| r.map_err(|e| e.to_string()));
+    assert_eq!(eval("return 5"), Some(Ok("5".into())));
+    assert_eq!(eval(" return vec![1; 2]; "), Some(Ok("vec![1; 2]".into())));
+    assert_eq!(eval("return"), Some(Ok("()".into())));
+    assert_eq!(eval("return;"), Some(Ok("()".into())));
+    assert!(eval("break").unwrap().unwrap_err().contains("`break`"));
+    assert!(eval("break 'outer 4;").unwrap().is_err());
+    assert!(eval("continue")
+        .unwrap()
+        .unwrap_err()
+        .contains("`continue`"));
+
+    assert_eq!(eval("returned + 1"), None);
+    assert_eq!(eval("loop { break 4 }"), None);
+    assert_eq!(eval("(|| return 5)()"), None);
+    assert_eq!(eval(""), None);
+}
+
+/// Split a snippet into its top level statements (and its final expression if it ends with one)
+/// exp: `let a = 1;\nfn f() {}\na + 1` => [`let a = 1;`, `fn f() {}`, `a + 1`]
+pub fn split_statements(snippet: &str) -> Vec<&str> {
+    use rustc_lexer::TokenKind;
+    let mut statements = vec![];
+    let mut depth = 0;
+    let mut pos = 0;
+    let mut start = 0;
+    for token in rustc_lexer::tokenize(snippet) {
+        pos += token.len;
+        let end = match token.kind {
+            TokenKind::OpenBrace | TokenKind::OpenParen | TokenKind::OpenBracket => {
+                depth += 1;
+                false
+            }
+            TokenKind::CloseParen | TokenKind::CloseBracket => {
+                depth -= 1;
+                false
+            }
+            TokenKind::CloseBrace => {
+                depth -= 1;
+                // `let a = S {};` ends with the `;`
+                depth == 0
+                    && !snippet[pos..].trim_start().starts_with(';')
+                    && !snippet[start..pos].trim_start().starts_with("let ")
+            }
+            TokenKind::Semi => depth == 0,
+            _ => false,
+        };
+        if end {
+            statements.push(snippet[start..pos].trim());
+            start = pos;
+        }
+    }
+    statements.push(snippet[start..].trim());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+#[test]
+fn split_statements_test() {
+    let snippet = "\
+let a = [1; 2];
+let p = Point { x: 1 };
+fn f(x: u8) -> u8 {
+    x + 1
+}
+let b = if a[0] == 1 { 1 } else { 2 };
+f(a[1]) + b";
+    assert_eq!(
+        split_statements(snippet),
+        [
+            "let a = [1; 2];",
+            "let p = Point { x: 1 };",
+            "fn f(x: u8) -> u8 {\n    x + 1\n}",
+            "let b = if a[0] == 1 { 1 } else { 2 };",
+            "f(a[1]) + b"
+        ]
+    );
+    assert_eq!(split_statements("let a = 1;\n"), ["let a = 1;"]);
+    assert!(split_statements(" ").is_empty());
+
+    // stepping through the statements, like `:step`
+    let mut repl = irust_repl::Repl::default();
+    let mut values = vec![];
+    for statement in split_statements("let a = 2;\nlet b = a * 3;\na + b") {
+        if is_statement(statement, true) {
+            repl.insert(statement);
+            let name = let_binding_name(statement).unwrap();
+            values.push(format!("{name} = {}", repl.eval(name).unwrap().output));
+        } else {
+            values.push(repl.eval(statement).unwrap().output);
+        }
+    }
+    assert_eq!(values, ["a = 2", "b = 6", "8"]);
+}
+
+/// The name bound by a `let` statement, exp: `let mut a = 1;` => `a`
+pub fn let_binding_name(statement: &str) -> Option<&str> {
+    let mut words = statement
+        .strip_prefix("let")
+        .filter(|rest| rest.starts_with(char::is_whitespace))?
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty());
+    let name = match words.next()? {
+        "mut" => words.next()?,
+        name => name,
+    };
+    // `let (a, b) = ..;` binds a pattern
+    let after_name = statement[statement.find(name)? + name.len()..].trim_start();
+    (after_name.starts_with([':', '=', ';'])).then_some(name)
+}
+
+#[test]
+fn let_binding_name_test() {
+    assert_eq!(let_binding_name("let a = 1;"), Some("a"));
+    assert_eq!(let_binding_name("let mut v: Vec<u8> = vec![];"), Some("v"));
+    assert_eq!(let_binding_name("let (a, b) = (1, 2);"), None);
+    assert_eq!(let_binding_name("letter = 1;"), None);
+    assert_eq!(let_binding_name("fn f() {}"), None);
+}
+
+/// The names of the `let` bindings that are in scope at the end of `code`, in definition order
+/// A shadowed binding is listed once, at its last definition, and patterns are skipped
+/// exp: `let a = 1;\n{ let b = 2; }\nlet (c, d) = (3, 4);\nlet a = "x";` => [`a`]
+pub fn bound_variables(code: &str) -> Vec<String> {
+    use rustc_lexer::TokenKind;
+    let tokens: Vec<(TokenKind, &str)> = {
+        let mut pos = 0;
+        rustc_lexer::tokenize(code)
+            .map(|token| {
+                pos += token.len;
+                (token.kind, &code[pos - token.len..pos])
+            })
+            .filter(|(kind, _)| {
+                !matches!(
+                    kind,
+                    TokenKind::Whitespace
+                        | TokenKind::LineComment { .. }
+                        | TokenKind::BlockComment { .. }
+                )
+            })
+            .collect()
+    };
+
+    // the bindings of every open block, a block closes its bindings
+    let mut scopes: Vec<Vec<&str>> = vec![vec![]];
+    for (idx, (kind, text)) in tokens.iter().enumerate() {
+        match (kind, *text) {
+            (TokenKind::OpenBrace, _) => scopes.push(vec![]),
+            (TokenKind::CloseBrace, _) if scopes.len() > 1 => {
+                scopes.pop();
+            }
+            (TokenKind::Ident, "let") => {
+                let mut name = idx + 1;
+                if tokens.get(name).map(|t| t.1) == Some("mut") {
+                    name += 1;
+                }
+                let (Some((TokenKind::Ident, name)), Some((after, _))) =
+                    (tokens.get(name), tokens.get(name + 1))
+                else {
+                    continue;
+                };
+                if matches!(after, TokenKind::Colon | TokenKind::Eq | TokenKind::Semi) {
+                    scopes
+                        .last_mut()
+                        .expect("there is always a scope")
+                        .push(name);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut names: Vec<String> = vec![];
+    for name in scopes.into_iter().flatten() {
+        names.retain(|n| n != name);
+        names.push(name.to_string());
+    }
+    names
+}
+
+#[test]
+fn bound_variables_test() {
+    let code = "\
+fn main() {
+let a = 1;
+let mut v: Vec<u8> = vec![];
+fn f() { let inner = 1; }
+{
+    let block = 2;
+}
+let (c, d) = (3, 4);
+if let Some(x) = Some(5) {}
+let a = \"x\";";
+    assert_eq!(bound_variables(code), ["v", "a"]);
+    assert!(bound_variables("").is_empty());
+}
+
+/// If the input is only made of comments (exp: `// note`, `/* a */ /// b`), is one of them a doc comment
+/// None if the input has some code, is empty or has an unterminated block comment
+pub fn comment_only(input: &str) -> Option<bool> {
+    use rustc_lexer::TokenKind;
+    let mut has_comment = false;
+    let mut has_doc = false;
+    for token in rustc_lexer::tokenize(input) {
+        match token.kind {
+            TokenKind::Whitespace => (),
+            TokenKind::LineComment { doc_style }
+            | TokenKind::BlockComment {
+                doc_style,
+                terminated: true,
+            } => {
+                has_comment = true;
+                has_doc |= doc_style.is_some();
+            }
+            _ => return None,
+        }
+    }
+    has_comment.then_some(has_doc)
+}
+
+#[test]
+fn comment_only_test() {
+    assert_eq!(comment_only("// note"), Some(false));
+    assert_eq!(comment_only("/* a */ // b\n// c"), Some(false));
+    assert_eq!(comment_only("/// doc"), Some(true));
+    assert_eq!(comment_only("//! inner doc"), Some(true));
+    assert_eq!(comment_only("/** doc */"), Some(true));
+    assert_eq!(comment_only("1 // one"), None);
+    assert_eq!(comment_only("/* unterminated"), None);
+    assert_eq!(comment_only("  "), None);
+}
+
+/// The names of the items (exp: `fn`, `struct`) that are in scope at the end of `code`, in definition order
+/// The items of closed blocks (exp: the methods of an `impl`) are skipped, and so is the repl `fn main` itself
+/// exp: `fn main() {\nfn f() {}\nstruct S;\nimpl S { fn new() {} }` => [`f`, `S`]
+pub fn defined_items(code: &str) -> Vec<String> {
+    use rustc_lexer::TokenKind;
+    const ITEMS: &[&str] = &[
+        "fn",
+        "struct",
+        "enum",
+        "union",
+        "trait",
+        "type",
+        "const",
+        "static",
+        "mod",
+        "macro_rules",
+    ];
+    let tokens: Vec<(TokenKind, &str)> = {
+        let mut pos = 0;
+        rustc_lexer::tokenize(code)
+            .map(|token| {
+                pos += token.len;
+                (token.kind, &code[pos - token.len..pos])
+            })
+            .filter(|(kind, _)| {
+                !matches!(
+                    kind,
+                    TokenKind::Whitespace
+                        | TokenKind::LineComment { .. }
+                        | TokenKind::BlockComment { .. }
+                )
+            })
+            .collect()
+    };
+
+    let mut scopes: Vec<Vec<&str>> = vec![vec![]];
+    for (idx, (kind, text)) in tokens.iter().enumerate() {
+        match (kind, *text) {
+            (TokenKind::OpenBrace, _) => scopes.push(vec![]),
+            (TokenKind::CloseBrace, _) if scopes.len() > 1 => {
+                scopes.pop();
+            }
+            (TokenKind::Ident, item) if ITEMS.contains(&item) => {
+                // `const fn f`, `static mut X`, `macro_rules! m`
+                let name = tokens[idx + 1..]
+                    .iter()
+                    .find(|(_, text)| !matches!(*text, "fn" | "mut" | "unsafe" | "!"));
+                let Some((TokenKind::Ident, name)) = name else {
+                    continue;
+                };
+                // the repl main
+                if scopes.len() == 1 && *name == "main" {
+                    continue;
+                }
+                // `const fn f` is seen twice
+                if item == "fn" && idx > 0 && tokens[idx - 1].1 == "const" {
+                    continue;
+                }
+                scopes
+                    .last_mut()
+                    .expect("there is always a scope")
+                    .push(name);
+            }
+            _ => (),
+        }
+    }
+
+    let mut names: Vec<String> = vec![];
+    for name in scopes.into_iter().flatten() {
+        names.retain(|n| n != name);
+        names.push(name.to_string());
+    }
+    names
+}
+
+#[test]
+fn defined_items_test() {
+    let code = "\
+fn main() {
+fn double(a: u8) -> u8 { a * 2 }
+struct Point { x: u8 }
+impl Point { fn new() -> Self { Point { x: 0 } } }
+const fn answer() -> u8 { 42 }
+static mut COUNT: u8 = 0;
+macro_rules! twice { ($e:expr) => { $e * 2 } }
+let a = 1;";
+    assert_eq!(
+        defined_items(code),
+        ["double", "Point", "answer", "COUNT", "twice"]
+    );
+    assert!(defined_items("").is_empty());
+}
+
+/// The std items completed by `:complete`, the prelude and the most used items
+pub const STD_COMPLETIONS: &[&str] = &[
+    "Box",
+    "Clone",
+    "Default",
+    "Err",
+    "HashMap",
+    "HashSet",
+    "Iterator",
+    "None",
+    "Ok",
+    "Option",
+    "Result",
+    "Some",
+    "String",
+    "ToString",
+    "Vec",
+    "assert!",
+    "assert_eq!",
+    "dbg!",
+    "eprintln!",
+    "format!",
+    "panic!",
+    "print!",
+    "println!",
+    "std",
+    "todo!",
+    "vec!",
+];
+
+/// The completions of `prefix`: the variables, the items and the std items, sorted and deduplicated
+pub fn completions(code: &str, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = bound_variables(code)
+        .into_iter()
+        .chain(defined_items(code))
+        .chain(STD_COMPLETIONS.iter().map(ToString::to_string))
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[test]
+fn completions_test() {
+    let code = "fn main() {\nlet values = vec![1];\nfn validate() {}\nlet v = 2;";
+    assert_eq!(completions(code, "v"), ["v", "validate", "values", "vec!"]);
+    assert_eq!(completions(code, "val"), ["validate", "values"]);
+    assert!(completions(code, "zz").is_empty());
+    // an empty prefix completes everything
+    assert_eq!(completions(code, "").len(), 3 + STD_COMPLETIONS.len());
+}
+
+/// Split a snippet into its statements and its final expression (if it ends with one)
+/// exp: `fn f() -> u8 { 4 }\nf()` => (`fn f() -> u8 { 4 }`, `f()`)
+pub fn split_snippet(snippet: &str) -> (Option<&str>, Option<&str>) {
+    use rustc_lexer::TokenKind;
+    let mut depth = 0;
+    let mut pos = 0;
+    // end of the last top level statement (after a `;` or an item block)
+    let mut statements_end = 0;
+    for token in rustc_lexer::tokenize(snippet) {
+        pos += token.len;
+        match token.kind {
+            TokenKind::OpenBrace | TokenKind::OpenParen | TokenKind::OpenBracket => depth += 1,
+            TokenKind::CloseParen | TokenKind::CloseBracket => depth -= 1,
+            TokenKind::CloseBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    statements_end = pos;
+                }
+            }
+            TokenKind::Semi if depth == 0 => statements_end = pos,
+            _ => (),
+        }
+    }
+    let (statements, expression) = snippet.split_at(statements_end);
+    (
+        Some(statements.trim()).filter(|s| !s.is_empty()),
+        Some(expression.trim()).filter(|s| !s.is_empty()),
+    )
+}
+
+#[test]
+fn split_snippet_test() {
+    let snippet = "\
+fn add(a: u8, b: u8) -> u8 {
+    a + b
+}
+let x = [1; 2];
+add(x[0], 3)
+";
+    assert_eq!(
+        split_snippet(snippet),
+        (
+            Some("fn add(a: u8, b: u8) -> u8 {\n    a + b\n}\nlet x = [1; 2];"),
+            Some("add(x[0], 3)")
+        )
+    );
+    assert_eq!(
+        split_snippet("struct A;\nimpl A {}"),
+        (Some("struct A;\nimpl A {}"), None)
+    );
+    // `;` and `}` in literals and comments are ignored
+    assert_eq!(
+        split_snippet("println!(\"a; }\") // ;"),
+        (None, Some("println!(\"a; }\") // ;"))
+    );
+    assert_eq!(split_snippet("  \n"), (None, None));
+}
+
+/// Is the version `a` newer than `b`, exp: `1.10.0` > `1.9.3`
+/// Only the numeric parts are compared (pre-release and build metadata are ignored)
+pub fn is_newer_version(a: &str, b: &str) -> bool {
+    let parse = |v: &str| -> Vec<u64> {
+        v.split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|n| n.parse().unwrap_or(0))
+            .collect()
+    };
+    parse(a) > parse(b)
+}
+
+/// The dependencies that have a newer version, sorted by name, as (name, current version, latest version)
+/// `latest_version` returns `None` if the latest version couldn't be found
+pub fn outdated_dependencies(
+    locked: &std::collections::HashMap<String, String>,
+    mut latest_version: impl FnMut(&str) -> Option<String>,
+) -> Vec<(String, String, String)> {
+    let mut outdated: Vec<_> = locked
+        .iter()
+        .filter_map(|(name, current)| {
+            let latest = latest_version(name)?;
+            is_newer_version(&latest, current).then(|| (name.clone(), current.clone(), latest))
+        })
+        .collect();
+    outdated.sort();
+    outdated
+}
+
+#[test]
+fn outdated_dependencies_test() {
+    let locked = [
+        ("rand", "0.8.5"),
+        ("itoa", "1.0.18"),
+        ("regex", "1.9.3"),
+        ("foo", "0.1.0"),
+    ]
+    .into_iter()
+    .map(|(name, version)| (name.to_string(), version.to_string()))
+    .collect();
+    let latest = |name: &str| {
+        match name {
+            "rand" => Some("0.9.0"),
+            "itoa" => Some("1.0.18"),
+            "regex" => Some("1.10.0"),
+            // not found
+            _ => None,
+        }
+        .map(ToOwned::to_owned)
+    };
+    assert_eq!(
+        outdated_dependencies(&locked, latest),
+        vec![
+            ("rand".into(), "0.8.5".into(), "0.9.0".into()),
+            ("regex".into(), "1.9.3".into(), "1.10.0".into()),
+        ]
+    );
+    assert!(!is_newer_version("1.0.0-beta.1", "1.0.0"));
+    assert!(is_newer_version("2.0.0-rc.1", "1.9.0"));
+}
+
+/// The crates of a `cargo search` output as (name, version, description)
+/// exp: `serde = "1.0.188"    # A generic serialization/deserialization framework`
+pub fn search_results(output: &str) -> Vec<(String, String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(" = \"")?;
+            let (version, description) = rest.split_once('"')?;
+            let description = description.trim_start().strip_prefix('#').unwrap_or("");
+            Some((
+                name.to_string(),
+                version.to_string(),
+                description.trim().to_string(),
+            ))
+        })
+        .collect()
+}
+
+#[test]
+fn search_results_test() {
+    let output = "serde = \"1.0.188\"    # A generic serialization/deserialization framework\n\
+        serde_json = \"1.0.105\"    # A JSON serialization file format\n\
+        no_description = \"0.1.0\"\n\
+        ... and 4230 crates more (use --limit N to see more)\n";
+    assert_eq!(
+        search_results(output),
+        vec![
+            (
+                "serde".into(),
+                "1.0.188".into(),
+                "A generic serialization/deserialization framework".into()
+            ),
+            (
+                "serde_json".into(),
+                "1.0.105".into(),
+                "A JSON serialization file format".into()
+            ),
+            ("no_description".into(), "0.1.0".into(), "".into()),
+        ]
+    );
+    assert!(search_results("").is_empty());
+}
+
+/// The candidate closest to `name` with the Levenshtein distance, None if they're all too far
+/// (more than a third of the name is different)
+pub fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let distance = |a: &str, b: &str| {
+        let b: Vec<char> = b.chars().collect();
+        let mut previous: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut current = vec![i + 1];
+            for (j, cb) in b.iter().enumerate() {
+                let substitution = previous[j] + usize::from(ca != *cb);
+                current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+            }
+            previous = current;
+        }
+        previous[b.len()]
+    };
+    candidates
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= name.chars().count() / 3)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[test]
+fn closest_test() {
+    let names = [":add", ":reset", ":reload", ":load"];
+    assert_eq!(closest(":ad", names.into_iter()), Some(":add"));
+    assert_eq!(closest(":relaod", names.into_iter()), Some(":reload"));
+    assert_eq!(closest(":lod", names.into_iter()), Some(":load"));
+    assert_eq!(closest(":xyzxyz", names.into_iter()), None);
+}
+
+/// The input to run: the last input for `:retry`, otherwise `buffer` which becomes the last input
+pub fn retry_input(buffer: String, last_input: &mut Option<String>) -> Result<String> {
+    if buffer.trim() == ":retry" {
+        return last_input
+            .clone()
+            .ok_or_else(|| "No command to retry".into());
+    }
+    *last_input = Some(buffer.clone());
+    Ok(buffer)
+}
+
+#[test]
+fn retry_input_test() {
+    let mut last_input = None;
+    assert!(retry_input(":retry".into(), &mut last_input).is_err());
+
+    let input = ":add  serde --features derive".to_string();
+    assert_eq!(retry_input(input.clone(), &mut last_input).unwrap(), input);
+    assert_eq!(
+        retry_input(":retry".into(), &mut last_input).unwrap(),
+        input
+    );
+    // retrying doesn't replace the last input
+    assert_eq!(
+        retry_input(" :retry ".into(), &mut last_input).unwrap(),
+        input
+    );
+    assert_eq!(
+        retry_input("1 + 1".into(), &mut last_input).unwrap(),
+        "1 + 1"
+    );
+    assert_eq!(
+        retry_input(":retry".into(), &mut last_input).unwrap(),
+        "1 + 1"
+    );
+}
+
+/// Expand a command alias, the arguments are passed after the aliased command
+/// exp: with `ll = "::ls -la"`, `:ll src` => `::ls -la src`
+pub fn expand_alias(
+    buffer: &str,
+    aliases: &std::collections::BTreeMap<String, String>,
+) -> Option<String> {
+    let cmd = buffer
+        .strip_prefix(':')
+        .filter(|cmd| !cmd.starts_with(':'))?;
+    let (name, args) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+    let command = aliases.get(name)?;
+    let args = args.trim();
+    if args.is_empty() {
+        Some(command.clone())
+    } else {
+        Some(format!("{command} {args}"))
+    }
+}
+
+#[test]
+fn expand_alias_test() {
+    let aliases = [("ll", "::ls -la"), ("t", ":type")]
+        .into_iter()
+        .map(|(name, command)| (name.to_string(), command.to_string()))
+        .collect();
+    assert_eq!(expand_alias(":ll", &aliases).as_deref(), Some("::ls -la"));
+    assert_eq!(
+        expand_alias(":ll  src ", &aliases).as_deref(),
+        Some("::ls -la src")
+    );
+    assert_eq!(
+        expand_alias(":t 1u8", &aliases).as_deref(),
+        Some(":type 1u8")
+    );
+    assert_eq!(expand_alias(":lll", &aliases), None);
+    assert_eq!(expand_alias("::ll", &aliases), None);
+    assert_eq!(expand_alias("ll", &aliases), None);
+}
+
+/// The dependencies of a Cargo.toml with their version requirement, exp: `regex = "1.5"` => (`regex`, `1.5`)
+/// The dependencies without a version show where they come from, exp: `path = "../foo"` => `path ../foo`
+pub fn manifest_dependencies(cargo_toml: &str) -> Result<Vec<(String, String)>> {
+    let manifest: toml::Table = toml::from_str(cargo_toml)?;
+    let Some(dependencies) = manifest.get("dependencies").and_then(toml::Value::as_table) else {
+        return Ok(vec![]);
     };
+    Ok(dependencies
+        .iter()
+        .map(|(name, spec)| {
+            let version = match spec {
+                toml::Value::String(version) => version.clone(),
+                toml::Value::Table(spec) => ["version", "path", "git"]
+                    .into_iter()
+                    .find_map(|key| {
+                        let value = spec.get(key)?.as_str()?;
+                        Some(if key == "version" {
+                            value.to_string()
+                        } else {
+                            format!("{key} {value}")
+                        })
+                    })
+                    .unwrap_or_else(|| "*".into()),
+                _ => "*".into(),
+            };
+            (name.clone(), version)
+        })
+        .collect())
+}
 
-    let open_tag = main_start + MAIN_FN.len();
+#[test]
+fn manifest_dependencies_test() {
+    let cargo_toml = r#"
+[package]
+name = "irust_host_repl"
+version = "0.1.0"
 
-    let mut close_tag = None;
+[dependencies]
+regex = "1.5"
+tokio = { version = "1", features = ["full"] }
+foo = { path = "../foo" }
+bar = { git = "https://example.com/bar" }
+"#;
+    assert_eq!(
+        manifest_dependencies(cargo_toml).unwrap(),
+        [
+            ("bar".to_string(), "git https://example.com/bar".to_string()),
+            ("foo".to_string(), "path ../foo".to_string()),
+            ("regex".to_string(), "1.5".to_string()),
+            ("tokio".to_string(), "1".to_string()),
+        ]
+    );
+    assert!(
+        manifest_dependencies("[package]\nname = \"a\"\n[dependencies]\n")
+            .unwrap()
+            .is_empty()
+    );
+    assert!(manifest_dependencies("[package]\nname = \"a\"\n")
+        .unwrap()
+        .is_empty());
+    assert!(manifest_dependencies("[dependencies").is_err());
+}
 
-    // look for closing tag
-    let mut tag_score = 1;
-    for (idx, character) in script[open_tag + 1..].chars().enumerate() {
-        if character == '{' {
-            tag_score += 1;
-        }
-        if character == '}' {
-            tag_score -= 1;
-            if tag_score == 0 {
-                close_tag = Some(idx);
-                break;
-            }
-        }
+/// Is a `:load` argument an http(s) url instead of a local path
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Download the code at `url` (with curl), the response must be utf8 like a local file
+pub fn fetch_code(url: &str) -> Result<String> {
+    if !crate::dependencies::dep_installed("curl") {
+        return Err("curl is required to load a url".into());
     }
-    if let Some(close_tag) = close_tag {
-        script.remove(open_tag + close_tag + 1);
-        script.replace_range(main_start..=open_tag, "");
+    // `--fail` turns the http errors (exp: 404) into an error status
+    let output = std::process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to fetch {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
     }
-    script
+    String::from_utf8(output.stdout)
+        .map_err(|_| format!("Failed to fetch {url}: the response is not valid UTF-8").into())
 }
 
-pub struct StringTools {}
-
-impl StringTools {
-    pub fn _insert_at_char_idx(buffer: &mut String, idx: usize, character: char) {
-        let mut buffer_chars: Vec<char> = buffer.chars().collect();
-        buffer_chars.insert(idx, character);
-        *buffer = buffer_chars.into_iter().collect();
+/// The code of a `:load` path, a local file or an http(s) url
+pub fn read_code(path: &std::path::Path) -> Result<String> {
+    match path.to_str().filter(|path| is_url(path)) {
+        Some(url) => fetch_code(url),
+        None => Ok(std::fs::read_to_string(path)?),
     }
+}
 
-    pub fn _remove_at_char_idx(buffer: &mut String, idx: usize) -> Option<char> {
-        let mut buffer_chars: Vec<char> = buffer.chars().collect();
+/// The code of a `:load` path as it's inserted in the repl, without its `main` function
+pub fn loaded_code(path: &std::path::Path) -> Result<String> {
+    Ok(remove_main(&read_code(path)?))
+}
 
-        let removed_char = if buffer_chars.len() > idx {
-            Some(buffer_chars.remove(idx))
-        } else {
-            None
-        };
+#[test]
+fn loaded_code_test() {
+    let path = std::env::temp_dir().join("irust_loaded_code_test.rs");
+    std::fs::write(
+        &path,
+        "/// A link\nfn link() -> &'static str {\n    \"https://docs.rs\" // a comment\n}\n\nfn main() {\n    let brace = \"{\";\n    let url = link();\n}\n",
+    )
+    .unwrap();
+    let code = loaded_code(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
 
-        *buffer = buffer_chars.into_iter().collect();
+    // like `:load`, the code is built then inserted
+    let mut repl = irust_repl::Repl::default();
+    let result = repl.eval_build(code.clone()).unwrap();
+    assert!(result.status.success(), "{}", result.output);
+    repl.insert(code);
+    assert_eq!(repl.eval("url").unwrap().output, "\"https://docs.rs\"");
+    assert_eq!(repl.eval("brace").unwrap().output, "\"{\"");
 
-        removed_char
-    }
+    // an async main and its runtime attribute are removed, so the file loads without the runtime crate
+    std::fs::write(
+        &path,
+        "#[tokio::main]\nasync fn main() {\n    let answer = async { 42 };\n}\n",
+    )
+    .unwrap();
+    let code = loaded_code(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut repl = irust_repl::Repl::default();
+    let result = repl.eval_build(code.clone()).unwrap();
+    assert!(result.status.success(), "{}", result.output);
+    repl.insert(code);
+    assert!(repl.eval("let _ = answer;").unwrap().status.success());
+}
 
-    pub fn chars_count(buffer: &str) -> usize {
-        buffer.chars().count()
-    }
+#[test]
+fn is_url_test() {
+    assert!(is_url("https://gist.githubusercontent.com/a/raw/main.rs"));
+    assert!(is_url("http://localhost:8000/main.rs"));
+    assert!(!is_url("main.rs"));
+    assert!(!is_url("./https/main.rs"));
+}
 
-    pub fn new_lines_count(buffer: &str) -> usize {
-        buffer.chars().filter(|c| c == &'\n').count()
+/// The compiler errors of a build output (without the cargo status lines) for the non interactive modes
+/// A runtime error (exp: a panic) is kept as is
+pub fn compile_errors(output: &str) -> String {
+    let compile_errors = output
+        .lines()
+        .skip_while(|line| !line.starts_with("error"))
+        .take_while(|line| !line.starts_with("error: could not compile"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let message = if compile_errors.is_empty() {
+        output
+    } else {
+        &compile_errors
+    };
+    message.trim().to_string()
+}
+
+/// The line numbers of `:del`, a number or a range separated by whitespace, exp: `2 4-6` => [2, 4, 5, 6]
+pub fn parse_line_numbers(args: &str) -> Result<Vec<usize>> {
+    let mut line_nums = vec![];
+    for arg in args.split_whitespace() {
+        let invalid = || format!("Invalid line number: `{arg}`");
+        let (start, end) = match arg.split_once('-') {
+            Some((start, end)) => (
+                start.parse::<usize>().map_err(|_| invalid())?,
+                end.parse::<usize>().map_err(|_| invalid())?,
+            ),
+            None => {
+                let line_num = arg.parse::<usize>().map_err(|_| invalid())?;
+                (line_num, line_num)
+            }
+        };
+        if start > end {
+            return Err(format!("Invalid range: `{arg}`, the start is after the end").into());
+        }
+        line_nums.extend(start..=end);
     }
+    Ok(line_nums)
+}
 
-    pub fn _is_multiline(string: &str) -> bool {
-        string.chars().filter(|c| *c == '\n').count() > 1
+#[test]
+fn parse_line_numbers_test() {
+    assert_eq!(parse_line_numbers("3").unwrap(), [3]);
+    assert_eq!(parse_line_numbers("3-5").unwrap(), [3, 4, 5]);
+    assert_eq!(parse_line_numbers("2 4 6").unwrap(), [2, 4, 6]);
+    assert_eq!(parse_line_numbers("1 3-4").unwrap(), [1, 3, 4]);
+    assert_eq!(
+        parse_line_numbers("2 x").unwrap_err().to_string(),
+        "Invalid line number: `x`"
+    );
+    assert_eq!(
+        parse_line_numbers("3-").unwrap_err().to_string(),
+        "Invalid line number: `3-`"
+    );
+    assert!(parse_line_numbers("5-3").is_err());
+}
+
+/// The item kinds that `:show <kind>` filters, any other filter is a substring
+const SHOW_ITEM_KINDS: &[&str] = &["fn", "struct", "enum", "trait", "impl"];
+
+/// The part of the repl code (as shown by `:show`) that `:show <filter>` shows
+/// With an item kind (exp: `fn`), the whole definitions with their attributes (except `main`), otherwise the lines containing `filter`
+pub fn filter_shown_code(code: &str, filter: &str) -> String {
+    let lines: Vec<&str> = code.lines().collect();
+    if !SHOW_ITEM_KINDS.contains(&filter) {
+        return lines
+            .into_iter()
+            .filter(|line| line.contains(filter))
+            .collect::<Vec<_>>()
+            .join("\n");
     }
 
-    pub fn strings_unique(s1: &str, s2: &mut String) {
-        let mut idx = s2.len();
+    let is_item_start = |line: &str| {
+        let mut line = line.trim_start();
+        for modifier in [
+            "pub(crate) ",
+            "pub ",
+            "const ",
+            "async ",
+            "unsafe ",
+            "extern \"C\" ",
+        ] {
+            line = line.strip_prefix(modifier).unwrap_or(line).trim_start();
+        }
+        line.starts_with(&format!("{filter} ")) || line.starts_with(&format!("{filter}<"))
+    };
+    let mut items = vec![];
+    let mut main_skipped = false;
+    let mut idx = 0;
+    while idx < lines.len() {
+        if !is_item_start(lines[idx]) {
+            idx += 1;
+            continue;
+        }
+        // the repl code is inside the generated main
+        if !main_skipped && filter == "fn" && lines[idx].contains("fn main(") {
+            main_skipped = true;
+            idx += 1;
+            continue;
+        }
+        let mut start = idx;
+        while start > 0 && {
+            let previous = lines[start - 1].trim_start();
+            previous.starts_with("#[") || previous.starts_with("///")
+        } {
+            start -= 1;
+        }
+        let mut depth = 0;
+        let mut end = idx;
         loop {
-            if s2.get(..idx).is_some() && !s2[..idx].is_empty() && s1.ends_with(&s2[..idx]) {
-                for _ in 0..idx {
-                    s2.remove(0);
-                }
-                break;
-            }
-            if idx == 0 {
-                if let Some(last_char) = s1.chars().last() {
-                    if last_char.is_alphanumeric() {
-                        s2.clear();
-                    }
-                }
+            let line = lines[end];
+            depth += line.matches('{').count() as isize - line.matches('}').count() as isize;
+            let done = depth <= 0 && (line.contains('}') || line.trim_end().ends_with(';'));
+            if done || end + 1 == lines.len() {
                 break;
             }
-
-            idx -= 1;
+            end += 1;
         }
+        items.push(lines[start..=end].join("\n"));
+        idx = end + 1;
     }
+    items.join("\n")
+}
 
-    pub fn unmatched_brackets(s: &str) -> bool {
-        let s = remove_comments(s);
-        let mut braces = std::collections::HashMap::new();
-        braces.insert('(', 0);
-        braces.insert('[', 0);
-        braces.insert('{', 0);
-
-        let mut quote = false;
-        let mut double_quote = false;
-        let mut previous_char = ' ';
-        for character in s.chars() {
-            // safe unwraps ahead
-            match character {
-                '(' => {
-                    if !quote && !double_quote {
-                        *braces.get_mut(&'(').unwrap() += 1;
-                    }
-                }
-                ')' => {
-                    if !quote && !double_quote {
-                        *braces.get_mut(&'(').unwrap() -= 1;
-                    }
-                }
-                '[' => {
-                    if !quote && !double_quote {
-                        *braces.get_mut(&'[').unwrap() += 1;
-                    }
-                }
-                ']' => {
-                    if !quote && !double_quote {
-                        *braces.get_mut(&'[').unwrap() -= 1;
-                    }
-                }
-                '{' => {
-                    if !quote && !double_quote {
-                        *braces.get_mut(&'{').unwrap() += 1;
-                    }
-                }
-                '}' => {
-                    if !quote && !double_quote {
-                        *braces.get_mut(&'{').unwrap() -= 1;
-                    }
-                }
-                '"' => {
-                    if previous_char != '\\' {
-                        double_quote = !double_quote;
-                    }
-                }
-                '\'' => {
-                    if previous_char != '\\' {
-                        quote = !quote;
-                    }
-                }
-                _ => (),
-            }
-            previous_char = character;
+#[test]
+fn filter_shown_code_test() {
+    let code = "\
+fn main() -> () {
+    let a = 1;
+    #[derive(Debug)]
+    struct P {
+        x: u8,
+    }
+    struct Unit;
+    pub fn f(x: u8) -> u8 {
+        if x > 1 {
+            x
+        } else {
+            0
         }
-
-        braces[&'('] != 0 || braces[&'['] != 0 || braces[&'{'] != 0
     }
+    impl<T> Tr for T {}
+    let b = f(a);
+    () // Do not write past this line (it will corrupt the repl)
+}";
+    assert_eq!(
+        filter_shown_code(code, "fn"),
+        "    pub fn f(x: u8) -> u8 {\n        if x > 1 {\n            x\n        } else {\n            0\n        }\n    }"
+    );
+    assert_eq!(
+        filter_shown_code(code, "struct"),
+        "    #[derive(Debug)]\n    struct P {\n        x: u8,\n    }\n    struct Unit;"
+    );
+    assert_eq!(filter_shown_code(code, "impl"), "    impl<T> Tr for T {}");
+    assert_eq!(filter_shown_code(code, "enum"), "");
+    assert_eq!(
+        filter_shown_code(code, "let"),
+        "    let a = 1;\n    let b = f(a);"
+    );
 }
 
-pub fn _read_until_bytes<R: std::io::BufRead + ?Sized>(
-    r: &mut R,
-    delim: &[u8],
-    buffer: &mut Vec<u8>,
-) -> std::io::Result<usize> {
-    let mut nn = 0;
-    let mut b = [0; 512];
-    loop {
-        let n = r.read(&mut b)?;
-        buffer.extend(&b[..n]);
-        nn += n;
-        if n == 0 || buffer.ends_with(delim) {
-            break Ok(nn);
+/// Rename the identifier `old` to `new` in `code`, the lines are kept, returns the code and the number of renames
+/// Only whole identifiers are renamed (not `old_x` or a string containing `old`), except the inline
+/// format arguments in string literals, exp: `"{old}"` and `"{old:?}"`
+pub fn rename_identifier(code: &str, old: &str, new: &str) -> (String, usize) {
+    use rustc_lexer::{LiteralKind, TokenKind};
+    let mut renamed = String::with_capacity(code.len());
+    let mut count = 0;
+    let mut pos = 0;
+    for token in rustc_lexer::tokenize(code) {
+        let text = &code[pos..pos + token.len];
+        pos += token.len;
+        match token.kind {
+            TokenKind::Ident if text == old => {
+                renamed.push_str(new);
+                count += 1;
+            }
+            TokenKind::Literal {
+                kind: LiteralKind::Str { .. } | LiteralKind::RawStr { .. },
+                ..
+            } => {
+                let mut text = text.to_string();
+                for (from, to) in [
+                    (format!("{{{old}}}"), format!("{{{new}}}")),
+                    (format!("{{{old}:"), format!("{{{new}:")),
+                ] {
+                    count += text.matches(&from).count();
+                    text = text.replace(&from, &to);
+                }
+                renamed.push_str(&text);
+            }
+            _ => renamed.push_str(text),
         }
     }
+    (renamed, count)
 }
 
-fn remove_comments(s: &str) -> String {
-    s.lines()
-        .filter(|l| !l.trim_start().starts_with("//"))
-        .map(|l| {
-            let mut quote = false;
-            let mut d_quote = false;
-
-            let mut l = l.chars().peekable();
-            let mut purged_line = String::new();
-
-            loop {
-                match (l.next(), l.peek()) {
-                    (Some('/'), Some('/')) => {
-                        if !quote && !d_quote {
-                            break;
-                        }
-                    }
-                    (Some('\''), _) => {
-                        quote = !quote;
-                        purged_line.push('\'');
-                    }
-                    (Some('"'), _) => {
-                        d_quote = !d_quote;
-                        purged_line.push('"');
-                    }
-                    (Some(c), _) => purged_line.push(c),
-                    _ => break,
-                }
-            }
-            purged_line + "\n"
-        })
-        .collect()
+#[test]
+fn rename_identifier_test() {
+    let code = "\
+let total = 1;
+let total_2 = total + 1; // total
+let s = \"total\";
+println!(\"{total} {total:?} {total_2}\");
+fn f(total: u8) -> u8 { total }";
+    let (renamed, count) = rename_identifier(code, "total", "sum");
+    assert_eq!(
+        renamed,
+        "\
+let sum = 1;
+let total_2 = sum + 1; // total
+let s = \"total\";
+println!(\"{sum} {sum:?} {total_2}\");
+fn f(sum: u8) -> u8 { sum }"
+    );
+    assert_eq!(count, 6);
+    assert_eq!(renamed.lines().count(), code.lines().count());
+    assert_eq!(rename_identifier(code, "nope", "x").1, 0);
 }
 
-fn _balanced_quotes(s: &str) -> bool {
-    s.match_indices(|p| p == '"' || p == '\'').count() % 2 == 0
+/// Where the `:reset <template>` templates are, exp: `~/.config/irust/templates/web.rs`
+pub fn templates_dir() -> Option<std::path::PathBuf> {
+    Some(dirs::config_dir()?.join("irust").join("templates"))
 }
 
-pub fn ctrlc_cancel(process: &mut std::process::Child) -> Result<()> {
-    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
-    // Running a command as Command::new().output takes at minimum 1ms
-    // So Polling should take a similar order of magnitude
-    if let Ok(event) = crossterm::event::poll(std::time::Duration::from_millis(1)) {
-        if event {
-            if let Ok(event) = crossterm::event::read() {
-                match event {
-                    Event::Key(KeyEvent {
-                        kind: KeyEventKind::Release,
-                        ..
-                    }) => (),
-                    Event::Key(KeyEvent {
-                        code: KeyCode::Char('c'),
-                        modifiers: crossterm::event::KeyModifiers::CONTROL,
-                        ..
-                    }) => {
-                        process.kill()?;
-                        return Err("Cancelled!".into());
-                    }
-                    Event::Key(KeyEvent {
-                        code: KeyCode::Char(a),
-                        ..
-                    }) => {
-                        use std::io::Write;
-                        // Ignore write errors (process might have ended)
-                        let _ = process.stdin.as_mut().unwrap().write_all(&[a as u8]);
-                    }
-                    Event::Key(KeyEvent {
-                        code: KeyCode::Enter,
-                        ..
-                    }) => {
-                        use std::io::Write;
-                        // Ignore write errors (process might have ended)
-                        let _ = process.stdin.as_mut().unwrap().write_all(&[b'\n']);
-                    }
-                    _ => (),
-                }
-            }
-        }
-    }
-    Ok(())
+/// Where the `:snippet save <name>` snippets are, exp: `~/.config/irust/snippets/point.rs`
+pub fn snippets_dir() -> Option<std::path::PathBuf> {
+    Some(dirs::config_dir()?.join("irust").join("snippets"))
 }
 
-pub fn find_workpace_root(metadata: String) -> Option<String> {
-    let start = metadata.find("workspace_root")? + 17;
-    let end = metadata[start..].find('"')?;
-    Some(metadata[start..start + end].to_string())
+/// The names of the templates (`.rs` files) in `dir`, sorted
+pub fn template_names(dir: &std::path::Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "rs"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
 }
 
-pub fn patch_name_to(path: &Path, name: &str) -> Result<()> {
-    let toml = std::fs::read_to_string(path)?;
-    let mut patched: String = String::new();
-    for line in toml.lines() {
-        if line.starts_with("name =") {
-            patched.push_str(&format!("name = \"{name}\""));
-            patched.push('\n');
-        } else {
-            patched.push_str(line);
-            patched.push('\n');
-        }
+/// Split a template in its `:add` commands and its code
+/// The commands are the comments at the start of the template, exp: `// :add serde --features derive`
+pub fn split_template(template: &str) -> (Vec<String>, String) {
+    let mut lines = template.lines().peekable();
+    let mut commands = vec![];
+    while let Some(command) = lines.peek().and_then(|line| {
+        let command = line.trim().strip_prefix("//")?.trim();
+        command.starts_with(":add ").then(|| command.to_string())
+    }) {
+        commands.push(command);
+        lines.next();
     }
-    std::fs::write(path, patched)?;
-    Ok(())
+    (commands, lines.collect::<Vec<_>>().join("\n"))
 }
 
-pub fn copy_dir(src_path: &Path, out_path: &Path) -> Result<()> {
-    if src_path.is_file() {
-        panic!("Incorrect usage")
+#[test]
+fn template_names_test() {
+    let dir = std::env::temp_dir().join("irust_template_names_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(template_names(&dir).is_empty());
+    std::fs::create_dir_all(dir.join("dir.rs")).unwrap();
+    for file in ["web.rs", "cli.rs", "notes.txt"] {
+        std::fs::write(dir.join(file), "").unwrap();
     }
-    let convert_path =
-        |path: &Path| -> Result<PathBuf> { Ok(out_path.join(path.strip_prefix(src_path)?)) };
-    let dcb = |dp: PathBuf| Ok(std::fs::create_dir(convert_path(&dp)?)?);
-    let fcb = |fp: PathBuf| Ok(std::fs::copy(fp.clone(), convert_path(&fp)?)?);
-    visit_dirs(src_path, &dcb, &fcb)
+    assert_eq!(template_names(&dir), ["cli", "web"]);
+    std::fs::remove_dir_all(&dir).unwrap();
 }
 
-fn visit_dirs(
-    dir: &Path,
-    dcb: &dyn Fn(PathBuf) -> Result<()>,
-    fcb: &dyn Fn(PathBuf) -> Result<u64>,
-) -> Result<()> {
-    if dir.is_dir() {
-        dcb(dir.to_path_buf())?;
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                visit_dirs(&path, dcb, fcb)?;
-            } else {
-                fcb(entry.path())?;
-            }
-        }
-    }
-    Ok(())
+#[test]
+fn split_template_test() {
+    let template =
+        "// :add serde --features derive\n//:add anyhow\n\nfn helper() {}\n// :add regex";
+    let (commands, code) = split_template(template);
+    assert_eq!(commands, [":add serde --features derive", ":add anyhow"]);
+    assert_eq!(code, "\nfn helper() {}\n// :add regex");
+
+    let (commands, code) = split_template("// a comment\nlet a = 1;");
+    assert!(commands.is_empty());
+    assert_eq!(code, "// a comment\nlet a = 1;");
 }
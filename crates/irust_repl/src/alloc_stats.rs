@@ -0,0 +1,55 @@
+/// Allocations made while evaluating an expression (including printing its result)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocStats {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+const MARKER: &str = "IRUST_ALLOC_STATS";
+
+/// Counting global allocator, it's appended to main.rs when allocation tracking is on
+pub(crate) const ALLOCATOR: &str = "
+mod irust_alloc {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    static BYTES: AtomicUsize = AtomicUsize::new(0);
+    struct Counter;
+    unsafe impl GlobalAlloc for Counter {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+            BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+            System.alloc(layout)
+        }
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+    #[global_allocator]
+    static GLOBAL: Counter = Counter;
+    pub fn snapshot() -> (usize, usize) {
+        (COUNT.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed))
+    }
+}";
+
+/// Wraps the eval statement so the allocations it makes are printed after it
+pub(crate) fn track(eval_statement: &str) -> String {
+    format!(
+        "let irust_alloc_start = irust_alloc::snapshot();
+{eval_statement}
+let irust_alloc_end = irust_alloc::snapshot();
+println!(\"\\n{MARKER} {{}} {{}}\", irust_alloc_end.0 - irust_alloc_start.0, irust_alloc_end.1 - irust_alloc_start.1);"
+    )
+}
+
+/// Removes the stats line printed by `track` from the output
+pub(crate) fn extract(output: &mut String) -> Option<AllocStats> {
+    let start = output.rfind(&format!("\n{MARKER} "))?;
+    let mut stats = output[start..].split_whitespace().skip(1);
+    let stats = AllocStats {
+        count: stats.next()?.parse().ok()?,
+        bytes: stats.next()?.parse().ok()?,
+    };
+    output.truncate(start);
+    Some(stats)
+}
@@ -0,0 +1,64 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Runtime used to drive `.await` expressions when the executor is not async
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AsyncRuntime {
+    /// futures::executor::block_on
+    #[default]
+    BlockOn,
+    Tokio,
+    AsyncStd,
+    Smol,
+}
+
+impl AsyncRuntime {
+    /// The crate that needs to be added (with `:add`) for this runtime
+    pub fn crate_name(&self) -> &'static str {
+        match self {
+            AsyncRuntime::BlockOn => "futures",
+            AsyncRuntime::Tokio => "tokio",
+            AsyncRuntime::AsyncStd => "async-std",
+            AsyncRuntime::Smol => "smol",
+        }
+    }
+
+    /// Wrap an expression containing `.await` so it can be evaluated in a sync main
+    pub fn wrap(&self, input: &str) -> String {
+        let block_on = match self {
+            AsyncRuntime::BlockOn => "futures::executor::block_on",
+            AsyncRuntime::Tokio => {
+                "tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on"
+            }
+            AsyncRuntime::AsyncStd => "async_std::task::block_on",
+            AsyncRuntime::Smol => "smol::block_on",
+        };
+        format!("{block_on}(async {{\n{input}\n}})")
+    }
+}
+
+impl FromStr for AsyncRuntime {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "block_on" => Ok(AsyncRuntime::BlockOn),
+            "tokio" => Ok(AsyncRuntime::Tokio),
+            "async-std" | "async_std" => Ok(AsyncRuntime::AsyncStd),
+            "smol" => Ok(AsyncRuntime::Smol),
+            _ => Err("Unknown async runtime (available: tokio, async-std, smol, block_on)".into()),
+        }
+    }
+}
+
+impl std::fmt::Display for AsyncRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsyncRuntime::BlockOn => write!(f, "block_on"),
+            AsyncRuntime::Tokio => write!(f, "tokio"),
+            AsyncRuntime::AsyncStd => write!(f, "async-std"),
+            AsyncRuntime::Smol => write!(f, "smol"),
+        }
+    }
+}
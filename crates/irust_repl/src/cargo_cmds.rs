@@ -4,11 +4,13 @@ use crate::{
     utils::{stdout_and_stderr, ProcessUtils},
     ToolChain,
 };
+use std::collections::HashMap;
 use std::io;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus};
 use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
 use std::{env::temp_dir, process::Stdio};
 use std::{fs, process};
 
@@ -28,12 +30,18 @@ const WRITE_LIB_LIMIT: &str = concat!(
 pub struct Cargo {
     pub name: String,
     pub paths: CargoPaths,
+    /// Run the evaluations in the sandbox (see `Repl::set_sandbox`)
+    pub sandbox: bool,
 }
 impl Default for Cargo {
     fn default() -> Self {
         let name = "irust_host_repl_".to_string() + &uuid::Uuid::new_v4().simple().to_string();
         let paths = CargoPaths::new(&name);
-        Self { name, paths }
+        Self {
+            name,
+            paths,
+            sandbox: false,
+        }
     }
 }
 
@@ -50,6 +58,7 @@ pub struct CargoPaths {
     pub lib_file: PathBuf,
     pub exe_path: PathBuf,
     pub release_exe_path: PathBuf,
+    pub sandbox_dir: PathBuf,
 }
 
 impl CargoPaths {
@@ -71,6 +80,7 @@ impl CargoPaths {
         let main_file = irust_src_dir.join("main.rs");
         let main_file_extern = irust_src_dir.join("main_extern.rs");
         let lib_file = irust_src_dir.join("lib.rs");
+        let sandbox_dir = irust_dir.join("sandbox");
         let exe_path = if cfg!(windows) {
             irust_target_dir.join(format!("debug/{}.exe", &name))
         } else {
@@ -94,6 +104,7 @@ impl CargoPaths {
             exe_path,
             release_exe_path,
             common_root,
+            sandbox_dir,
         }
     }
 }
@@ -140,45 +151,53 @@ edition = \"2021\""
         toolchain: ToolChain,
         interactive_function: Option<fn(&mut process::Child) -> Result<()>>,
     ) -> Result<(ExitStatus, String)> {
+        let (status, output, _) =
+            self.cargo_run_with_build_output(color, release, toolchain, interactive_function)?;
+        Ok((status, output))
+    }
+
+    /// Like `cargo_run` but the build output of a successful build is returned too (exp: to show its warnings)
+    pub fn cargo_run_with_build_output(
+        &self,
+        color: bool,
+        release: bool,
+        toolchain: ToolChain,
+        interactive_function: Option<fn(&mut process::Child) -> Result<()>>,
+    ) -> Result<(ExitStatus, String, Option<String>)> {
         let (status, output) = self.cargo_build_output(color, release, toolchain)?;
 
         if !status.success() {
-            Ok((status, output))
+            Ok((status, output, None))
         } else {
             // Run the exexcutable directly instead of cargo run
             // This allows to run it without modifying the current working directory
             // example: std::process::Commmand::new("pwd") will output the expected path instead of `/tmp/irust_host_repl`
-            if !release {
-                Ok((
-                    status,
-                    stdout_and_stderr(
-                        std::process::Command::new(&self.paths.exe_path)
-                            .stdin(Stdio::piped())
-                            .stdout(Stdio::piped())
-                            .stderr(Stdio::piped())
-                            .spawn()?
-                            .interactive_output(interactive_function)?,
-                    ),
-                ))
+            let exe_path = if !release {
+                &self.paths.exe_path
             } else {
-                Ok((
-                    status,
-                    stdout_and_stderr(
-                        std::process::Command::new(&self.paths.release_exe_path)
-                            .stdin(Stdio::piped())
-                            .stdout(Stdio::piped())
-                            .stderr(Stdio::piped())
-                            .spawn()?
-                            .interactive_output(interactive_function)?,
-                    ),
-                ))
+                &self.paths.release_exe_path
+            };
+            let mut cmd = std::process::Command::new(exe_path);
+            if self.sandbox {
+                crate::sandbox::restrict(&mut cmd, &self.paths.sandbox_dir);
             }
+            Ok((
+                status,
+                stdout_and_stderr(
+                    cmd.stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped())
+                        .spawn()?
+                        .interactive_output(interactive_function)?,
+                ),
+                Some(output),
+            ))
         }
     }
 
     fn is_local_dep(dep: &[String]) -> bool {
         match dep {
-            &[ref path] => path.starts_with("/"),
+            [path] => path.starts_with("/"),
             _ => false,
         }
     }
@@ -265,6 +284,159 @@ edition = \"2021\""
         Ok(())
     }
 
+    /// Check if `name` is listed under `[dependencies]` in the repl Cargo.toml
+    /// `-` and `_` are treated the same, exp: `async-std` matches `async_std`
+    pub fn has_dependency(&self, name: &str) -> Result<bool> {
+        let normalize = |s: &str| s.trim().trim_matches('"').replace('-', "_");
+        let name = normalize(name);
+        let cargo_toml = std::fs::read_to_string(&self.paths.cargo_toml_file)?;
+
+        let mut in_dependencies = false;
+        for line in cargo_toml.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_dependencies = line == "[dependencies]";
+            } else if in_dependencies {
+                if let Some(key) = line.split(['=', '.']).next() {
+                    if normalize(key) == name {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// The lines of the `[dependencies]` section of the repl Cargo.toml, exp: [`regex = "1.5"`]
+    pub fn dependency_lines(&self) -> Result<Vec<String>> {
+        let cargo_toml = fs::read_to_string(&self.paths.cargo_toml_file)?;
+        let mut in_dependencies = false;
+        let mut lines = vec![];
+        for line in cargo_toml.lines() {
+            if line.trim_start().starts_with('[') {
+                in_dependencies = line.trim() == "[dependencies]";
+            } else if in_dependencies && !line.trim().is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Add `lines` (from `dependency_lines`) to the `[dependencies]` section of the repl Cargo.toml
+    /// The dependencies that are already there are kept as is
+    pub fn add_dependency_lines(&self, lines: &[String]) -> Result<()> {
+        let dependency_name = |line: &str| {
+            line.split(['=', '.'])
+                .next()
+                .map(|key| key.trim().trim_matches('"').replace('-', "_"))
+        };
+        let cargo_toml = fs::read_to_string(&self.paths.cargo_toml_file)?;
+        let existing: Vec<_> = self
+            .dependency_lines()?
+            .iter()
+            .filter_map(|line| dependency_name(line))
+            .collect();
+        let added = lines
+            .iter()
+            .filter(|line| !dependency_name(line).is_some_and(|name| existing.contains(&name)));
+
+        let mut new_cargo_toml: Vec<String> = vec![];
+        let mut has_dependencies = false;
+        for line in cargo_toml.lines() {
+            new_cargo_toml.push(line.to_string());
+            if line.trim() == "[dependencies]" {
+                has_dependencies = true;
+                new_cargo_toml.extend(added.clone().cloned());
+            }
+        }
+        if !has_dependencies {
+            new_cargo_toml.push("[dependencies]".into());
+            new_cargo_toml.extend(added.cloned());
+        }
+        fs::write(
+            &self.paths.cargo_toml_file,
+            new_cargo_toml.join("\n") + "\n",
+        )?;
+        Ok(())
+    }
+
+    /// The registry dependencies of the repl with their versions in Cargo.lock
+    pub fn locked_dependencies(&self) -> Result<HashMap<String, String>> {
+        // Creates Cargo.lock if needed, without upgrading the already locked dependencies
+        let output = Command::new("cargo")
+            .current_dir(&self.paths.irust_dir)
+            .args(["update", "--workspace", "--color", "never"])
+            .output()?;
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
+        let cargo_lock = fs::read_to_string(self.paths.irust_dir.join("Cargo.lock"))?;
+        Ok(parse_locked_dependencies(&cargo_lock, &self.name))
+    }
+
+    /// The latest version of `krate` in the registry (requires network access)
+    pub fn latest_version(&self, krate: &str) -> Result<String> {
+        // Run outside of the repl project, or else cargo shows the locked version
+        let output = Command::new("cargo")
+            .current_dir(&self.paths.common_root)
+            .args(["info", "--color", "never", krate])
+            .output()?;
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
+        // exp: `version: 1.0.18`
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix("version: "))
+            .and_then(|version| version.split_whitespace().next())
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| format!("Failed to find the latest version of {krate}").into())
+    }
+
+    /// The `cargo search` output of the crates matching `query` in the registry (requires network access)
+    pub fn search(&self, query: &str, limit: usize) -> Result<String> {
+        let output = Command::new("cargo")
+            .current_dir(&self.paths.common_root)
+            .args(["search", "--color", "never", "--limit"])
+            .arg(limit.to_string())
+            .arg(query)
+            .output()?;
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Rewrite the registry dependencies of the repl Cargo.toml to the exact versions resolved in Cargo.lock
+    /// exp: `rand = "0.8"` => `rand = "=0.8.5"`
+    /// Returns the pinned dependencies with their versions
+    pub fn pin_dependencies(&self) -> Result<Vec<(String, String)>> {
+        let locked = self.locked_dependencies()?;
+
+        let cargo_toml = fs::read_to_string(&self.paths.cargo_toml_file)?;
+        let mut pinned = vec![];
+        let mut in_dependencies = false;
+        let mut new_cargo_toml = vec![];
+        for line in cargo_toml.lines() {
+            if line.trim_start().starts_with('[') {
+                in_dependencies = line.trim() == "[dependencies]";
+            }
+            let pinned_line = in_dependencies
+                .then(|| pin_dependency_line(line, &locked))
+                .flatten();
+            if let Some((line, name, version)) = pinned_line {
+                new_cargo_toml.push(line);
+                pinned.push((name, version));
+            } else {
+                new_cargo_toml.push(line.to_string());
+            }
+        }
+        fs::write(
+            &self.paths.cargo_toml_file,
+            new_cargo_toml.join("\n") + "\n",
+        )?;
+        Ok(pinned)
+    }
+
     // The difference in env flags makes cargo recompiles again!!!
     // => make  sure all build env flags are the same
     // Or even better dont use any
@@ -360,6 +532,69 @@ edition = \"2021\""
         ))
     }
 
+    /// Build the repl from scratch with `--timings` and return the html report
+    /// A separate target dir is used so all the dependencies are recompiled (fresh units are not timed)
+    pub fn cargo_build_timings(&self, toolchain: ToolChain) -> Result<String> {
+        let target_dir = self.paths.irust_dir.join("timings_target");
+        let _ = fs::remove_dir_all(&target_dir);
+        let mut cmd = Command::new("cargo");
+        let output = self
+            .cargo_common(&mut cmd, "build", toolchain)
+            .arg("--timings")
+            .args(["--color", "never"])
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .output()?;
+        let report = fs::read_to_string(target_dir.join("cargo-timings/cargo-timing.html"));
+        let _ = fs::remove_dir_all(&target_dir);
+
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
+        Ok(report?)
+    }
+
+    /// Time a cold build of the repl crate (its previous build artifacts are removed, the dependencies are kept)
+    /// then a warm rebuild of the same code after touching main.rs
+    pub fn cargo_build_times(&self, toolchain: ToolChain) -> Result<(Duration, Duration)> {
+        let mut cmd = Command::new("cargo");
+        self.cargo_common(&mut cmd, "clean", toolchain)
+            .args(["-p", &self.name])
+            .output()?;
+
+        let build = || -> Result<Duration> {
+            let now = Instant::now();
+            let mut cmd = Command::new("cargo");
+            let output = self
+                .cargo_common(&mut cmd, "build", toolchain)
+                .args(["--color", "never"])
+                .output()?;
+            if !output.status.success() {
+                return Err(stdout_and_stderr(output).into());
+            }
+            Ok(now.elapsed())
+        };
+        let cold = build()?;
+        fs::File::options()
+            .write(true)
+            .open(&self.paths.main_file)?
+            .set_modified(SystemTime::now())?;
+        let warm = build()?;
+        Ok((cold, warm))
+    }
+
+    /// Remove the build artifacts, the repl code is kept
+    pub fn cargo_clean(&self, toolchain: ToolChain) -> Result<()> {
+        let mut cmd = Command::new("cargo");
+        let output = self
+            .cargo_common(&mut cmd, "clean", toolchain)
+            .args(["--color", "never"])
+            .output()?;
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
+        Ok(())
+    }
+
     pub fn cargo_fmt(&self, c: &str) -> std::io::Result<String> {
         let fmt_path = self.paths.irust_dir.join("fmt_file");
         // Ignore file doesn't exist error
@@ -367,6 +602,7 @@ edition = \"2021\""
 
         let mut fmt_file = fs::OpenOptions::new()
             .create(true)
+            .truncate(true)
             .read(true)
             .write(true)
             .open(&fmt_path)?;
@@ -382,6 +618,27 @@ edition = \"2021\""
         Ok(fmt_c)
     }
 
+    /// Run the tests of the repl lib with coverage instrumentation (requires cargo-llvm-cov)
+    /// and return the lcov report
+    pub fn cargo_llvm_cov(&self, toolchain: ToolChain) -> Result<String> {
+        let report = self.paths.irust_dir.join("lcov.info");
+        let mut cmd = Command::new("cargo");
+        let output = self
+            .cargo_common(&mut cmd, "llvm-cov", toolchain)
+            .arg("--lib")
+            .arg("--lcov")
+            .arg("--output-path")
+            .arg(&report)
+            .args(["--color", "never"])
+            .output()?;
+        if !output.status.success() {
+            return Err((stdout_and_stderr(output) + WRITE_LIB_LIMIT).into());
+        }
+        let lcov = fs::read_to_string(&report);
+        let _ = fs::remove_file(&report);
+        Ok(lcov?)
+    }
+
     pub fn cargo_asm(&self, fnn: &str, toolchain: ToolChain) -> Result<String> {
         // 0 doesn't activate FORCE_COLOR (tested)
         let force_color = if no_color() { "0" } else { "1" };
@@ -425,15 +682,50 @@ edition = \"2021\""
         Ok(stdout_and_stderr(output).trim().to_owned())
     }
 
+    /// The macro expansion of the repl binary without colors (exp: to highlight it)
+    pub fn cargo_expand_bin(&self, toolchain: ToolChain) -> Result<String> {
+        let mut cmd = Command::new("cargo");
+        let output = self
+            .cargo_common(&mut cmd, "expand", toolchain)
+            .args(["--color", "never"])
+            .args(["--bin", &self.name])
+            .output()?;
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Document the repl lib and return the path to the generated index.html
+    pub fn cargo_doc(&self, private: bool, toolchain: ToolChain) -> Result<PathBuf> {
+        let color = if no_color() { "never" } else { "always" };
+        let mut cmd = Command::new("cargo");
+        self.cargo_common(&mut cmd, "doc", toolchain)
+            .args(["--color", color])
+            .args(["--lib", "--no-deps"]);
+        if private {
+            cmd.arg("--document-private-items");
+        }
+        let output = cmd.output()?;
+        if !output.status.success() {
+            return Err((stdout_and_stderr(output) + WRITE_LIB_LIMIT).into());
+        }
+        Ok(self
+            .paths
+            .irust_target_dir
+            .join("doc")
+            .join(&self.name)
+            .join("index.html"))
+    }
+
     pub fn cargo_fmt_file(&self, file: &Path) {
         // Cargo fmt is optional
         let _ = self.try_cargo_fmt_file(file);
     }
 
-    fn try_cargo_fmt_file(&self, file: &Path) -> io::Result<()> {
-        std::process::Command::new("rustfmt")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
+    /// Format the file with rustfmt, the error is the rustfmt output if it fails (exp: invalid syntax)
+    pub fn try_cargo_fmt_file(&self, file: &Path) -> Result<()> {
+        let output = std::process::Command::new("rustfmt")
             // ensure that main is always spread on two lines
             // this is needed for inserting the input correctly in the repl
             // fn main() {
@@ -441,11 +733,29 @@ edition = \"2021\""
             .arg("--config")
             .arg("empty_item_single_line=false")
             .arg(file)
-            .spawn()?
-            .wait()?;
+            .output()?;
+        if !output.status.success() {
+            return Err(stdout_and_stderr(output).into());
+        }
         Ok(())
     }
 
+    /// Rewrite the `edition` of the repl Cargo.toml
+    pub fn set_edition(&self, edition: Edition) -> io::Result<()> {
+        let cargo_toml = fs::read_to_string(&self.paths.cargo_toml_file)?
+            .lines()
+            .map(|line| {
+                if line.starts_with("edition = ") {
+                    format!("edition = \"{edition}\"")
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(&self.paths.cargo_toml_file, cargo_toml)
+    }
+
     fn clean_cargo_toml(&self, edition: Edition) -> io::Result<()> {
         // edition needs to be specified or racer will not be able to autocomplete dependencies
         // bug maybe?
@@ -477,3 +787,96 @@ edition = \"{edition}\"",
         std::fs::remove_dir_all(&self.paths.irust_dir)
     }
 }
+
+/// The registry packages that `root` depends on directly, with their locked versions
+fn parse_locked_dependencies(cargo_lock: &str, root: &str) -> HashMap<String, String> {
+    struct Package<'a> {
+        name: &'a str,
+        version: &'a str,
+        from_registry: bool,
+        dependencies: Vec<&'a str>,
+    }
+
+    let packages: Vec<Package> = cargo_lock
+        .split("[[package]]")
+        .skip(1)
+        .map(|entry| {
+            let field = |key: &str| {
+                entry.lines().find_map(|line| {
+                    let value = line.strip_prefix(key)?.trim_start().strip_prefix('=')?;
+                    Some(value.trim().trim_matches('"'))
+                })
+            };
+            // dependencies = [
+            //  "rand 0.8.5",
+            //  "serde",
+            // ]
+            let dependencies = entry
+                .split_once("dependencies = [")
+                .and_then(|(_, deps)| deps.split_once(']'))
+                .map(|(deps, _)| {
+                    deps.split(',')
+                        .map(|dep| dep.trim().trim_matches('"'))
+                        .filter(|dep| !dep.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            Package {
+                name: field("name").unwrap_or_default(),
+                version: field("version").unwrap_or_default(),
+                from_registry: field("source").is_some_and(|s| s.starts_with("registry+")),
+                dependencies,
+            }
+        })
+        .collect();
+
+    let Some(root) = packages.iter().find(|p| p.name == root) else {
+        return HashMap::new();
+    };
+    root.dependencies
+        .iter()
+        .filter_map(|dep| {
+            // The version is only specified when multiple versions of the same crate are locked
+            let mut dep = dep.split_whitespace();
+            let name = dep.next()?;
+            let version = dep.next();
+            let package = packages
+                .iter()
+                .find(|p| p.name == name && version.map_or(true, |v| v == p.version))?;
+            package
+                .from_registry
+                .then(|| (name.to_string(), package.version.to_string()))
+        })
+        .collect()
+}
+
+/// Pin a `[dependencies]` line to its locked version
+/// Returns the new line, the crate name and its version
+fn pin_dependency_line(
+    line: &str,
+    locked: &HashMap<String, String>,
+) -> Option<(String, String, String)> {
+    let (key, spec) = line.split_once('=')?;
+    let (key, spec) = (key.trim(), spec.trim());
+    // renamed dependency, exp: `rng = { package = "rand", version = "0.8" }`
+    let name = quoted_field(spec, "package")
+        .map(|(start, end)| &spec[start..end])
+        .unwrap_or(key);
+    let version = locked.get(name)?;
+
+    let spec = if spec.starts_with('"') {
+        format!("\"={version}\"")
+    } else {
+        let (start, end) = quoted_field(spec, "version")?;
+        format!("{}={version}{}", &spec[..start], &spec[end..])
+    };
+    Some((format!("{key} = {spec}"), name.to_string(), version.clone()))
+}
+
+/// The range of the string value of `field` in an inline table, exp: `version` in `{ version = "1.0" }`
+fn quoted_field(table: &str, field: &str) -> Option<(usize, usize)> {
+    let field_start = table.find(&format!("{field} = \""))?;
+    let start = field_start + field.len() + " = \"".len();
+    let end = start + table[start..].find('"')?;
+    Some((start, end))
+}
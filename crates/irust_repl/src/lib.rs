@@ -10,6 +10,14 @@ mod edition;
 pub use edition::Edition;
 mod compile_mode;
 pub use compile_mode::CompileMode;
+mod async_runtime;
+pub use async_runtime::AsyncRuntime;
+mod alloc_stats;
+pub use alloc_stats::AllocStats;
+mod render;
+pub use render::ReplRender;
+mod sandbox;
+mod stack_size;
 
 use once_cell::sync::Lazy;
 mod utils;
@@ -18,6 +26,8 @@ use std::{
     io::{self, Write},
     path::PathBuf,
     process::{Child, ExitStatus},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
 };
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -37,6 +47,10 @@ pub struct EvalConfig<'a, S: ToString> {
 pub struct EvalResult {
     pub output: String,
     pub status: ExitStatus,
+    /// Only set if allocation tracking is on
+    pub alloc_stats: Option<AllocStats>,
+    /// The cargo build output of a successful evaluation, exp: to show its warnings
+    pub build_output: Option<String>,
 }
 
 impl From<(ExitStatus, String)> for EvalResult {
@@ -44,6 +58,8 @@ impl From<(ExitStatus, String)> for EvalResult {
         Self {
             output: result.1,
             status: result.0,
+            alloc_stats: None,
+            build_output: None,
         }
     }
 }
@@ -57,6 +73,14 @@ pub struct Repl {
     main_result: MainResult,
     edition: Edition,
     prelude: Option<PathBuf>,
+    type_aliases: Vec<String>,
+    track_alloc: bool,
+    /// The stack size of the evaluation thread, the evaluation runs on the main thread if it's not set
+    stack_size: Option<usize>,
+    /// Set when the first build (started by `new`) is done
+    warm_up_time: Arc<OnceLock<Duration>>,
+    /// The manifests saved before the last dependency changes with a description of the change
+    manifest_snapshots: Vec<(String, String)>,
     pub cargo: Cargo,
 }
 impl Default for Repl {
@@ -78,6 +102,10 @@ impl Drop for Repl {
 }
 
 const PRELUDE_NAME: &str = "irust_prelude";
+/// How many dependency changes can be undone
+const MAX_MANIFEST_SNAPSHOTS: usize = 20;
+/// Ends the footer line of the main function
+const FOOTER_WARNING: &str = " // Do not write past this line (it will corrupt the repl)";
 
 impl Repl {
     pub fn new(
@@ -103,10 +131,43 @@ impl Repl {
             // NOTE: This code blocks
             cargo.cargo_add_sync(&dependecy)?;
         }
-        cargo.cargo_build(toolchain)?;
+        let warm_up_time = Arc::new(OnceLock::new());
+        let mut warm_up = cargo.cargo_build(toolchain)?;
+        std::thread::spawn({
+            let warm_up_time = warm_up_time.clone();
+            let now = Instant::now();
+            move || {
+                let _ = warm_up.wait();
+                let _ = warm_up_time.set(now.elapsed());
+            }
+        });
+
+        let (body, cursor) =
+            Self::initial_body(executor, main_result, prelude_parent_path.is_some());
+        Ok(Self {
+            body,
+            cursor,
+            toolchain,
+            executor,
+            main_result,
+            edition,
+            prelude: prelude_parent_path,
+            type_aliases: vec![],
+            track_alloc: false,
+            stack_size: None,
+            warm_up_time,
+            manifest_snapshots: vec![],
+            cargo,
+        })
+    }
 
+    fn initial_body(
+        executor: Executor,
+        main_result: MainResult,
+        with_prelude: bool,
+    ) -> (Vec<String>, usize) {
         let (header, footer) = Self::generate_body_delimiters(executor, main_result);
-        let (body, cursor) = if prelude_parent_path.is_some() {
+        if with_prelude {
             (
                 vec![
                     header,
@@ -118,25 +179,13 @@ impl Repl {
             )
         } else {
             (vec![header, footer, "}".to_string()], 1)
-        };
-        Ok(Self {
-            body,
-            cursor,
-            toolchain,
-            executor,
-            main_result,
-            edition,
-            prelude: prelude_parent_path,
-            cargo,
-        })
+        }
     }
 
     fn generate_body_delimiters(executor: Executor, main_result: MainResult) -> (String, String) {
         (
             executor.main() + " -> " + main_result.ttype() + "{",
-            "#[allow(unreachable_code)]".to_string()
-                + main_result.instance()
-                + " // Do not write past this line (it will corrupt the repl)",
+            "#[allow(unreachable_code)]".to_string() + main_result.instance() + FOOTER_WARNING,
         )
     }
 
@@ -204,6 +253,10 @@ impl Repl {
     }
 
     pub fn reset(&mut self) -> Result<()> {
+        let type_aliases = std::mem::take(&mut self.type_aliases);
+        let track_alloc = self.track_alloc;
+        let stack_size = self.stack_size;
+        let sandbox = self.cargo.sandbox;
         *self = Self::new(
             self.toolchain,
             self.executor,
@@ -211,6 +264,31 @@ impl Repl {
             self.edition,
             self.prelude.clone(),
         )?;
+        self.set_type_aliases(type_aliases);
+        self.track_alloc = track_alloc;
+        self.stack_size = stack_size;
+        self.set_sandbox(sandbox)?;
+        Ok(())
+    }
+
+    /// Type aliases (exp: `type R<T> = Result<T, Box<dyn std::error::Error>>;`) that are always in scope
+    /// They're inserted right away (so they should be set before any code is inserted to be placed right after the prelude)
+    /// and again after every reset
+    pub fn set_type_aliases(&mut self, type_aliases: Vec<String>) {
+        for alias in &type_aliases {
+            self.insert(alias);
+        }
+        self.type_aliases = type_aliases;
+    }
+
+    /// Like `reset` but only clears the code, the cargo project is kept as is
+    /// so the dependencies and their build artifacts survive
+    pub fn clear(&mut self) -> Result<()> {
+        (self.body, self.cursor) =
+            Self::initial_body(self.executor, self.main_result, self.prelude.is_some());
+        let type_aliases = std::mem::take(&mut self.type_aliases);
+        self.set_type_aliases(type_aliases);
+        self.write()?;
         Ok(())
     }
 
@@ -251,25 +329,58 @@ impl Repl {
     ) -> Result<EvalResult> {
         let input = input.to_string();
         // `\n{}\n` to avoid print appearing in error messages
-        let eval_statement = format!(
-            "{}{}{}std::process::exit(0);", // exit(0) allows :hard_load functions to inspect variables that are used after this line
-            evaluator[0], input, evaluator[1]
-        );
+        let mut eval_statement = format!("{}{}{}", evaluator[0], input, evaluator[1]);
+        if self.track_alloc {
+            eval_statement = alloc_stats::track(&eval_statement);
+        }
+        // exit(0) allows :hard_load functions to inspect variables that are used after this line
+        eval_statement += "std::process::exit(0);";
         let toolchain = self.toolchain;
 
         let cargo = self.cargo.clone();
-        let (status, mut eval_result) = self.eval_in_tmp_repl(eval_statement, |_| {
-            cargo.cargo_run(
-                color,
-                compile_mode.is_release(),
-                toolchain,
-                interactive_function,
-            )
-        })?;
-
+        let (status, mut eval_result, build_output) =
+            self.eval_in_tmp_repl(eval_statement, |_| {
+                cargo.cargo_run_with_build_output(
+                    color,
+                    compile_mode.is_release(),
+                    toolchain,
+                    interactive_function,
+                )
+            })?;
+
+        let alloc_stats = if self.track_alloc {
+            alloc_stats::extract(&mut eval_result)
+        } else {
+            None
+        };
         // remove trailing new line
         eval_result.pop();
-        Ok((status, eval_result).into())
+        Ok(EvalResult {
+            output: eval_result,
+            status,
+            alloc_stats,
+            build_output,
+        })
+    }
+
+    /// Run the evaluations on a thread with `stack_size` bytes of stack, None runs them on the main thread
+    pub fn set_stack_size(&mut self, stack_size: Option<usize>) {
+        self.stack_size = stack_size;
+    }
+
+    /// Count the allocations made by evaluated expressions, using a counting global allocator
+    pub fn set_track_alloc(&mut self, track_alloc: bool) {
+        self.track_alloc = track_alloc;
+    }
+
+    /// Run the evaluations in a sandbox: an empty environment, the sandbox directory as the working directory
+    /// and on linux no file system access outside of it and no network (see the `sandbox` module)
+    pub fn set_sandbox(&mut self, sandbox: bool) -> Result<()> {
+        if sandbox {
+            std::fs::create_dir_all(&self.cargo.paths.sandbox_dir)?;
+        }
+        self.cargo.sandbox = sandbox;
+        Ok(())
     }
 
     pub fn eval_build(&mut self, input: impl ToString) -> Result<EvalResult> {
@@ -308,6 +419,8 @@ impl Repl {
 
         result
     }
+    /// Run `f` with `input` temporarily inserted in the repl code
+    /// The repl cargo project is reused, so the added dependencies are available to `input`
     pub fn eval_in_tmp_repl<T>(
         &mut self,
         input: String,
@@ -334,6 +447,17 @@ impl Repl {
         self.toolchain = toolchain;
     }
 
+    pub fn edition(&self) -> Edition {
+        self.edition
+    }
+
+    /// The Cargo.toml is rewritten with the new edition, the code isn't rebuilt
+    pub fn set_edition(&mut self, edition: Edition) -> Result<()> {
+        self.cargo.set_edition(edition)?;
+        self.edition = edition;
+        Ok(())
+    }
+
     pub fn set_main_result(&mut self, main_result: MainResult) {
         self.main_result = main_result;
         // rebuild main fn
@@ -347,13 +471,85 @@ impl Repl {
         self.cargo.cargo_add(dep)
     }
 
+    /// Save the manifest before a dependency change, so `undo_dep` can restore it
+    /// `change` describes the change, exp: `:add regex`
+    pub fn snapshot_manifest(&mut self, change: impl ToString) -> Result<()> {
+        let cargo_toml = std::fs::read_to_string(&self.cargo.paths.cargo_toml_file)?;
+        if self.manifest_snapshots.len() == MAX_MANIFEST_SNAPSHOTS {
+            self.manifest_snapshots.remove(0);
+        }
+        self.manifest_snapshots
+            .push((change.to_string(), cargo_toml));
+        Ok(())
+    }
+
+    /// Restore the manifest saved before the last dependency change
+    /// Returns the description of the reverted change, None if there is nothing to undo
+    pub fn undo_dep(&mut self) -> Result<Option<String>> {
+        let Some((change, cargo_toml)) = self.manifest_snapshots.pop() else {
+            return Ok(None);
+        };
+        std::fs::write(&self.cargo.paths.cargo_toml_file, cargo_toml)?;
+        Ok(Some(change))
+    }
+
+    /// Add `dep` only for the duration of `f`
+    /// The manifest is restored afterwards, even if adding the dependency or `f` failed
+    pub fn with_temporary_dep<T>(
+        &mut self,
+        dep: &[String],
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> Result<T> {
+        let cargo_toml = std::fs::read_to_string(&self.cargo.paths.cargo_toml_file)?;
+        let result = self.cargo.cargo_add_sync(dep).map(|_| f(self));
+        std::fs::write(&self.cargo.paths.cargo_toml_file, cargo_toml)?;
+        result
+    }
+
     pub fn build(&self) -> std::io::Result<std::process::Child> {
         self.cargo.cargo_build(self.toolchain)
     }
 
     pub fn write(&self) -> io::Result<()> {
         let mut main_file = std::fs::File::create(&self.cargo.paths.main_file)?;
-        write!(main_file, "{}", self.body.join("\n"))?;
+        let (header, _) = Self::generate_body_delimiters(self.executor, self.main_result);
+        // With a stack size, the repl main runs on a thread spawned by the real main
+        let main_header = match self.stack_size {
+            Some(_) => stack_size::rename_main(&header),
+            None => header.clone(),
+        };
+        // Enter the sandbox before running any of the repl code
+        let enter = self
+            .cargo
+            .sandbox
+            .then(|| sandbox::enter(&self.cargo.paths.sandbox_dir));
+        let body: Vec<&str> = self
+            .body
+            .iter()
+            .flat_map(|line| {
+                if *line == header {
+                    let mut lines = vec![main_header.as_str()];
+                    lines.extend(enter.as_deref());
+                    lines
+                } else {
+                    vec![line.as_str()]
+                }
+            })
+            .collect();
+        write!(main_file, "{}", body.join("\n"))?;
+        if self.cargo.sandbox {
+            write!(main_file, "{}", sandbox::SANDBOX)?;
+        }
+        if let Some(stack_size) = self.stack_size {
+            write!(
+                main_file,
+                "{}",
+                stack_size::main(self.main_result, stack_size)
+            )?;
+        }
+        if self.track_alloc {
+            write!(main_file, "{}", alloc_stats::ALLOCATOR)?;
+        }
 
         Ok(())
     }
@@ -390,6 +586,36 @@ impl Repl {
         Ok(())
     }
 
+    /// The line (starting at 1) of the main file where the body line `body_line` (starting at 0) is written
+    pub fn main_file_line(&self, body_line: usize) -> usize {
+        let header = Self::generate_body_delimiters(self.executor, self.main_result).0;
+        let header_idx = self
+            .body
+            .iter()
+            .position(|line| *line == header)
+            .unwrap_or(0);
+        // the sandbox is entered on the line after the main header
+        let enter = usize::from(self.cargo.sandbox && body_line > header_idx);
+        body_line + 1 + enter
+    }
+
+    /// The body line of a (1 based) line of the lib written by `with_lib`
+    pub fn lib_line_to_body_line(&self, lib_line: usize) -> usize {
+        let main_idx = self
+            .body
+            .iter()
+            .position(|line| {
+                line == &Self::generate_body_delimiters(self.executor, self.main_result).0
+            })
+            .unwrap_or(0);
+        let idx = lib_line.saturating_sub(1);
+        if idx < main_idx {
+            idx
+        } else {
+            idx + 1
+        }
+    }
+
     fn remove_lib(&self) -> io::Result<()> {
         std::fs::remove_file(&self.cargo.paths.lib_file)
     }
@@ -408,6 +634,19 @@ impl Repl {
         }
     }
 
+    /// The lines inserted before the cursor, without the generated main function and prelude import
+    pub fn inserted_lines(&self) -> &[String] {
+        let start = if self.prelude.is_some() { 2 } else { 1 };
+        &self.body[start..self.cursor]
+    }
+
+    /// Remove the last `count` inserted lines before the cursor and return them
+    pub fn remove_last_lines(&mut self, count: usize) -> Vec<String> {
+        let count = count.min(self.inserted_lines().len());
+        self.cursor -= count;
+        self.body.drain(self.cursor..self.cursor + count).collect()
+    }
+
     pub fn del(&mut self, line_num: &str) -> Result<()> {
         if let Ok(line_num) = line_num.parse::<usize>() {
             if line_num != 0 && line_num + 1 < self.body.len() {
@@ -420,6 +659,76 @@ impl Repl {
         Err("Incorrect line number".into())
     }
 
+    /// Remove the lines `line_nums` (like `del`), nothing is removed if one of them is out of range
+    pub fn del_lines(&mut self, line_nums: &[usize]) -> Result<()> {
+        if let Some(line_num) = line_nums
+            .iter()
+            .find(|&&line_num| line_num == 0 || line_num + 1 >= self.body.len())
+        {
+            return Err(format!("Incorrect line number: {line_num}").into());
+        }
+        let mut line_nums = line_nums.to_vec();
+        line_nums.sort_unstable();
+        line_nums.dedup();
+        // from the last line so the removals don't shift the next ones
+        for line_num in line_nums.into_iter().rev() {
+            self.body.remove(line_num);
+            self.cursor -= 1;
+        }
+        Ok(())
+    }
+
+    /// How long the first build of the repl took, `None` while it's still running
+    pub fn warm_up_time(&self) -> Option<Duration> {
+        self.warm_up_time.get().copied()
+    }
+
+    /// Line where the next input is inserted
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// A hash of everything that changes what an evaluation does: the code, the manifest and the repl settings
+    /// It's stable across sessions (the generated crate name is ignored), exp: to invalidate a cache
+    pub fn state_hash(&self) -> Result<u64> {
+        let cargo_toml = std::fs::read_to_string(&self.cargo.paths.cargo_toml_file)?
+            .replace(&self.cargo.name, "irust_host_repl");
+        let settings = format!(
+            "{} {} {:?} {} {:?} {} {:?} {:?}",
+            self.toolchain,
+            self.executor,
+            self.main_result,
+            self.edition,
+            self.type_aliases,
+            self.track_alloc,
+            self.stack_size,
+            self.cargo.sandbox
+        );
+        Ok(utils::fnv1a(&[
+            self.body().as_bytes(),
+            cargo_toml.as_bytes(),
+            settings.as_bytes(),
+        ]))
+    }
+
+    /// The code written in the repl, without the generated main function and prelude import
+    /// It can be loaded again with `insert`
+    pub fn user_code(&self) -> String {
+        let start = if self.prelude.is_some() { 2 } else { 1 };
+        // the last lines are the footer and the closing `}`
+        self.body[start..self.body.len() - 2].join("\n")
+    }
+
+    /// The repl code as the main file of a standalone program, exp: to `cargo run` it outside of the repl
+    pub fn standalone_code(&self) -> String {
+        let mut body = self.body.clone();
+        let footer = body.len() - 2;
+        if let Some(instance) = body[footer].strip_suffix(FOOTER_WARNING) {
+            body[footer] = instance.to_string();
+        }
+        body.join("\n") + "\n"
+    }
+
     pub fn lines(&self) -> impl Iterator<Item = &String> {
         self.body.iter()
     }
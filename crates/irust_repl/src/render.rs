@@ -0,0 +1,14 @@
+/// Controls how the values of a type are shown by IRust, instead of their `Debug` output
+/// IRust uses it when the repl depends on `irust_repl` (`:add irust_repl`), exp:
+/// ```
+/// struct Matrix([[u8; 2]; 2]);
+/// impl irust_repl::ReplRender for Matrix {
+///     fn repl_render(&self) -> String {
+///         self.0.map(|row| format!("{row:?}")).join("\n")
+///     }
+/// }
+/// ```
+/// The rendered text can contain ANSI escape codes to style it
+pub trait ReplRender {
+    fn repl_render(&self) -> String;
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Restricts the evaluation process, it's appended to main.rs when the sandbox is on
+/// On linux, landlock is used to deny all file system access outside of the sandbox directory
+/// and all tcp connections (with kernel >= 6.7), the evaluation fails if landlock is not supported
+/// On other platforms this does nothing, only the environment and the working directory are restricted
+pub(crate) const SANDBOX: &str = r#"
+mod irust_sandbox {
+    #[cfg(target_os = "linux")]
+    pub fn enter(dir: &str) {
+        use std::os::fd::AsRawFd;
+        extern "C" {
+            fn syscall(number: i64, ...) -> i64;
+            fn prctl(option: i32, ...) -> i32;
+        }
+        const CREATE_RULESET: i64 = 444;
+        const ADD_RULE: i64 = 445;
+        const RESTRICT_SELF: i64 = 446;
+        #[repr(C)]
+        struct RulesetAttr {
+            handled_access_fs: u64,
+            handled_access_net: u64,
+        }
+        #[repr(C, packed)]
+        struct PathBeneathAttr {
+            allowed_access: u64,
+            parent_fd: i32,
+        }
+        let fail = |msg: &str| -> ! {
+            eprintln!("IRust sandbox: {msg} ({}), use `:config sandbox off` to disable it", std::io::Error::last_os_error());
+            std::process::exit(1)
+        };
+
+        let abi = unsafe { syscall(CREATE_RULESET, std::ptr::null::<RulesetAttr>(), 0usize, 1u32) };
+        if abi < 1 {
+            fail("landlock is not supported");
+        }
+        // every abi version handles more access rights
+        let handled_access_fs: u64 = match abi {
+            1 => (1 << 13) - 1,
+            2 => (1 << 14) - 1,
+            3 | 4 => (1 << 15) - 1,
+            _ => (1 << 16) - 1,
+        };
+        let (attr, attr_size) = if abi >= 4 {
+            (RulesetAttr { handled_access_fs, handled_access_net: 0b11 }, 16usize)
+        } else {
+            (RulesetAttr { handled_access_fs, handled_access_net: 0 }, 8usize)
+        };
+        let ruleset = unsafe { syscall(CREATE_RULESET, &attr, attr_size, 0u32) };
+        if ruleset < 0 {
+            fail("failed to create the landlock ruleset");
+        }
+        let Ok(dir) = std::fs::File::open(dir) else {
+            fail("failed to open the sandbox directory");
+        };
+        let rule = PathBeneathAttr { allowed_access: handled_access_fs, parent_fd: dir.as_raw_fd() };
+        if unsafe { syscall(ADD_RULE, ruleset, 1u32, &rule, 0u32) } < 0 {
+            fail("failed to allow the sandbox directory");
+        }
+        // PR_SET_NO_NEW_PRIVS
+        if unsafe { prctl(38, 1u64, 0u64, 0u64, 0u64) } < 0 {
+            fail("failed to drop the privileges");
+        }
+        if unsafe { syscall(RESTRICT_SELF, ruleset, 0u32) } < 0 {
+            fail("failed to restrict the evaluation");
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn enter(_dir: &str) {}
+}"#;
+
+/// Enters the sandbox, it's inserted at the start of main so the repl code is restricted too
+pub(crate) fn enter(dir: &Path) -> String {
+    format!("irust_sandbox::enter({:?});", dir.display().to_string())
+}
+
+/// Runs the evaluation with an empty environment in the sandbox directory
+pub(crate) fn restrict(cmd: &mut Command, dir: &Path) {
+    cmd.env_clear()
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("TMPDIR", dir);
+    // keep the backtraces working
+    if let Ok(backtrace) = std::env::var("RUST_BACKTRACE") {
+        cmd.env("RUST_BACKTRACE", backtrace);
+    }
+}
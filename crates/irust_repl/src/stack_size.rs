@@ -0,0 +1,42 @@
+use crate::MainResult;
+
+/// The repl main is renamed so it can run on a thread with a bigger stack
+const REPL_MAIN: &str = "fn irust_main()";
+
+/// Renames the main function of the body header, exp: `fn main() -> () {` => `fn irust_main() -> () {`
+pub(crate) fn rename_main(header: &str) -> String {
+    header.replacen("fn main()", REPL_MAIN, 1)
+}
+
+/// The real main, it's appended to main.rs when a stack size is set
+/// It runs the repl main on a thread with `stack_size` bytes of stack, a panic is propagated
+pub(crate) fn main(main_result: MainResult, stack_size: usize) -> String {
+    match main_result {
+        MainResult::Unit => format!(
+            r#"
+fn main() {{
+    let irust_main = std::thread::Builder::new().stack_size({stack_size}).spawn(irust_main).expect("IRust: failed to spawn the evaluation thread");
+    if let Err(e) = irust_main.join() {{
+        std::panic::resume_unwind(e)
+    }}
+}}"#
+        ),
+        // `Box<dyn Error>` is not `Send`, the error is sent as its debug string
+        MainResult::Result => format!(
+            r#"
+struct IRustMainError(String);
+impl std::fmt::Debug for IRustMainError {{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {{
+        f.write_str(&self.0)
+    }}
+}}
+fn main() -> Result<(), IRustMainError> {{
+    let irust_main = std::thread::Builder::new().stack_size({stack_size}).spawn(|| irust_main().map_err(|e| format!("{{e:?}}"))).expect("IRust: failed to spawn the evaluation thread");
+    match irust_main.join() {{
+        Ok(result) => result.map_err(IRustMainError),
+        Err(e) => std::panic::resume_unwind(e),
+    }}
+}}"#
+        ),
+    }
+}
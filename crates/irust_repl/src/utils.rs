@@ -15,6 +15,19 @@ pub fn stdout_and_stderr(out: Output) -> String {
     String::from_utf8(out).unwrap_or_default()
 }
 
+/// 64 bits FNV-1a, unlike `DefaultHasher` it's the same across rust versions
+pub(crate) fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        // the length separates the parts, exp: ["ab", "c"] and ["a", "bc"]
+        for byte in part.len().to_le_bytes().iter().chain(part.iter()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
 pub trait ProcessUtils {
     fn interactive_output(self, function: Option<fn(&mut Child) -> Result<()>>) -> Result<Output>;
 }
@@ -29,3 +29,489 @@ fn two_repls_at_the_same_time() {
 
     assert_eq!(a1_thread.join().unwrap() + a2_thread.join().unwrap(), 9)
 }
+
+#[test]
+fn doc_private_items() {
+    let mut repl = Repl::default();
+    repl.insert("/// Adds one\nfn add_one(a: u8) -> u8 { a + 1 }");
+    let index = repl
+        .with_lib(|| repl.cargo.cargo_doc(true, ToolChain::Default))
+        .unwrap()
+        .unwrap();
+    assert!(index.exists());
+    assert!(index.with_file_name("fn.add_one.html").exists());
+}
+
+#[test]
+fn async_runtime_wrap() {
+    let mut repl = Repl::default();
+    repl.insert("async fn d() -> usize {4}");
+
+    for runtime in [AsyncRuntime::BlockOn, AsyncRuntime::Smol] {
+        assert!(!repl.cargo.has_dependency(runtime.crate_name()).unwrap());
+        repl.cargo
+            .cargo_add_sync(&[runtime.crate_name().to_string()])
+            .unwrap();
+        assert!(repl.cargo.has_dependency(runtime.crate_name()).unwrap());
+
+        assert_eq!(repl.eval(runtime.wrap("d().await")).unwrap().output, "4");
+    }
+}
+
+#[test]
+fn clear_keeps_dependencies() {
+    let mut repl = Repl::default();
+    let dep_parent = std::env::temp_dir().join("irust_clear_keeps_dependencies");
+    repl.cargo
+        .cargo_new_lib_simple(&dep_parent, "irust_clear_dep")
+        .unwrap();
+    repl.cargo
+        .cargo_add_sync(&[dep_parent.join("irust_clear_dep").display().to_string()])
+        .unwrap();
+
+    repl.insert("let a = 4;");
+    assert_eq!(repl.eval("a").unwrap().output, "4");
+    let deps_dir = repl.cargo.paths.irust_target_dir.join("debug/deps");
+    let dep_artifacts = || {
+        std::fs::read_dir(&deps_dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("libirust_clear_dep") && name.ends_with(".rlib")
+            })
+            .count()
+    };
+    assert!(dep_artifacts() > 0);
+
+    repl.clear().unwrap();
+    assert!(!repl.body().contains("let a = 4;"));
+    assert!(repl.cargo.has_dependency("irust_clear_dep").unwrap());
+    assert!(dep_artifacts() > 0);
+    assert_eq!(repl.eval("1 + 1").unwrap().output, "2");
+}
+
+#[test]
+fn reset_keeps_dependency_lines() {
+    let mut repl = Repl::default();
+    let dep_parent = std::env::temp_dir().join("irust_reset_keeps_dependency_lines");
+    repl.cargo
+        .cargo_new_lib_simple(&dep_parent, "irust_reset_dep")
+        .unwrap();
+    repl.cargo
+        .cargo_add_sync(&[dep_parent.join("irust_reset_dep").display().to_string()])
+        .unwrap();
+    let deps = repl.cargo.dependency_lines().unwrap();
+    assert!(deps.iter().any(|line| line.starts_with("irust_reset_dep")));
+
+    repl.reset().unwrap();
+    assert!(!repl.cargo.has_dependency("irust_reset_dep").unwrap());
+    repl.cargo.add_dependency_lines(&deps).unwrap();
+    assert!(repl.cargo.has_dependency("irust_reset_dep").unwrap());
+    // adding them again doesn't duplicate them
+    repl.cargo.add_dependency_lines(&deps).unwrap();
+    assert_eq!(repl.cargo.dependency_lines().unwrap(), deps);
+    assert_eq!(repl.eval("1 + 1").unwrap().output, "2");
+}
+
+#[test]
+fn eval_build_keeps_body() {
+    let mut repl = Repl::default();
+    repl.insert("let a = 4;");
+    let body = repl.body();
+    assert!(!repl
+        .eval_build("fn f() -> u8 { \"not a u8\" }")
+        .unwrap()
+        .status
+        .success());
+    assert_eq!(repl.body(), body);
+    assert!(repl
+        .eval_build("fn f() -> u8 { 1 }")
+        .unwrap()
+        .status
+        .success());
+    assert_eq!(repl.body(), body);
+}
+
+#[test]
+fn set_edition() {
+    let mut repl = Repl::default();
+    repl.set_edition(Edition::E2015).unwrap();
+    assert!(std::fs::read_to_string(&repl.cargo.paths.cargo_toml_file)
+        .unwrap()
+        .contains("edition = \"2015\""));
+    // `async` is only a keyword since 2018
+    repl.insert("let async = 1;");
+    assert_eq!(repl.eval("async + 1").unwrap().output, "2");
+
+    repl.set_edition(Edition::E2021).unwrap();
+    assert!(!repl.eval_build(String::new()).unwrap().status.success());
+    assert_eq!(repl.edition().to_string(), "2021");
+}
+
+#[test]
+fn standalone_code() {
+    let mut repl = Repl::default();
+    repl.insert("let a = 1;");
+    repl.insert("println!(\"{a}\");");
+    let code = repl.standalone_code();
+    assert!(code.starts_with("fn main() "));
+    assert!(code.contains("let a = 1;\nprintln!(\"{a}\");"));
+    assert!(!code.contains("Do not write past this line"));
+}
+
+#[test]
+fn del_lines() {
+    let mut repl = Repl::default();
+    repl.insert("let a = 1;\nlet b = 2;\nlet c = 3;\nlet d = 4;");
+    let line_of = |repl: &Repl, code: &str| repl.lines().position(|line| line == code).unwrap();
+    let (b, d) = (line_of(&repl, "let b = 2;"), line_of(&repl, "let d = 4;"));
+
+    assert!(repl.del_lines(&[b, 1000]).is_err());
+    assert_eq!(repl.lines_count(), line_of(&repl, "}"));
+    assert!(repl.body().contains("let b = 2;"));
+
+    repl.del_lines(&[b, d, b]).unwrap();
+    assert!(!repl.body().contains("let b = 2;"));
+    assert!(!repl.body().contains("let d = 4;"));
+    assert_eq!(repl.eval("a + c").unwrap().output, "4");
+}
+
+#[test]
+fn undo_dep() {
+    let mut repl = Repl::default();
+    let dep_parent = std::env::temp_dir().join("irust_undo_dep");
+    repl.cargo
+        .cargo_new_lib_simple(&dep_parent, "irust_undo_dep")
+        .unwrap();
+    assert_eq!(repl.undo_dep().unwrap(), None);
+
+    let dep = dep_parent.join("irust_undo_dep").display().to_string();
+    repl.snapshot_manifest(format!(":add {dep}")).unwrap();
+    repl.cargo
+        .cargo_add_sync(std::slice::from_ref(&dep))
+        .unwrap();
+    assert!(repl.cargo.has_dependency("irust_undo_dep").unwrap());
+
+    assert_eq!(repl.undo_dep().unwrap(), Some(format!(":add {dep}")));
+    assert!(!repl.cargo.has_dependency("irust_undo_dep").unwrap());
+    assert_eq!(repl.undo_dep().unwrap(), None);
+    assert_eq!(repl.eval("1 + 1").unwrap().output, "2");
+}
+
+#[test]
+fn state_hash() {
+    let mut repl = Repl::default();
+    let hash = repl.state_hash().unwrap();
+    // evaluations don't change the state
+    assert_eq!(repl.eval("1 + 1").unwrap().output, "2");
+    assert_eq!(repl.state_hash().unwrap(), hash);
+    // the generated crate name is not part of the state
+    assert_eq!(Repl::default().state_hash().unwrap(), hash);
+
+    repl.insert("let a = 1;");
+    let inserted = repl.state_hash().unwrap();
+    assert_ne!(inserted, hash);
+    repl.set_track_alloc(true);
+    assert_ne!(repl.state_hash().unwrap(), inserted);
+    repl.set_track_alloc(false);
+    assert_eq!(repl.state_hash().unwrap(), inserted);
+
+    repl.reset().unwrap();
+    assert_eq!(repl.state_hash().unwrap(), hash);
+}
+
+#[test]
+fn remove_last_lines() {
+    let mut repl = Repl::default();
+    assert!(repl.remove_last_lines(1).is_empty());
+    repl.insert("let a = 1;");
+    repl.insert("fn b() -> u8 {\n    2\n}");
+    assert_eq!(repl.inserted_lines().len(), 4);
+    assert_eq!(repl.remove_last_lines(3), ["fn b() -> u8 {", "    2", "}"]);
+    assert_eq!(repl.inserted_lines(), ["let a = 1;"]);
+    assert_eq!(repl.remove_last_lines(5), ["let a = 1;"]);
+    assert_eq!(repl.eval("1").unwrap().output, "1");
+}
+
+#[test]
+fn user_code() {
+    let mut repl = Repl::default();
+    assert_eq!(repl.user_code(), "");
+    repl.insert("fn double(a: u8) -> u8 { a * 2 }");
+    repl.insert("let a = double(2);");
+    let code = repl.user_code();
+    assert_eq!(code, "fn double(a: u8) -> u8 { a * 2 }\nlet a = double(2);");
+
+    // it can be loaded again
+    repl.reset().unwrap();
+    repl.insert(code);
+    assert_eq!(repl.eval("a").unwrap().output, "4");
+}
+
+#[test]
+fn temporary_dep() {
+    let mut repl = Repl::default();
+    let dep_parent = std::env::temp_dir().join("irust_temporary_dep");
+    repl.cargo
+        .cargo_new_lib_simple(&dep_parent, "irust_temporary_dep")
+        .unwrap();
+    std::fs::write(
+        dep_parent.join("irust_temporary_dep/src/lib.rs"),
+        "pub fn four() -> u8 { 4 }",
+    )
+    .unwrap();
+
+    let dep = [dep_parent.join("irust_temporary_dep").display().to_string()];
+    let output = repl
+        .with_temporary_dep(&dep, |repl| repl.eval("irust_temporary_dep::four()"))
+        .unwrap()
+        .unwrap()
+        .output;
+    assert_eq!(output, "4");
+    assert!(!repl.cargo.has_dependency("irust_temporary_dep").unwrap());
+
+    // the manifest is restored even if the evaluation fails
+    let result = repl
+        .with_temporary_dep(&dep, |repl| repl.eval("irust_temporary_dep::five()"))
+        .unwrap()
+        .unwrap();
+    assert!(!result.status.success());
+    assert!(!repl.cargo.has_dependency("irust_temporary_dep").unwrap());
+}
+
+#[test]
+fn type_aliases() {
+    let mut repl = Repl::default();
+    repl.set_type_aliases(vec![
+        "type R<T> = Result<T, Box<dyn std::error::Error>>;".to_string()
+    ]);
+    repl.insert(r#"fn parse(s: &str) -> R<u8> { Ok(s.parse()?) }"#);
+    assert_eq!(repl.eval("parse(\"4\").unwrap()").unwrap().output, "4");
+
+    // aliases survive a reset
+    repl.reset().unwrap();
+    assert_eq!(
+        repl.eval("{ let r: R<u8> = Ok(4); r.unwrap() }")
+            .unwrap()
+            .output,
+        "4"
+    );
+}
+
+#[test]
+fn track_alloc() {
+    let mut repl = Repl::default();
+    repl.set_track_alloc(true);
+
+    let baseline = repl.eval("1").unwrap();
+    assert_eq!(baseline.output, "1");
+    let baseline = baseline.alloc_stats.unwrap();
+
+    let result = repl.eval("vec![1u64; 100].len()").unwrap();
+    assert_eq!(result.output, "100");
+    let stats = result.alloc_stats.unwrap();
+    assert!(stats.count > baseline.count);
+    assert!(stats.bytes >= baseline.bytes + 800);
+
+    repl.set_track_alloc(false);
+    assert!(repl.eval("1").unwrap().alloc_stats.is_none());
+}
+
+#[test]
+fn fmt_extern_edit() {
+    let mut repl = Repl::default();
+    let main_file = repl.cargo.paths.main_file_extern.clone();
+
+    // simulate a messy external edit
+    std::fs::write(&main_file, "fn main() {\nlet   a=1 ;\n}\n").unwrap();
+    repl.cargo.cargo_fmt_file(&main_file);
+    repl.update_from_extern_main_file().unwrap();
+    assert_eq!(repl.body(), "fn main() {\n    let a = 1;\n}");
+
+    // invalid code is kept as is
+    std::fs::write(&main_file, "fn main() {\nlet   a= ;\n}\n").unwrap();
+    repl.cargo.cargo_fmt_file(&main_file);
+    repl.update_from_extern_main_file().unwrap();
+    assert_eq!(repl.body(), "fn main() {\nlet   a= ;\n}");
+    assert!(repl.cargo.try_cargo_fmt_file(&main_file).is_err());
+}
+
+#[test]
+fn version_pin() {
+    let repl = Repl::default();
+    repl.cargo.cargo_add_sync(&["itoa@1".to_string()]).unwrap();
+    let pinned = repl.cargo.pin_dependencies().unwrap();
+    assert_eq!(pinned.len(), 1);
+    let (krate, version) = &pinned[0];
+    assert_eq!(krate, "itoa");
+
+    let cargo_lock =
+        std::fs::read_to_string(repl.cargo.paths.irust_dir.join("Cargo.lock")).unwrap();
+    assert!(cargo_lock.contains(&format!("name = \"itoa\"\nversion = \"{version}\"")));
+    let cargo_toml = std::fs::read_to_string(&repl.cargo.paths.cargo_toml_file).unwrap();
+    assert!(cargo_toml.contains(&format!("itoa = \"={version}\"")));
+}
+
+#[test]
+fn tmp_repl_dependencies() {
+    let mut repl = Repl::default();
+    repl.cargo
+        .cargo_add_sync(&["serde_json".to_string()])
+        .unwrap();
+
+    // this is how `:type` finds the type of an expression
+    let cargo = repl.cargo.clone();
+    let output = repl
+        .eval_in_tmp_repl("let _:() = serde_json::Value::Null;".into(), |_| {
+            Ok(cargo
+                .cargo_build_output(false, false, ToolChain::Default)?
+                .1)
+        })
+        .unwrap();
+    assert!(output.contains("expected `()`, found `Value`"));
+}
+
+#[test]
+fn annotated_bindings() {
+    let mut repl = Repl::default();
+    repl.insert("let s: &'static str = \"hi\";");
+    repl.insert("fn keep(s: &'static str) -> &'static str { s }");
+    assert_eq!(repl.eval("keep(s)").unwrap().output, "\"hi\"");
+
+    // the annotation is what makes these bindings compile
+    repl.insert("let mut v: Vec<Option<u8>> = Vec::new();");
+    repl.insert("v.push(Some(1));");
+    repl.insert("let r: &[Option<u8>] = &v;");
+    assert_eq!(repl.eval("r").unwrap().output, "[Some(1)]");
+    repl.insert("let parsed: Result<u64, _> = \"42\".parse();");
+    assert_eq!(
+        repl.eval("parsed.map(u64::leading_zeros)").unwrap().output,
+        "Ok(58)"
+    );
+    repl.insert("let f: fn(u8) -> u8 = |x| x * 2;");
+    assert_eq!(repl.eval("f(2)").unwrap().output, "4");
+
+    // the code is stored as is
+    let body = repl.body();
+    assert!(body.contains("let s: &'static str = \"hi\";"));
+    assert!(body.contains("let r: &[Option<u8>] = &v;"));
+}
+
+#[test]
+fn warm_up_time() {
+    let repl = Repl::default();
+    let now = std::time::Instant::now();
+    while repl.warm_up_time().is_none() {
+        assert!(
+            now.elapsed().as_secs() < 120,
+            "the warm up build never ended"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(repl.warm_up_time().unwrap() > std::time::Duration::ZERO);
+}
+
+#[test]
+fn build_times() {
+    let mut repl = Repl::default();
+    // build the dependencies of the prelude so they're not timed
+    repl.eval("1").unwrap();
+
+    // enough code for the incremental compilation to make a difference
+    let code: String = (0..300)
+        .map(|i| format!("fn f{i}(x: u64) -> u64 {{ (0..x).map(|i| i * {i}).sum() }}\n"))
+        .collect();
+    let cargo = repl.cargo.clone();
+    let (cold, warm) = repl
+        .eval_in_tmp_repl(code, |_| cargo.cargo_build_times(ToolChain::Default))
+        .unwrap();
+    assert!(warm < cold, "warm: {warm:?}, cold: {cold:?}");
+
+    let error = repl
+        .eval_in_tmp_repl("let a: u8 = \"a\";".into(), |_| {
+            cargo.cargo_build_times(ToolChain::Default)
+        })
+        .unwrap_err();
+    assert!(error.to_string().contains("mismatched types"));
+}
+
+#[test]
+fn sandbox() {
+    let mut repl = Repl::default();
+    repl.set_sandbox(true).unwrap();
+    let outside = repl.cargo.paths.irust_dir.join("outside.txt");
+    std::fs::write(&outside, "secret").unwrap();
+    let inside = repl.cargo.paths.sandbox_dir.join("inside.txt");
+    std::fs::write(&inside, "public").unwrap();
+
+    let read = |path: &std::path::Path| format!("std::fs::read_to_string({path:?}).is_ok()");
+    // the working directory is the sandbox directory
+    assert_eq!(
+        repl.eval("std::fs::read_to_string(\"inside.txt\").unwrap()")
+            .unwrap()
+            .output,
+        "\"public\""
+    );
+    assert_eq!(
+        repl.eval("std::env::vars().count() <= 3").unwrap().output,
+        "true"
+    );
+    if cfg!(target_os = "linux") {
+        assert_eq!(repl.eval(read(&outside)).unwrap().output, "false");
+        // inserted code is sandboxed too
+        repl.insert(format!("let leaked = {};", read(&outside)));
+        assert_eq!(repl.eval("leaked").unwrap().output, "false");
+
+        repl.set_sandbox(false).unwrap();
+        assert_eq!(repl.eval("leaked").unwrap().output, "true");
+    }
+}
+
+#[test]
+fn stack_size() {
+    let mut repl = Repl::default();
+    // ~8KB per frame in debug, that's ~160MB of stack
+    repl.insert(
+        "fn deep(n: u64) -> u64 { let pad = std::hint::black_box([n; 1024]); if n == 0 { 0 } else { deep(n - 1) + 1 + pad[0] - n } }",
+    );
+    let overflow = repl.eval("deep(20_000)").unwrap();
+    assert!(overflow.output.contains("has overflowed its stack"));
+
+    repl.set_stack_size(Some(512 * 1024 * 1024));
+    assert_eq!(repl.eval("deep(20_000)").unwrap().output, "20000");
+    // panics and errors still reach the real main
+    assert!(repl
+        .eval("panic!(\"oops\")")
+        .unwrap()
+        .output
+        .contains("oops"));
+    repl.set_main_result(MainResult::Result);
+    let error = repl.eval("\"a\".parse::<u8>()?").unwrap();
+    assert!(error
+        .output
+        .contains("Error: ParseIntError { kind: InvalidDigit }"));
+}
+
+#[test]
+fn eval_build_output() {
+    let mut repl = Repl::default();
+    let result = repl.eval("{ let unused = 1; 2 }").unwrap();
+    assert_eq!(result.output, "2");
+    assert!(result
+        .build_output
+        .unwrap()
+        .contains("unused variable: `unused`"));
+    // a failed build has no build output, it's the output
+    assert!(repl.eval("nope").unwrap().build_output.is_none());
+}
+
+#[test]
+fn main_file_line() {
+    let mut repl = Repl::default();
+    repl.insert("let a = 1;");
+    let code = "let b = 2;\nlet c: u8 = \"c\";";
+    let error = repl.eval_build(code).unwrap().output;
+    let line = repl.main_file_line(repl.cursor()) + 1;
+    assert!(error.contains(&format!("src/main.rs:{line}:")));
+}
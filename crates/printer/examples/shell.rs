@@ -15,8 +15,8 @@ fn main() -> Result<()> {
 
     loop {
         let inp = crossterm::event::read()?;
-        match inp {
-            crossterm::event::Event::Key(key) => match key {
+        if let crossterm::event::Event::Key(key) = inp {
+            match key {
                 KeyEvent {
                     kind: KeyEventKind::Release,
                     ..
@@ -33,13 +33,11 @@ fn main() -> Result<()> {
                 KeyEvent {
                     code: KeyCode::Backspace,
                     ..
-                } => {
-                    if !buffer.is_at_start() {
-                        buffer.move_backward();
-                        printer.cursor.move_left();
-                        buffer.remove_current_char();
-                        printer.print_input(&default_process_fn, &buffer)?;
-                    }
+                } if !buffer.is_at_start() => {
+                    buffer.move_backward();
+                    printer.cursor.move_left();
+                    buffer.remove_current_char();
+                    printer.print_input(&default_process_fn, &buffer)?;
                 }
                 KeyEvent {
                     code: KeyCode::Enter,
@@ -59,8 +57,7 @@ fn main() -> Result<()> {
                     ..
                 } => break,
                 _ => (),
-            },
-            _ => (),
+            }
         }
         std::io::Write::flush(&mut printer.writer.raw)?;
     }
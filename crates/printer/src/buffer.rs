@@ -123,9 +123,9 @@ impl Buffer {
     }
 }
 
-impl ToString for Buffer {
-    fn to_string(&self) -> String {
-        self.buffer.iter().collect()
+impl std::fmt::Display for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.buffer.iter().try_for_each(|c| write!(f, "{c}"))
     }
 }
 
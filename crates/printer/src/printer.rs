@@ -1,6 +1,11 @@
-use crossterm::{style::Color, terminal::ClearType};
+use crossterm::{
+    style::{Color, ResetColor, SetForegroundColor},
+    terminal::ClearType,
+};
+use std::io::Write;
 use std::ops::Range;
 use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{buffer::Buffer, Result};
 
@@ -15,6 +20,8 @@ pub struct Printer<W: std::io::Write> {
     pub writer: writer::Writer<W>,
     pub cursor: cursor::Cursor<W>,
     pub prompt: String,
+    /// Command used to page the outputs that don't fit in the terminal, exp: `less -R`
+    pub pager: Option<String>,
 }
 
 impl<W: std::io::Write> Printer<W> {
@@ -26,6 +33,7 @@ impl<W: std::io::Write> Printer<W> {
             writer: writer::Writer::new(raw.clone()),
             cursor: cursor::Cursor::new(raw, prompt_len),
             prompt,
+            pager: None,
         }
     }
 }
@@ -63,6 +71,68 @@ impl PrintQueue {
     pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
+
+    /// Number of terminal rows needed to print the queue with the given terminal width
+    pub fn height(&self, width: usize) -> usize {
+        let width = width.max(1);
+        let mut rows = 0;
+        let mut line_width = 0;
+        let add_str = |string: &str, line_width: &mut usize, rows: &mut usize| {
+            let mut lines = string.split('\n');
+            if let Some(first) = lines.next() {
+                *line_width += first.width();
+            }
+            for line in lines {
+                *rows += line_width.div_ceil(width).max(1);
+                *line_width = line.width();
+            }
+        };
+        for item in &self.items {
+            match item {
+                PrinterItem::Char(c, _) => line_width += c.width().unwrap_or(0),
+                PrinterItem::String(string, _) => add_str(string, &mut line_width, &mut rows),
+                PrinterItem::Str(string, _) => add_str(string, &mut line_width, &mut rows),
+                PrinterItem::RcString(string, range, _) => {
+                    add_str(&string[range.clone()], &mut line_width, &mut rows)
+                }
+                PrinterItem::NewLine => {
+                    rows += line_width.div_ceil(width).max(1);
+                    line_width = 0;
+                }
+            }
+        }
+        if line_width > 0 {
+            rows += line_width.div_ceil(width);
+        }
+        rows
+    }
+
+    /// Render the queue as text with ANSI colors
+    pub fn render(&self) -> String {
+        let mut rendered = String::new();
+        for item in &self.items {
+            let (string, color) = match item {
+                PrinterItem::Char(c, color) => (c.to_string(), *color),
+                PrinterItem::String(string, color) => (string.clone(), *color),
+                PrinterItem::Str(string, color) => (string.to_string(), *color),
+                PrinterItem::RcString(string, range, color) => {
+                    (string[range.clone()].to_string(), *color)
+                }
+                PrinterItem::NewLine => {
+                    rendered.push('\n');
+                    continue;
+                }
+            };
+            if !writer::no_color() {
+                rendered += &SetForegroundColor(color).to_string();
+            }
+            rendered += &string;
+        }
+        if !writer::no_color() {
+            rendered += &ResetColor.to_string();
+        }
+        rendered
+    }
 }
 
 impl Iterator for PrintQueue {
@@ -193,6 +263,16 @@ impl<W: std::io::Write> Printer<W> {
     }
 
     pub fn print_output(&mut self, printer: PrintQueue) -> Result<()> {
+        // Page the output if it doesn't fit in the terminal, print it inline if the pager fails
+        if let Some(pager) = self.pager.clone() {
+            if printer.height(self.cursor.width()) >= self.cursor.height()
+                && self.page(&pager, &printer.render()).is_ok()
+            {
+                self.readjust_cursor_pos()?;
+                return Ok(());
+            }
+        }
+
         for item in printer {
             match item {
                 PrinterItem::Char(c, color) => {
@@ -218,6 +298,32 @@ impl<W: std::io::Write> Printer<W> {
         Ok(())
     }
 
+    fn page(&mut self, pager: &str, output: &str) -> Result<()> {
+        let mut pager = pager.split_whitespace();
+        let mut cmd = std::process::Command::new(pager.next().ok_or("empty pager command")?);
+        cmd.args(pager).stdin(std::process::Stdio::piped());
+
+        self.writer.raw.flush()?;
+        // the pager handles the terminal itself
+        let _ = crossterm::terminal::disable_raw_mode();
+        let status = (|| -> Result<std::process::ExitStatus> {
+            let mut pager = cmd.spawn()?;
+            // the pager can exit before reading everything (exp: `q` in less)
+            let _ = pager
+                .stdin
+                .take()
+                .expect("stdin is piped")
+                .write_all(output.as_bytes());
+            Ok(pager.wait()?)
+        })();
+        let _ = crossterm::terminal::enable_raw_mode();
+
+        if !status?.success() {
+            return Err("the pager failed".into());
+        }
+        Ok(())
+    }
+
     fn print_out_str(&mut self, string: &str, color: Color) -> Result<()> {
         self.writer.raw.set_fg(color)?;
         self.writer.raw.write(&string.replace('\n', "\r\n"))?;
@@ -1,5 +1,5 @@
 use super::default_process_fn;
-use super::Printer;
+use super::{PrintQueue, Printer, PrinterItem};
 use crossterm::style::Color;
 use std::io::Write;
 
@@ -152,3 +152,44 @@ fn move_to_and_modify_start(printer: &mut Printer<impl Write>, x: usize, y: usiz
     printer.cursor.pos.starting_pos.1 = y;
     printer.cursor.goto_start();
 }
+
+#[test]
+fn height() {
+    let mut queue = PrintQueue::default();
+    queue.push(PrinterItem::Str("hello\nworld", Color::Red));
+    queue.add_new_line(1);
+    queue.push(PrinterItem::String("a".repeat(25), Color::Red));
+    assert_eq!(queue.height(10), 5);
+    assert_eq!(queue.height(100), 3);
+}
+
+#[test]
+fn large_output_is_paged() -> Result<()> {
+    let paged = std::env::temp_dir().join("printer_large_output_is_paged");
+    let _ = std::fs::remove_file(&paged);
+    let mut p = Printer::new(std::io::sink(), "".to_owned());
+    p.pager = Some(format!("cp /dev/stdin {}", paged.display()));
+
+    // fits in the terminal
+    p.print_output(PrinterItem::Str("small", Color::Red).into())?;
+    assert!(!paged.exists());
+
+    let mut queue = PrintQueue::default();
+    for i in 0..p.cursor.height() {
+        queue.push(PrinterItem::String(format!("line {i}"), Color::Red));
+        queue.add_new_line(1);
+    }
+    let rendered = queue.render();
+    p.print_output(queue)?;
+    assert_eq!(std::fs::read_to_string(&paged)?, rendered);
+    assert!(rendered.contains("line 0\n"));
+
+    // a failing pager falls back to inline printing
+    p.pager = Some("irust-no-such-pager".to_owned());
+    let mut queue = PrintQueue::default();
+    queue.add_new_line(p.cursor.height());
+    p.print_output(queue)?;
+
+    std::fs::remove_file(&paged)?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use crate::{buffer::Buffer, Result};
 use crossterm::{style::Color, terminal::ClearType};
 mod raw;
+pub(crate) use raw::no_color;
 use raw::Raw;
 use std::{cell::RefCell, rc::Rc};
 
@@ -3,7 +3,7 @@ use crossterm::{queue, style::*, terminal::*};
 use std::{cell::RefCell, fmt::Display, rc::Rc, sync::OnceLock};
 
 static NO_COLOR: OnceLock<bool> = OnceLock::new();
-fn no_color() -> bool {
+pub(crate) fn no_color() -> bool {
     *NO_COLOR.get_or_init(|| std::env::var("NO_COLOR").is_ok())
 }
 